@@ -0,0 +1,112 @@
+//! Programs execution benchmark: compiles examples (and heavy benchmark programs), runs each one
+//! in-process with and without decode cache and prints the speedup.
+//! Without decode cache every instruction is fetched and decoded from memory on each execution,
+//! like the dispatch loop worked before the cache was introduced.
+//!
+//! Usage: `cargo bench`
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use mvm::{assembly, vm};
+
+const ITERATIONS: u32 = 5;
+
+const PROGRAMS: &[&str] = &[
+    "examples/hello_world.asm",
+    "examples/strings_comparison.asm",
+    "examples/allocator_usage.asm",
+    "examples/interrupts.asm",
+    "benches/programs/counter.asm",
+];
+
+/// Compiled program with its memory settings from metadata
+struct Program {
+    code: Vec<u8>,
+    memsize: usize,
+    stacksize: usize,
+    constants_size: u64,
+}
+
+fn compile(source: &Path) -> Program {
+    let code = std::fs::read_to_string(source).expect("unable to read program");
+    let filename = source.display().to_string();
+
+    let (binary, _) = assembly::assemble(&filename, &code, true)
+        .unwrap_or_else(|errors| panic!("`{filename}` compilation failed:\n{}", errors.concat()));
+
+    // metadata: [memory size] [stack size] [constants size] 0xff
+    let metadata_end = binary
+        .windows(2)
+        .position(|window| window == [0xff, vm::Opcode::DataSection as u8])
+        .expect("compiled program has no metadata");
+
+    let number = |index: usize| {
+        let bytes = binary[index * 8..(index + 1) * 8].try_into();
+        u64::from_be_bytes(bytes.expect("8 bytes slice"))
+    };
+
+    Program {
+        memsize: number(0) as usize,
+        stacksize: number(1) as usize,
+        constants_size: number(2),
+        code: binary[metadata_end + 1..].to_vec(),
+    }
+}
+
+fn measure(name: &str, program: &Program, decode_cache: bool) -> Duration {
+    (0..ITERATIONS)
+        .map(|_| {
+            let mut vm = vm::VM::new(program.memsize, program.stacksize)
+                .unwrap_or_else(|err| panic!("`{name}`: unable to create VM [{err}]"));
+
+            // program output is captured to keep benchmark report clean
+            let (streams, _, _) = vm::Streams::captured(Vec::new());
+
+            vm.streams = streams;
+            vm.constants_size = program.constants_size;
+            vm.decode_cache.enabled = decode_cache;
+
+            vm.insert_program(&program.code)
+                .unwrap_or_else(|err| panic!("`{name}`: unable to load program [{err}]"));
+
+            let start = Instant::now();
+            let result = vm.run();
+            let elapsed = start.elapsed();
+
+            if let Err(err) = result {
+                panic!("`{name}` failed [{err}]");
+            }
+
+            assert_eq!(vm.exit_code, 0, "`{name}` exited with non-zero code");
+
+            elapsed
+        })
+        .min()
+        .unwrap_or_default()
+}
+
+fn main() {
+    println!(
+        "{:<24} {:>12} {:>12} {:>8}",
+        "program", "uncached", "cached", "speedup"
+    );
+
+    for program in PROGRAMS {
+        let source = Path::new(env!("CARGO_MANIFEST_DIR")).join(program);
+        let name = source.file_stem().unwrap_or_default().to_string_lossy();
+
+        let compiled = compile(&source);
+
+        let uncached = measure(&name, &compiled, false);
+        let cached = measure(&name, &compiled, true);
+
+        println!(
+            "{:<24} {:>12.2?} {:>12.2?} {:>7.2}x",
+            name,
+            uncached,
+            cached,
+            uncached.as_secs_f64() / cached.as_secs_f64()
+        );
+    }
+}
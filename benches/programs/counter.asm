@@ -0,0 +1,19 @@
+; Counter Benchmark
+; Tight loop of arithmetics, comparison and jump instructions.
+
+section .data
+section .text
+entry _start
+
+_start:
+  mov %r0, $0
+  mov %r1, $5000000
+
+loop:
+  add %r0, $1
+  cmp %r0, %r1
+  jnz loop
+
+  mov %r0, $0
+  mov %call, $sys_exit
+  int $int_syscall
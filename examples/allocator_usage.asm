@@ -1,4 +1,9 @@
 ; MVM Allocator Example
+;
+; expect-stderr: [DEBUG] %accumulator = 309
+; expect-stderr: [DEBUG] %r0 = 123
+; expect-stderr: [DEBUG] %r0 = 123
+; expect-stderr: [DEBUG] %r0 = 0
 
 section .data
 section .text
@@ -21,6 +26,16 @@ fn_free:
   int $int_syscall
   ret
 
+fn_realloc:
+  mov %call, $sys_realloc
+  int $int_syscall
+  ret
+
+fn_calloc:
+  mov %call, $sys_calloc
+  int $int_syscall
+  ret
+
 
 ; ===| Program Entrypoint |===
 
@@ -40,11 +55,32 @@ _start:
   load64 %r0, %accumulator ; ---|> now loading it to verify
   dbg %r0 ; --------------------|> the value from memory.
 
+  ; realloc
+
+  mov %r0, %accumulator ; ------|> growing allocated memory
+  mov %r1, $16 ; ---------------|> to 16 bytes (data is kept)
+  call fn_realloc
+
+  load64 %r0, %accumulator
+  dbg %r0
+
   ; free
 
   mov %r0, %accumulator ; ------|> dropping allocated memory
   call fn_free ; ---------------|> by its pointer
 
+  ; calloc
+
+  mov %r0, $4 ; ----------------|> 4 zeroed 64 bit numbers
+  mov %r1, $8
+  call fn_calloc
+
+  load64 %r0, %accumulator
+  dbg %r0
+
+  mov %r0, %accumulator
+  call fn_free ; ---------------|> by its pointer
+
   ; exit
   mov %r0, $0
   call fn_exit
@@ -0,0 +1,70 @@
+; Program Arguments Example
+; Prints every argument on a new line: `mvm run args.mvm -- hello world`
+;
+; args: hello world
+; expect-stdout: args.mvm
+; expect-stdout: hello
+; expect-stdout: world
+
+section .data
+  newline:
+    ascii "\n"
+
+section .text
+entry _start
+
+; ===| Functions |===
+
+print_cstr:
+  ; -- prints null-terminated string from %r0 --
+
+  mov %r1, %r0 ; void* buffer
+  mov %r2, $0 ; string length
+
+strlen_loop:
+  load8 %r3, %r0
+  cmp %r3, $0
+  jz strlen_end
+
+  add %r0, $1
+  add %r2, $1
+  jmp strlen_loop
+
+strlen_end:
+  mov %r0, $1 ; stdout
+  mov %call, $sys_write
+  int $int_syscall
+
+  mov %r0, $1
+  mov %r1, newline
+  add %r1, $8
+  mov %r2, $1
+  mov %call, $sys_write
+  int $int_syscall
+
+  ret
+
+; ===| Program Entrypoint |===
+
+_start:
+  ; -- argc and argv are also placed to %r0 and %r1 at startup --
+
+  mov %call, $sys_argv
+  int $int_syscall
+
+  mov %r5, %accumulator ; char** argv
+
+args_loop:
+  load64 %r0, %r5
+  cmp %r0, $0
+  jz exit
+
+  call print_cstr
+
+  add %r5, $8
+  jmp args_loop
+
+exit:
+  mov %r0, $0
+  mov %call, $sys_exit
+  int $int_syscall
@@ -0,0 +1,60 @@
+; Indirect Calls Example
+; Function address is loaded with `lea` and passed as callback to another function.
+;
+; expect-stdout: Hello from callback!
+; expect-stdout: Hello from callback!
+
+section .data
+  hello:
+    ascii "Hello from callback!\n"
+  hello_len:
+    [. - hello]
+
+section .text
+entry _start
+
+; ===| Functions |===
+
+print_hello:
+  mov %r0, $1
+  mov %r1, hello
+  add %r1, $8
+  mov %r2, hello_len
+  sub %r2, $8
+
+  mov %call, $sys_write
+  int $int_syscall
+
+  ret
+
+; void repeat(void (*callback)(), u64 times)
+repeat:
+  ; r6 and r7 are callee-saved, callback can change r0 .. r5
+  push64 %r6
+  push64 %r7
+
+  mov %r6, %r0
+  mov %r7, %r1
+
+repeat_loop:
+  call %r6
+
+  sub %r7, $1
+  cmp %r7, $0
+  jnz repeat_loop
+
+  pop64 %r7
+  pop64 %r6
+
+  ret
+
+; ===| Program Entrypoint |===
+
+_start:
+  lea %r0, print_hello
+  mov %r1, $2
+  call repeat
+
+  mov %r0, $0
+  mov %call, $sys_exit
+  int $int_syscall
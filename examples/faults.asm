@@ -0,0 +1,47 @@
+; Faults Trapping Example
+; Program installs fault handler, divides by zero and exits with fault kind as code.
+;
+; expect-stdout: Fault trapped, exiting with its kind
+; expect-exit: 1
+
+section .data
+  message:
+    ascii "Fault trapped, exiting with its kind\n"
+  message_len:
+    [. - message]
+
+section .text
+entry _start
+
+; ===| Fault Handler |===
+
+; %r0 - fault kind
+; %r1 - faulting instruction address
+; %r2 - fault details
+fault_handler:
+  mov %r3, %r0
+
+  mov %r0, $1
+  mov %r1, message
+  add %r1, $8
+  mov %r2, message_len
+  sub %r2, $8
+
+  mov %call, $sys_write
+  int $int_syscall
+
+  mov %r0, %r3
+  mov %call, $sys_exit
+  int $int_syscall
+
+; ===| Program Entrypoint |===
+
+_start:
+  setvec $int_fault, fault_handler
+
+  mov %r0, $10
+  div %r0, $0
+
+  mov %r0, $0
+  mov %call, $sys_exit
+  int $int_syscall
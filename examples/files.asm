@@ -0,0 +1,107 @@
+; Filesystem Example
+; Run it with sandbox directory: `mvm run files.mvm --fs-root .`
+;
+; run-flags: --fs-root .
+; expect-stdout: Hello from file!
+
+section .data
+  path:
+    ascii "hello.txt"
+  path_len:
+    [. - path]
+
+  message:
+    ascii "Hello from file!\n"
+  message_len:
+    [. - message]
+
+  buffer:
+    [.] ; buffer address (like in `ascii` directive)
+    [$0] [$0] [$0] [$0]
+
+section .text
+entry _start
+
+; ===| Functions |===
+
+fn_syscall:
+  int $int_syscall
+  ret
+
+; ===| Program Entrypoint |===
+
+_start:
+  ; -- i64 open(char* path, u64 path_len, u64 flags) --
+
+  mov %r0, path
+  add %r0, $8
+  mov %r1, path_len
+  sub %r1, $8 ; length without address prefix
+  mov %r2, $6 ; write (0b010) | create (0b100)
+
+  mov %call, $sys_open
+  call fn_syscall
+
+  ; -- i64 fwrite(u64 fd, void* buffer, u64 len) --
+
+  mov %r5, %accumulator ; keeping file descriptor
+
+  mov %r0, %r5
+  mov %r1, message
+  add %r1, $8
+  mov %r2, message_len
+  sub %r2, $8
+
+  mov %call, $sys_fwrite
+  call fn_syscall
+
+  ; -- i64 close(u64 fd) --
+
+  mov %r0, %r5
+  mov %call, $sys_close
+  call fn_syscall
+
+  ; -- reading file back --
+
+  mov %r0, path
+  add %r0, $8
+  mov %r1, path_len
+  sub %r1, $8 ; length without address prefix
+  mov %r2, $1 ; read (0b001)
+
+  mov %call, $sys_open
+  call fn_syscall
+
+  mov %r5, %accumulator
+
+  mov %r0, %r5
+  mov %r1, buffer
+  add %r1, $8
+  mov %r2, $32
+
+  mov %call, $sys_fread
+  call fn_syscall
+
+  ; -- printing read bytes to stdout --
+
+  mov %r2, %accumulator
+  mov %r0, $1
+  mov %r1, buffer
+  add %r1, $8
+
+  mov %call, $sys_write
+  call fn_syscall
+
+  ; -- removing file --
+
+  mov %r0, path
+  add %r0, $8
+  mov %r1, path_len
+  sub %r1, $8 ; length without address prefix
+
+  mov %call, $sys_unlink
+  call fn_syscall
+
+  mov %r0, $0
+  mov %call, $sys_exit
+  call fn_syscall
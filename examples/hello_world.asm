@@ -1,4 +1,6 @@
 ; Hello World Example
+;
+; expect-stdout: Hello, World!
 
 section .data
   hello:
@@ -16,6 +18,7 @@ print:
   ; -- syscall void write(int output, void *buffer, size_t length) --
 
   mov %r2, %r1 ; size_t length (length variable)
+  sub %r2, $8 ; length without address offset
 
   mov %r1, %r0 ; void* buffer (ptr to message)
   add %r1, $8 ; string address offset
@@ -0,0 +1,42 @@
+; User Interrupt Handlers Example
+; Program installs its own handler for interrupt 32 and calls it twice.
+;
+; expect-stdout: Hello from interrupt handler!
+; expect-stdout: Hello from interrupt handler!
+
+section .data
+  message:
+    ascii "Hello from interrupt handler!\n"
+  message_len:
+    [. - message]
+
+section .text
+entry _start
+
+; ===| Interrupt Handlers |===
+
+int32_handler:
+  ; registers (including accumulator) will be restored by `iret`
+
+  mov %r0, $1
+  mov %r1, message
+  add %r1, $8
+  mov %r2, message_len
+  sub %r2, $8
+
+  mov %call, $sys_write
+  int $int_syscall
+
+  iret
+
+; ===| Program Entrypoint |===
+
+_start:
+  setvec $32, int32_handler
+
+  int $32
+  int $32
+
+  mov %r0, $0
+  mov %call, $sys_exit
+  int $int_syscall
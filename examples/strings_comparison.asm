@@ -1,13 +1,18 @@
-; Strings manual comparison.
+; Strings comparison with `memcmp` instruction.
 ; Prints "not equals" if strings are different.
 ; NOTE: Try to change values in `str1` and `str2` and see how it works
+;
+; expect-stdout: equals!
 
 section .data
   str1:
     ascii "hello"
+  str1_len:
+    [. - str1]
+
   str2:
     ascii "hello"
-  len:
+  str2_len:
     [. - str2]
 
   ne_str:
@@ -24,50 +29,28 @@ entry _start
 
 _start:
   ; setting pointers to strings starts
-  
+
   mov %r0, str1
   mov %r1, str2
 
   add %r0, $8 ; u64 address offset (because of ascii directive)
   add %r1, $8 ; -------------------|
 
-  jmp cmp_loop
-
-cmp_loop:
-  ; loading characters from pointers
-
-  load8 %r3, %r0
-  load8 %r4, %r1
+  ; strings with different lengths are never equal
 
-  ; comparing characters
-
-  cmp %r3, %r4
-
-  ; if not equals (not 0) exit with output
+  mov %r2, str1_len
+  mov %r3, str2_len
 
+  cmp %r2, %r3
   jnz print_ne
 
-  ; incrementing pointers
-
-  add %r0, $1
-  add %r1, $1
+  ; comparing all characters at once
 
-  ; checking for null terminator
-
-  load8 %r3, %r0
-  load8 %r4, %r1
-
-  mov %accumulator, $0
-
-  cmp %r3, %accumulator
-  jz print_eq
-
-  cmp %r3, %accumulator
-  jz print_eq
-
-  ; returning to loop
+  sub %r2, $8 ; length without address offset
+  memcmp %r0, %r1, %r2
+  jnz print_ne
 
-  jmp cmp_loop
+  jmp print_eq
 
 print_eq:
   mov %r0, eq_str
@@ -87,6 +70,7 @@ print:
   ; -- syscall void write(int output, void *buffer, size_t length) --
 
   mov %r2, %r1 ; size_t length (length variable)
+  sub %r2, $8 ; length without address offset
 
   mov %r1, %r0 ; void* buffer (ptr to message)
   add %r1, $8 ; string address offset
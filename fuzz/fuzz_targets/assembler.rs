@@ -0,0 +1,16 @@
+//! Runs assembler stages (lexer, parser, semantic analyzer and code generator) on arbitrary source.
+//! Any input must be either compiled or rejected with errors, but never panic.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mvm::assembly;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(source) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    let _ = assembly::assemble("FUZZ", source, false);
+    let _ = assembly::assemble("FUZZ", source, true);
+});
@@ -0,0 +1,55 @@
+//! Verifies and executes arbitrary bytecode. Every malformed program must end with `MvmError`
+//! (or be handled by program fault handlers), but never panic.
+//! First byte of input selects machine options, the rest is a program (without metadata).
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mvm::{MEMSIZE_DEFAULT, STACKSIZE_DEFAULT, vm};
+
+// infinite loops are valid programs, so execution is limited
+const STEPS_LIMIT: usize = 10_000;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&options, program)) = data.split_first() else {
+        return;
+    };
+
+    let _ = vm::verify(program);
+
+    let memsize = MEMSIZE_DEFAULT * 4;
+    let Ok(mut vm) = vm::VM::new(memsize, STACKSIZE_DEFAULT) else {
+        return;
+    };
+
+    let (streams, _, _) = vm::Streams::captured(Vec::new());
+    vm.streams = streams;
+
+    vm.self_modifying = options & 1 != 0;
+    vm.no_exec_data = options & 2 != 0;
+    vm.decode_cache.enabled = options & 4 == 0;
+    vm.heap_check.enabled = options & 8 != 0;
+    vm.profiler.enabled = options & 16 != 0;
+    vm.coverage.enabled = options & 32 != 0;
+
+    if options & 64 != 0 {
+        vm.memory_limit = Some(memsize * 4);
+    }
+
+    if options & 128 != 0 {
+        vm.arguments = vec![String::from("fuzz"), String::from("argument")];
+        vm.environment = vec![String::from("KEY=VALUE")];
+    }
+
+    if vm.insert_program(program).is_err() {
+        return;
+    }
+
+    vm.running = true;
+
+    for _ in 0..STEPS_LIMIT {
+        if !vm.running || vm.step().is_err() {
+            break;
+        }
+    }
+});
@@ -0,0 +1,8 @@
+section .data
+  a:
+    [b - a]
+  b:
+    $1
+section .text
+f:
+  halt
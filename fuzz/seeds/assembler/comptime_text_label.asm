@@ -0,0 +1,6 @@
+section .data
+  fp:
+    [func]
+section .text
+func:
+  halt
@@ -0,0 +1,4 @@
+section .data
+section .text
+_start:
+  [%r1 + $8]
@@ -17,6 +17,11 @@ pub struct Codegen {
     constants: HashMap<String, Constant>,
     constants_refs: HashMap<u64, String>,
 
+    /// Resolved text section labels (sorted by address) for debug info file
+    symbols: Vec<(u64, String)>,
+    /// Text section instructions addresses with source offsets
+    instructions: Vec<(u64, usize)>,
+
     output: Vec<u8>,
 }
 
@@ -33,6 +38,9 @@ impl Codegen {
             constants: HashMap::new(),
             constants_refs: HashMap::new(),
 
+            symbols: Vec::new(),
+            instructions: Vec::new(),
+
             output: Vec::new(),
         }
     }
@@ -49,14 +57,14 @@ impl Codegen {
         let mut relative_constants_pointers: HashMap<String, u64> = HashMap::new();
         let mut constants_slice: Vec<u8> = Vec::new();
 
-        for constant in &self.constants {
+        for (id, constant) in &self.constants {
             let ptr = constants_slice.len() as u64;
 
-            match constant.1 {
+            match constant {
                 Constant::U8(value) => {
                     constants_slice.push(*value);
 
-                    relative_constants_pointers.insert(value.to_string(), ptr);
+                    relative_constants_pointers.insert(id.clone(), ptr);
                 }
 
                 Constant::U16(value) => {
@@ -65,7 +73,7 @@ impl Codegen {
                     constants_slice.push(bytes[0]);
                     constants_slice.push(bytes[1]);
 
-                    relative_constants_pointers.insert(value.to_string(), ptr);
+                    relative_constants_pointers.insert(id.clone(), ptr);
                 }
 
                 Constant::U32(value) => {
@@ -76,7 +84,7 @@ impl Codegen {
                     constants_slice.push(bytes[2]);
                     constants_slice.push(bytes[3]);
 
-                    relative_constants_pointers.insert(value.to_string(), ptr);
+                    relative_constants_pointers.insert(id.clone(), ptr);
                 }
 
                 Constant::U64(value) => {
@@ -92,7 +100,7 @@ impl Codegen {
                     constants_slice.push(bytes[6]);
                     constants_slice.push(bytes[7]);
 
-                    relative_constants_pointers.insert(value.to_string(), ptr);
+                    relative_constants_pointers.insert(id.clone(), ptr);
                 }
             }
         }
@@ -112,6 +120,7 @@ impl Codegen {
         }
 
         let text_section_offset = constants_slice.len() as u64;
+        let constants_size = text_section_offset;
 
         self.output.append(&mut constants_slice);
 
@@ -138,6 +147,17 @@ impl Codegen {
             labels.insert(id, Label::new(ptr, label.data_section));
         }
 
+        self.symbols = labels
+            .iter()
+            .filter(|(_, label)| !label.data_section)
+            .map(|(id, label)| (label.ptr, id.to_string()))
+            .collect();
+        self.symbols.sort();
+
+        for (ptr, _) in &mut self.instructions {
+            *ptr += text_section_offset;
+        }
+
         // merging data and text section
 
         self.output.append(&mut text_section);
@@ -192,7 +212,12 @@ impl Codegen {
             stack_size
         };
 
-        let metadata = [mem_size.to_be_bytes(), stack_size.to_be_bytes()].concat();
+        let metadata = [
+            mem_size.to_be_bytes(),
+            stack_size.to_be_bytes(),
+            constants_size.to_be_bytes(),
+        ]
+        .concat();
         self.output = [metadata, vec![0xFF], self.output.clone()].concat();
 
         &self.output
@@ -205,6 +230,13 @@ impl Codegen {
         self.output.push(byte);
     }
 
+    fn push_u64(&mut self, value: u64) {
+        value
+            .to_be_bytes()
+            .into_iter()
+            .for_each(|byte| self.push_byte(byte));
+    }
+
     fn add_constant(&mut self, id: String, constant: Constant) {
         if let Some(prev) = self.constants.get(&id) {
             if prev < &constant {
@@ -223,6 +255,7 @@ impl Codegen {
             "syscall" => 80,
             "int_syscall" => 80,
             "int_accinc" => 0,
+            "int_fault" => 1,
 
             // syscalls
             "sys_exit" => 0,
@@ -230,9 +263,158 @@ impl Codegen {
             "sys_write" => 2,
             "sys_alloc" => 3,
             "sys_free" => 4,
+            "sys_open" => 5,
+            "sys_close" => 6,
+            "sys_fread" => 7,
+            "sys_fwrite" => 8,
+            "sys_seek" => 9,
+            "sys_stat" => 10,
+            "sys_unlink" => 11,
+            "sys_readdir" => 12,
+            "sys_argc" => 13,
+            "sys_argv" => 14,
+            "sys_envp" => 15,
+            "sys_sbrk" => 16,
+            "sys_realloc" => 17,
+            "sys_calloc" => 18,
+            "sys_memstats" => 19,
+
+            _ => unreachable!("lexer accepts only known asm constants"),
+        }
+    }
+
+    /// Compiles `load` with register, `[%base + $disp]` or `[%base + %index * $scale]` address.
+    /// Opcodes are ordered as: register, displacement, indexed.
+    fn compile_load(&mut self, opcodes: [Opcode; 3], args: &[Expression]) {
+        let [register_opcode, offset_opcode, indexed_opcode] = opcodes;
+
+        let dest = args.first().unwrap();
+        let address = args.get(1).unwrap();
+
+        match address {
+            Expression::MemoryOperand {
+                base,
+                index: Some(index),
+                scale,
+                displacement: _,
+                span: _,
+            } => {
+                self.push_byte(indexed_opcode as u8);
+                self.compile_expr(dest);
+                self.compile_expr(base);
+                self.compile_expr(index);
+                self.push_byte(Self::memory_operand_value(scale.as_deref(), 1) as u8);
+            }
+
+            Expression::MemoryOperand {
+                base, displacement, ..
+            } => {
+                self.push_byte(offset_opcode as u8);
+                self.compile_expr(dest);
+                self.compile_expr(base);
+                self.push_u64(Self::memory_operand_value(displacement.as_deref(), 0));
+            }
+
+            _ => {
+                self.push_byte(register_opcode as u8);
+                self.compile_expr(dest);
+                self.compile_expr(address);
+            }
+        }
+    }
+
+    /// Compiles `store` with register, `[%base + $disp]` or `[%base + %index * $scale]` address.
+    /// Opcodes are ordered as: register, displacement, indexed.
+    fn compile_store(&mut self, opcodes: [Opcode; 3], args: &[Expression]) {
+        let [register_opcode, offset_opcode, indexed_opcode] = opcodes;
+
+        let address = args.first().unwrap();
+        let src = args.get(1).unwrap();
+
+        match address {
+            Expression::MemoryOperand {
+                base,
+                index: Some(index),
+                scale,
+                displacement: _,
+                span: _,
+            } => {
+                self.push_byte(indexed_opcode as u8);
+                self.compile_expr(base);
+                self.compile_expr(index);
+                self.push_byte(Self::memory_operand_value(scale.as_deref(), 1) as u8);
+                self.compile_expr(src);
+            }
+
+            Expression::MemoryOperand {
+                base, displacement, ..
+            } => {
+                self.push_byte(offset_opcode as u8);
+                self.compile_expr(base);
+                self.push_u64(Self::memory_operand_value(displacement.as_deref(), 0));
+                self.compile_expr(src);
+            }
+
+            _ => {
+                self.push_byte(register_opcode as u8);
+                self.compile_expr(address);
+                self.compile_expr(src);
+            }
+        }
+    }
+
+    /// Memory operand scale and displacement are encoded inline (not in constants pool)
+    fn memory_operand_value(expr: Option<&Expression>, default: u64) -> u64 {
+        match expr {
+            Some(Expression::UIntConstant(value, _)) => *value,
+            Some(Expression::AsmConstant(name, _)) => Self::get_asm_constant(name),
+            _ => default,
+        }
+    }
+
+    /// Compiles constant operand, which is read by VM with fixed width (not the minimal one)
+    fn compile_fixed_constant(&mut self, expr: &Expression, constant: fn(u64) -> Constant) {
+        let value = match expr {
+            Expression::UIntConstant(value, _) => *value,
+            Expression::AsmConstant(name, _) => Self::get_asm_constant(name),
+            _ => return self.compile_expr(expr),
+        };
+
+        let constant = constant(value);
+        let id = match constant {
+            Constant::U8(_) => format!("{value}:u8"),
+            Constant::U16(_) => format!("{value}:u16"),
+            Constant::U32(_) => format!("{value}:u32"),
+            Constant::U64(_) => format!("{value}:u64"),
+        };
+
+        self.add_constant(id.clone(), constant);
+        self.constants_refs.insert(self.pc, id);
+
+        // 64 bit address number
+
+        self.push_u64(0);
+    }
 
-            _ => unreachable!(),
+    /// Debug info file content: `source <path>` line, `binary <checksum>` line (hex),
+    /// `label <address> <name>` line for each text label and `line <address> <line>` line
+    /// for each text instruction
+    pub fn debug_info(&self, source_path: &str, source: &str) -> String {
+        let mut info = format!(
+            "source {source_path}\nbinary {:x}\n",
+            crate::vm::DebugInfo::checksum(&self.output)
+        );
+
+        for (ptr, id) in &self.symbols {
+            info.push_str(&format!("label {ptr} {id}\n"));
+        }
+
+        for (ptr, offset) in &self.instructions {
+            let line = source[..*offset].matches('\n').count() + 1;
+            info.push_str(&format!("line {ptr} {line}\n"));
         }
+
+        info
     }
 
     fn compile_expr(&mut self, expr: &Expression) {
@@ -278,27 +460,27 @@ impl Codegen {
                 span: _,
             } => match directive.as_str() {
                 "ascii" => {
-                    assert_eq!(args.len(), 1);
+                    let [Expression::StringConstant(string, _)] = args.as_slice() else {
+                        unreachable!("parser gives `ascii` only one string argument");
+                    };
 
-                    if let Some(Expression::StringConstant(string, _)) = args.first() {
-                        let str_bytes = string.bytes();
-                        let addr_bytes = self.pc.to_be_bytes();
+                    let str_bytes = string.bytes();
+                    let addr_bytes = self.pc.to_be_bytes();
 
-                        self.push_byte(addr_bytes[0]);
-                        self.push_byte(addr_bytes[1]);
-                        self.push_byte(addr_bytes[2]);
-                        self.push_byte(addr_bytes[3]);
+                    self.push_byte(addr_bytes[0]);
+                    self.push_byte(addr_bytes[1]);
+                    self.push_byte(addr_bytes[2]);
+                    self.push_byte(addr_bytes[3]);
 
-                        self.push_byte(addr_bytes[4]);
-                        self.push_byte(addr_bytes[5]);
-                        self.push_byte(addr_bytes[6]);
-                        self.push_byte(addr_bytes[7]);
+                    self.push_byte(addr_bytes[4]);
+                    self.push_byte(addr_bytes[5]);
+                    self.push_byte(addr_bytes[6]);
+                    self.push_byte(addr_bytes[7]);
 
-                        str_bytes.for_each(|byte| self.push_byte(byte));
-                    }
+                    str_bytes.for_each(|byte| self.push_byte(byte));
                 }
 
-                _ => unimplemented!(),
+                _ => unreachable!("parser creates only `ascii` directive"),
             },
 
             Expression::ComptimeExpr { expr, span: _ } => {
@@ -316,407 +498,13 @@ impl Codegen {
                 self.push_byte(bytes[7]);
             }
 
-            Expression::Instruction {
-                name,
-                args,
-                span: _,
-            } => {
-                match name.as_str() {
-                    "halt" => {
-                        self.push_byte(Opcode::Halt as u8);
-                    }
-
-                    "ret" => {
-                        self.push_byte(Opcode::Return as u8);
-                    }
-
-                    "call" => {
-                        self.push_byte(Opcode::Call as u8);
-                        self.compile_expr(args.first().unwrap());
-                    }
-
-                    "int" => {
-                        self.push_byte(Opcode::Interrupt as u8);
-                        self.compile_expr(args.first().unwrap());
-                    }
-
-                    "dbg" => {
-                        if !self.release {
-                            self.push_byte(Opcode::Debug as u8);
-                            self.compile_expr(args.first().unwrap());
-                        }
-                    }
-
-                    "mov" => {
-                        // mov %dest, ...
-                        if let Some(Expression::AsmReg(_, _)) = args.first() {
-                            match args.get(1) {
-                                Some(Expression::UIntConstant(value, _)) => {
-                                    let constant = Constant::new(*value);
-
-                                    match constant {
-                                        Constant::U8(_) => self.push_byte(Opcode::Mov8 as u8),
-                                        Constant::U16(_) => self.push_byte(Opcode::Mov16 as u8),
-                                        Constant::U32(_) => self.push_byte(Opcode::Mov32 as u8),
-                                        Constant::U64(_) => self.push_byte(Opcode::Mov64 as u8),
-                                    }
-
-                                    self.compile_expr(args.first().unwrap());
-                                    self.compile_expr(args.get(1).unwrap());
-                                }
-
-                                Some(Expression::AsmReg(_, _)) => {
-                                    self.push_byte(Opcode::MovR2R as u8);
-                                    self.compile_expr(args.first().unwrap());
-                                    self.compile_expr(args.get(1).unwrap());
-                                }
-
-                                Some(Expression::LabelRef(_, _)) => {
-                                    self.push_byte(Opcode::Mov64 as u8);
-                                    self.compile_expr(args.first().unwrap());
-                                    self.compile_expr(args.get(1).unwrap());
-                                }
-
-                                Some(Expression::AsmConstant(id, _)) => {
-                                    let asm_const = Self::get_asm_constant(id);
-                                    let constant = Constant::new(asm_const);
-
-                                    match constant {
-                                        Constant::U8(_) => self.push_byte(Opcode::Mov8 as u8),
-                                        Constant::U16(_) => self.push_byte(Opcode::Mov16 as u8),
-                                        Constant::U32(_) => self.push_byte(Opcode::Mov32 as u8),
-                                        Constant::U64(_) => self.push_byte(Opcode::Mov64 as u8),
-                                    }
-
-                                    self.compile_expr(args.first().unwrap());
-                                    self.compile_expr(args.get(1).unwrap());
-                                }
-
-                                _ => unreachable!(),
-                            }
-                        }
-                    }
-
-                    "add" => match args.get(1) {
-                        Some(Expression::UIntConstant(value, _)) => {
-                            let constant = Constant::new(*value);
-
-                            match constant {
-                                Constant::U8(_) => self.push_byte(Opcode::Add8 as u8),
-                                Constant::U16(_) => self.push_byte(Opcode::Add16 as u8),
-                                Constant::U32(_) => self.push_byte(Opcode::Add32 as u8),
-                                Constant::U64(_) => self.push_byte(Opcode::Add64 as u8),
-                            }
-
-                            self.compile_expr(args.first().unwrap());
-                            self.compile_expr(args.get(1).unwrap());
-                        }
-
-                        Some(Expression::AsmReg(_, _)) => {
-                            self.push_byte(Opcode::AddR2R as u8);
-                            self.compile_expr(args.first().unwrap());
-                            self.compile_expr(args.get(1).unwrap());
-                        }
-
-                        Some(Expression::LabelRef(_, _)) => {
-                            self.push_byte(Opcode::Add64 as u8);
-                            self.compile_expr(args.first().unwrap());
-                            self.compile_expr(args.get(1).unwrap());
-                        }
-
-                        _ => unreachable!(),
-                    },
-
-                    "sub" => match args.get(1) {
-                        Some(Expression::UIntConstant(value, _)) => {
-                            let constant = Constant::new(*value);
-
-                            match constant {
-                                Constant::U8(_) => self.push_byte(Opcode::Sub8 as u8),
-                                Constant::U16(_) => self.push_byte(Opcode::Sub16 as u8),
-                                Constant::U32(_) => self.push_byte(Opcode::Sub32 as u8),
-                                Constant::U64(_) => self.push_byte(Opcode::Sub64 as u8),
-                            }
-
-                            self.compile_expr(args.first().unwrap());
-                            self.compile_expr(args.get(1).unwrap());
-                        }
-
-                        Some(Expression::AsmReg(_, _)) => {
-                            self.push_byte(Opcode::SubR2R as u8);
-                            self.compile_expr(args.first().unwrap());
-                            self.compile_expr(args.get(1).unwrap());
-                        }
-
-                        Some(Expression::LabelRef(_, _)) => {
-                            self.push_byte(Opcode::Sub64 as u8);
-                            self.compile_expr(args.first().unwrap());
-                            self.compile_expr(args.get(1).unwrap());
-                        }
-
-                        _ => unreachable!(),
-                    },
-
-                    "mul" => match args.get(1) {
-                        Some(Expression::UIntConstant(value, _)) => {
-                            let constant = Constant::new(*value);
-
-                            match constant {
-                                Constant::U8(_) => self.push_byte(Opcode::Mul8 as u8),
-                                Constant::U16(_) => self.push_byte(Opcode::Mul16 as u8),
-                                Constant::U32(_) => self.push_byte(Opcode::Mul32 as u8),
-                                Constant::U64(_) => self.push_byte(Opcode::Mul64 as u8),
-                            }
-
-                            self.compile_expr(args.first().unwrap());
-                            self.compile_expr(args.get(1).unwrap());
-                        }
-
-                        Some(Expression::AsmReg(_, _)) => {
-                            self.push_byte(Opcode::MulR2R as u8);
-                            self.compile_expr(args.first().unwrap());
-                            self.compile_expr(args.get(1).unwrap());
-                        }
-
-                        Some(Expression::LabelRef(_, _)) => {
-                            self.push_byte(Opcode::Mul64 as u8);
-                            self.compile_expr(args.first().unwrap());
-                            self.compile_expr(args.get(1).unwrap());
-                        }
-
-                        _ => unreachable!(),
-                    },
-
-                    "div" => match args.get(1) {
-                        Some(Expression::UIntConstant(value, _)) => {
-                            let constant = Constant::new(*value);
-
-                            match constant {
-                                Constant::U8(_) => self.push_byte(Opcode::Div8 as u8),
-                                Constant::U16(_) => self.push_byte(Opcode::Div16 as u8),
-                                Constant::U32(_) => self.push_byte(Opcode::Div32 as u8),
-                                Constant::U64(_) => self.push_byte(Opcode::Div64 as u8),
-                            }
-
-                            self.compile_expr(args.first().unwrap());
-                            self.compile_expr(args.get(1).unwrap());
-                        }
-
-                        Some(Expression::AsmReg(_, _)) => {
-                            self.push_byte(Opcode::DivR2R as u8);
-                            self.compile_expr(args.first().unwrap());
-                            self.compile_expr(args.get(1).unwrap());
-                        }
-
-                        Some(Expression::LabelRef(_, _)) => {
-                            self.push_byte(Opcode::Div64 as u8);
-                            self.compile_expr(args.first().unwrap());
-                            self.compile_expr(args.get(1).unwrap());
-                        }
-
-                        _ => unreachable!(),
-                    },
-
-                    "cmp" => match args.get(1) {
-                        Some(Expression::UIntConstant(value, _)) => {
-                            let constant = Constant::new(*value);
-
-                            match constant {
-                                Constant::U8(_) => self.push_byte(Opcode::Cmp8 as u8),
-                                Constant::U16(_) => self.push_byte(Opcode::Cmp16 as u8),
-                                Constant::U32(_) => self.push_byte(Opcode::Cmp32 as u8),
-                                Constant::U64(_) => self.push_byte(Opcode::Cmp64 as u8),
-                            }
-
-                            self.compile_expr(args.first().unwrap());
-                            self.compile_expr(args.get(1).unwrap());
-                        }
-
-                        Some(Expression::AsmReg(_, _)) => {
-                            self.push_byte(Opcode::CmpR2R as u8);
-                            self.compile_expr(args.first().unwrap());
-                            self.compile_expr(args.get(1).unwrap());
-                        }
-
-                        Some(Expression::LabelRef(_, _)) => {
-                            self.push_byte(Opcode::Cmp64 as u8);
-                            self.compile_expr(args.first().unwrap());
-                            self.compile_expr(args.get(1).unwrap());
-                        }
-
-                        _ => unreachable!(),
-                    },
-
-                    "xadd" => {
-                        self.push_byte(Opcode::XAdd as u8);
-                        self.compile_expr(args.first().unwrap());
-                        self.compile_expr(args.get(1).unwrap());
-                    }
-
-                    "push8" => {
-                        self.push_byte(Opcode::Push8 as u8);
-                        self.compile_expr(args.first().unwrap());
-                    }
-
-                    "push16" => {
-                        self.push_byte(Opcode::Push16 as u8);
-                        self.compile_expr(args.first().unwrap());
-                    }
-
-                    "push32" => {
-                        self.push_byte(Opcode::Push32 as u8);
-                        self.compile_expr(args.first().unwrap());
-                    }
-
-                    "push64" => {
-                        self.push_byte(Opcode::Push64 as u8);
-                        self.compile_expr(args.first().unwrap());
-                    }
-
-                    "pop8" => {
-                        self.push_byte(Opcode::Pop8 as u8);
-                        self.compile_expr(args.first().unwrap());
-                    }
-
-                    "pop16" => {
-                        self.push_byte(Opcode::Pop16 as u8);
-                        self.compile_expr(args.first().unwrap());
-                    }
-
-                    "pop32" => {
-                        self.push_byte(Opcode::Pop32 as u8);
-                        self.compile_expr(args.first().unwrap());
-                    }
-
-                    "pop64" => {
-                        self.push_byte(Opcode::Pop64 as u8);
-                        self.compile_expr(args.first().unwrap());
-                    }
-
-                    "frame8" => {
-                        self.push_byte(Opcode::Frame8 as u8);
-                        self.compile_expr(args.first().unwrap());
-                        self.compile_expr(args.get(1).unwrap());
-                    }
-
-                    "frame16" => {
-                        self.push_byte(Opcode::Frame16 as u8);
-                        self.compile_expr(args.first().unwrap());
-                        self.compile_expr(args.get(1).unwrap());
-                    }
-
-                    "frame32" => {
-                        self.push_byte(Opcode::Frame32 as u8);
-                        self.compile_expr(args.first().unwrap());
-                        self.compile_expr(args.get(1).unwrap());
-                    }
-
-                    "frame64" => {
-                        self.push_byte(Opcode::Frame64 as u8);
-                        self.compile_expr(args.first().unwrap());
-                        self.compile_expr(args.get(1).unwrap());
-                    }
-
-                    "peek8" => {
-                        self.push_byte(Opcode::Peek8 as u8);
-                        self.compile_expr(args.first().unwrap());
-                        self.compile_expr(args.get(1).unwrap());
-                    }
-
-                    "peek16" => {
-                        self.push_byte(Opcode::Peek16 as u8);
-                        self.compile_expr(args.first().unwrap());
-                        self.compile_expr(args.get(1).unwrap());
-                    }
-
-                    "peek32" => {
-                        self.push_byte(Opcode::Peek32 as u8);
-                        self.compile_expr(args.first().unwrap());
-                        self.compile_expr(args.get(1).unwrap());
-                    }
-
-                    "peek64" => {
-                        self.push_byte(Opcode::Peek64 as u8);
-                        self.compile_expr(args.first().unwrap());
-                        self.compile_expr(args.get(1).unwrap());
-                    }
-
-                    "load8" => {
-                        self.push_byte(Opcode::Load8 as u8);
-                        self.compile_expr(args.first().unwrap());
-                        self.compile_expr(args.get(1).unwrap());
-                    }
-
-                    "load16" => {
-                        self.push_byte(Opcode::Load16 as u8);
-                        self.compile_expr(args.first().unwrap());
-                        self.compile_expr(args.get(1).unwrap());
-                    }
-
-                    "load32" => {
-                        self.push_byte(Opcode::Load32 as u8);
-                        self.compile_expr(args.first().unwrap());
-                        self.compile_expr(args.get(1).unwrap());
-                    }
-
-                    "load64" => {
-                        self.push_byte(Opcode::Load64 as u8);
-                        self.compile_expr(args.first().unwrap());
-                        self.compile_expr(args.get(1).unwrap());
-                    }
-
-                    "store8" => {
-                        self.push_byte(Opcode::Store8 as u8);
-                        self.compile_expr(args.first().unwrap());
-                        self.compile_expr(args.get(1).unwrap());
-                    }
-
-                    "store16" => {
-                        self.push_byte(Opcode::Store16 as u8);
-                        self.compile_expr(args.first().unwrap());
-                        self.compile_expr(args.get(1).unwrap());
-                    }
-
-                    "store32" => {
-                        self.push_byte(Opcode::Store32 as u8);
-                        self.compile_expr(args.first().unwrap());
-                        self.compile_expr(args.get(1).unwrap());
-                    }
-
-                    "store64" => {
-                        self.push_byte(Opcode::Store64 as u8);
-                        self.compile_expr(args.first().unwrap());
-                        self.compile_expr(args.get(1).unwrap());
-                    }
-
-                    "jmp" => {
-                        self.push_byte(Opcode::Jmp as u8);
-                        self.compile_expr(args.first().unwrap());
-                    }
-
-                    "jz" => {
-                        self.push_byte(Opcode::Jz as u8);
-                        self.compile_expr(args.first().unwrap());
-                    }
-
-                    "jnz" => {
-                        self.push_byte(Opcode::Jnz as u8);
-                        self.compile_expr(args.first().unwrap());
-                    }
-
-                    "je" => {
-                        self.push_byte(Opcode::Je as u8);
-                        self.compile_expr(args.first().unwrap());
-                        self.compile_expr(args.get(1).unwrap());
-                    }
+            Expression::Instruction { name, args, span } => {
+                let instruction_ptr = self.pc;
 
-                    "jne" => {
-                        self.push_byte(Opcode::Jne as u8);
-                        self.compile_expr(args.first().unwrap());
-                        self.compile_expr(args.get(1).unwrap());
-                    }
+                self.compile_instruction(name, args);
 
-                    _ => unimplemented!(),
+                if !self.data_section && self.pc > instruction_ptr {
+                    self.instructions.push((instruction_ptr, span.offset()));
                 }
             }
             Expression::BinaryExpr {
@@ -724,7 +512,7 @@ impl Codegen {
                 lhs: _,
                 rhs: _,
                 span: _,
-            } => unreachable!(),
+            } => unreachable!("analyzer allows binary expressions only in comptime expressions"),
 
             Expression::UIntConstant(value, _) => {
                 if self.data_section {
@@ -777,7 +565,9 @@ impl Codegen {
                 self.push_byte(0);
             }
 
-            Expression::StringConstant(_, _) => unreachable!(),
+            Expression::StringConstant(_, _) => {
+                unreachable!("analyzer allows string constants only in directives")
+            }
 
             Expression::AsmConstant(name, _) => {
                 let const_value = Self::get_asm_constant(name);
@@ -810,23 +600,610 @@ impl Codegen {
                 );
             }
 
-            Expression::CurrentPtr(_) => unreachable!(),
+            Expression::CurrentPtr(_) => {
+                unreachable!("analyzer allows current pointer only in comptime expressions")
+            }
+
+            Expression::MemoryOperand { .. } => {
+                unreachable!("memory operands are compiled by load and store instructions")
+            }
 
-            _ => unimplemented!(),
+            Expression::None => unreachable!("parser doesn't output empty expressions"),
         }
     }
 
-    fn calculate_comptime_expr(&self, expr: &Expression) -> u64 {
-        match expr {
-            Expression::ComptimeExpr { expr, span: _ } => self.calculate_comptime_expr(expr),
+    fn compile_instruction(&mut self, name: &str, args: &[Expression]) {
+        match name {
+            "halt" => {
+                self.push_byte(Opcode::Halt as u8);
+            }
 
-            Expression::BinaryExpr {
-                op,
-                lhs,
-                rhs,
-                span: _,
-            } => {
-                let lhs = self.calculate_comptime_expr(lhs);
+            "ret" => {
+                self.push_byte(Opcode::Return as u8);
+            }
+
+            "call" => {
+                if let Some(Expression::AsmReg(_, _)) = args.first() {
+                    self.push_byte(Opcode::CallReg as u8);
+                } else {
+                    self.push_byte(Opcode::Call as u8);
+                }
+
+                self.compile_expr(args.first().unwrap());
+            }
+
+            "fret" => {
+                self.push_byte(Opcode::FullReturn as u8);
+            }
+
+            "fcall" => {
+                if let Some(Expression::AsmReg(_, _)) = args.first() {
+                    self.push_byte(Opcode::FullCallReg as u8);
+                } else {
+                    self.push_byte(Opcode::FullCall as u8);
+                }
+
+                self.compile_expr(args.first().unwrap());
+            }
+
+            "int" => {
+                self.push_byte(Opcode::Interrupt as u8);
+                self.compile_expr(args.first().unwrap());
+            }
+
+            "setvec" => {
+                self.push_byte(Opcode::SetVector as u8);
+                self.compile_expr(args.first().unwrap());
+                self.compile_expr(args.get(1).unwrap());
+            }
+
+            "iret" => {
+                self.push_byte(Opcode::InterruptReturn as u8);
+            }
+
+            "dbg" => {
+                if !self.release {
+                    self.push_byte(Opcode::Debug as u8);
+                    self.compile_expr(args.first().unwrap());
+                }
+            }
+
+            "trap" => {
+                self.push_byte(Opcode::Trap as u8);
+                self.compile_expr(args.first().unwrap());
+            }
+
+            "assert" => {
+                self.push_byte(Opcode::Assert as u8);
+                self.compile_expr(args.first().unwrap());
+                self.compile_expr(args.get(1).unwrap());
+            }
+
+            "mov" => {
+                // mov %dest, ...
+                if let Some(Expression::AsmReg(_, _)) = args.first() {
+                    match args.get(1) {
+                        Some(Expression::UIntConstant(value, _)) => {
+                            let constant = Constant::new(*value);
+
+                            match constant {
+                                Constant::U8(_) => self.push_byte(Opcode::Mov8 as u8),
+                                Constant::U16(_) => self.push_byte(Opcode::Mov16 as u8),
+                                Constant::U32(_) => self.push_byte(Opcode::Mov32 as u8),
+                                Constant::U64(_) => self.push_byte(Opcode::Mov64 as u8),
+                            }
+
+                            self.compile_expr(args.first().unwrap());
+                            self.compile_expr(args.get(1).unwrap());
+                        }
+
+                        Some(Expression::AsmReg(_, _)) => {
+                            self.push_byte(Opcode::MovR2R as u8);
+                            self.compile_expr(args.first().unwrap());
+                            self.compile_expr(args.get(1).unwrap());
+                        }
+
+                        Some(Expression::LabelRef(_, _)) => {
+                            self.push_byte(Opcode::Mov64 as u8);
+                            self.compile_expr(args.first().unwrap());
+                            self.compile_expr(args.get(1).unwrap());
+                        }
+
+                        Some(Expression::AsmConstant(id, _)) => {
+                            let asm_const = Self::get_asm_constant(id);
+                            let constant = Constant::new(asm_const);
+
+                            match constant {
+                                Constant::U8(_) => self.push_byte(Opcode::Mov8 as u8),
+                                Constant::U16(_) => self.push_byte(Opcode::Mov16 as u8),
+                                Constant::U32(_) => self.push_byte(Opcode::Mov32 as u8),
+                                Constant::U64(_) => self.push_byte(Opcode::Mov64 as u8),
+                            }
+
+                            self.compile_expr(args.first().unwrap());
+                            self.compile_expr(args.get(1).unwrap());
+                        }
+
+                        _ => unreachable!(),
+                    }
+                }
+            }
+
+            "add" => match args.get(1) {
+                Some(Expression::UIntConstant(value, _)) => {
+                    let constant = Constant::new(*value);
+
+                    match constant {
+                        Constant::U8(_) => self.push_byte(Opcode::Add8 as u8),
+                        Constant::U16(_) => self.push_byte(Opcode::Add16 as u8),
+                        Constant::U32(_) => self.push_byte(Opcode::Add32 as u8),
+                        Constant::U64(_) => self.push_byte(Opcode::Add64 as u8),
+                    }
+
+                    self.compile_expr(args.first().unwrap());
+                    self.compile_expr(args.get(1).unwrap());
+                }
+
+                Some(Expression::AsmReg(_, _)) => {
+                    self.push_byte(Opcode::AddR2R as u8);
+                    self.compile_expr(args.first().unwrap());
+                    self.compile_expr(args.get(1).unwrap());
+                }
+
+                Some(Expression::LabelRef(_, _)) => {
+                    self.push_byte(Opcode::Add64 as u8);
+                    self.compile_expr(args.first().unwrap());
+                    self.compile_expr(args.get(1).unwrap());
+                }
+
+                _ => unreachable!(),
+            },
+
+            "sub" => match args.get(1) {
+                Some(Expression::UIntConstant(value, _)) => {
+                    let constant = Constant::new(*value);
+
+                    match constant {
+                        Constant::U8(_) => self.push_byte(Opcode::Sub8 as u8),
+                        Constant::U16(_) => self.push_byte(Opcode::Sub16 as u8),
+                        Constant::U32(_) => self.push_byte(Opcode::Sub32 as u8),
+                        Constant::U64(_) => self.push_byte(Opcode::Sub64 as u8),
+                    }
+
+                    self.compile_expr(args.first().unwrap());
+                    self.compile_expr(args.get(1).unwrap());
+                }
+
+                Some(Expression::AsmReg(_, _)) => {
+                    self.push_byte(Opcode::SubR2R as u8);
+                    self.compile_expr(args.first().unwrap());
+                    self.compile_expr(args.get(1).unwrap());
+                }
+
+                Some(Expression::LabelRef(_, _)) => {
+                    self.push_byte(Opcode::Sub64 as u8);
+                    self.compile_expr(args.first().unwrap());
+                    self.compile_expr(args.get(1).unwrap());
+                }
+
+                _ => unreachable!(),
+            },
+
+            "mul" => match args.get(1) {
+                Some(Expression::UIntConstant(value, _)) => {
+                    let constant = Constant::new(*value);
+
+                    match constant {
+                        Constant::U8(_) => self.push_byte(Opcode::Mul8 as u8),
+                        Constant::U16(_) => self.push_byte(Opcode::Mul16 as u8),
+                        Constant::U32(_) => self.push_byte(Opcode::Mul32 as u8),
+                        Constant::U64(_) => self.push_byte(Opcode::Mul64 as u8),
+                    }
+
+                    self.compile_expr(args.first().unwrap());
+                    self.compile_expr(args.get(1).unwrap());
+                }
+
+                Some(Expression::AsmReg(_, _)) => {
+                    self.push_byte(Opcode::MulR2R as u8);
+                    self.compile_expr(args.first().unwrap());
+                    self.compile_expr(args.get(1).unwrap());
+                }
+
+                Some(Expression::LabelRef(_, _)) => {
+                    self.push_byte(Opcode::Mul64 as u8);
+                    self.compile_expr(args.first().unwrap());
+                    self.compile_expr(args.get(1).unwrap());
+                }
+
+                _ => unreachable!(),
+            },
+
+            "div" => match args.get(1) {
+                Some(Expression::UIntConstant(value, _)) => {
+                    let constant = Constant::new(*value);
+
+                    match constant {
+                        Constant::U8(_) => self.push_byte(Opcode::Div8 as u8),
+                        Constant::U16(_) => self.push_byte(Opcode::Div16 as u8),
+                        Constant::U32(_) => self.push_byte(Opcode::Div32 as u8),
+                        Constant::U64(_) => self.push_byte(Opcode::Div64 as u8),
+                    }
+
+                    self.compile_expr(args.first().unwrap());
+                    self.compile_expr(args.get(1).unwrap());
+                }
+
+                Some(Expression::AsmReg(_, _)) => {
+                    self.push_byte(Opcode::DivR2R as u8);
+                    self.compile_expr(args.first().unwrap());
+                    self.compile_expr(args.get(1).unwrap());
+                }
+
+                Some(Expression::LabelRef(_, _)) => {
+                    self.push_byte(Opcode::Div64 as u8);
+                    self.compile_expr(args.first().unwrap());
+                    self.compile_expr(args.get(1).unwrap());
+                }
+
+                _ => unreachable!(),
+            },
+
+            "cmp" => match args.get(1) {
+                Some(Expression::UIntConstant(value, _)) => {
+                    let constant = Constant::new(*value);
+
+                    match constant {
+                        Constant::U8(_) => self.push_byte(Opcode::Cmp8 as u8),
+                        Constant::U16(_) => self.push_byte(Opcode::Cmp16 as u8),
+                        Constant::U32(_) => self.push_byte(Opcode::Cmp32 as u8),
+                        Constant::U64(_) => self.push_byte(Opcode::Cmp64 as u8),
+                    }
+
+                    self.compile_expr(args.first().unwrap());
+                    self.compile_expr(args.get(1).unwrap());
+                }
+
+                Some(Expression::AsmReg(_, _)) => {
+                    self.push_byte(Opcode::CmpR2R as u8);
+                    self.compile_expr(args.first().unwrap());
+                    self.compile_expr(args.get(1).unwrap());
+                }
+
+                Some(Expression::LabelRef(_, _)) => {
+                    self.push_byte(Opcode::Cmp64 as u8);
+                    self.compile_expr(args.first().unwrap());
+                    self.compile_expr(args.get(1).unwrap());
+                }
+
+                _ => unreachable!(),
+            },
+
+            "lea" => {
+                self.push_byte(Opcode::Lea as u8);
+                self.compile_expr(args.first().unwrap());
+                self.compile_expr(args.get(1).unwrap());
+            }
+
+            "xadd" => {
+                self.push_byte(Opcode::XAdd as u8);
+                self.compile_expr(args.first().unwrap());
+                self.compile_expr(args.get(1).unwrap());
+            }
+
+            "memcpy" => {
+                self.push_byte(Opcode::MemCopy as u8);
+                args.iter().for_each(|arg| self.compile_expr(arg));
+            }
+
+            "memset" => {
+                self.push_byte(Opcode::MemSet as u8);
+                args.iter().for_each(|arg| self.compile_expr(arg));
+            }
+
+            "memcmp" => {
+                self.push_byte(Opcode::MemCompare as u8);
+                args.iter().for_each(|arg| self.compile_expr(arg));
+            }
+
+            "push8" => {
+                self.push_byte(Opcode::Push8 as u8);
+                self.compile_expr(args.first().unwrap());
+            }
+
+            "push16" => {
+                self.push_byte(Opcode::Push16 as u8);
+                self.compile_expr(args.first().unwrap());
+            }
+
+            "push32" => {
+                self.push_byte(Opcode::Push32 as u8);
+                self.compile_expr(args.first().unwrap());
+            }
+
+            "push64" => {
+                self.push_byte(Opcode::Push64 as u8);
+                self.compile_expr(args.first().unwrap());
+            }
+
+            "pop8" => {
+                self.push_byte(Opcode::Pop8 as u8);
+                self.compile_expr(args.first().unwrap());
+            }
+
+            "pop16" => {
+                self.push_byte(Opcode::Pop16 as u8);
+                self.compile_expr(args.first().unwrap());
+            }
+
+            "pop32" => {
+                self.push_byte(Opcode::Pop32 as u8);
+                self.compile_expr(args.first().unwrap());
+            }
+
+            "pop64" => {
+                self.push_byte(Opcode::Pop64 as u8);
+                self.compile_expr(args.first().unwrap());
+            }
+
+            "frame8" => {
+                self.push_byte(Opcode::Frame8 as u8);
+                self.compile_expr(args.first().unwrap());
+                self.compile_fixed_constant(args.get(1).unwrap(), |value| {
+                    Constant::U16(value as u16)
+                });
+            }
+
+            "frame16" => {
+                self.push_byte(Opcode::Frame16 as u8);
+                self.compile_expr(args.first().unwrap());
+                self.compile_fixed_constant(args.get(1).unwrap(), |value| {
+                    Constant::U16(value as u16)
+                });
+            }
+
+            "frame32" => {
+                self.push_byte(Opcode::Frame32 as u8);
+                self.compile_expr(args.first().unwrap());
+                self.compile_fixed_constant(args.get(1).unwrap(), |value| {
+                    Constant::U16(value as u16)
+                });
+            }
+
+            "frame64" => {
+                self.push_byte(Opcode::Frame64 as u8);
+                self.compile_expr(args.first().unwrap());
+                self.compile_fixed_constant(args.get(1).unwrap(), |value| {
+                    Constant::U16(value as u16)
+                });
+            }
+
+            "peek8" => {
+                self.push_byte(Opcode::Peek8 as u8);
+                self.compile_expr(args.first().unwrap());
+                self.compile_fixed_constant(args.get(1).unwrap(), |value| {
+                    Constant::U16(value as u16)
+                });
+            }
+
+            "peek16" => {
+                self.push_byte(Opcode::Peek16 as u8);
+                self.compile_expr(args.first().unwrap());
+                self.compile_fixed_constant(args.get(1).unwrap(), |value| {
+                    Constant::U16(value as u16)
+                });
+            }
+
+            "peek32" => {
+                self.push_byte(Opcode::Peek32 as u8);
+                self.compile_expr(args.first().unwrap());
+                self.compile_fixed_constant(args.get(1).unwrap(), |value| {
+                    Constant::U16(value as u16)
+                });
+            }
+
+            "peek64" => {
+                self.push_byte(Opcode::Peek64 as u8);
+                self.compile_expr(args.first().unwrap());
+                self.compile_fixed_constant(args.get(1).unwrap(), |value| {
+                    Constant::U16(value as u16)
+                });
+            }
+
+            "setframe8" => {
+                self.push_byte(Opcode::SetFrame8 as u8);
+                self.compile_fixed_constant(args.first().unwrap(), |value| {
+                    Constant::U16(value as u16)
+                });
+                self.compile_expr(args.get(1).unwrap());
+            }
+
+            "setframe16" => {
+                self.push_byte(Opcode::SetFrame16 as u8);
+                self.compile_fixed_constant(args.first().unwrap(), |value| {
+                    Constant::U16(value as u16)
+                });
+                self.compile_expr(args.get(1).unwrap());
+            }
+
+            "setframe32" => {
+                self.push_byte(Opcode::SetFrame32 as u8);
+                self.compile_fixed_constant(args.first().unwrap(), |value| {
+                    Constant::U16(value as u16)
+                });
+                self.compile_expr(args.get(1).unwrap());
+            }
+
+            "setframe64" => {
+                self.push_byte(Opcode::SetFrame64 as u8);
+                self.compile_fixed_constant(args.first().unwrap(), |value| {
+                    Constant::U16(value as u16)
+                });
+                self.compile_expr(args.get(1).unwrap());
+            }
+
+            "poke8" => {
+                self.push_byte(Opcode::Poke8 as u8);
+                self.compile_fixed_constant(args.first().unwrap(), |value| {
+                    Constant::U16(value as u16)
+                });
+                self.compile_expr(args.get(1).unwrap());
+            }
+
+            "poke16" => {
+                self.push_byte(Opcode::Poke16 as u8);
+                self.compile_fixed_constant(args.first().unwrap(), |value| {
+                    Constant::U16(value as u16)
+                });
+                self.compile_expr(args.get(1).unwrap());
+            }
+
+            "poke32" => {
+                self.push_byte(Opcode::Poke32 as u8);
+                self.compile_fixed_constant(args.first().unwrap(), |value| {
+                    Constant::U16(value as u16)
+                });
+                self.compile_expr(args.get(1).unwrap());
+            }
+
+            "poke64" => {
+                self.push_byte(Opcode::Poke64 as u8);
+                self.compile_fixed_constant(args.first().unwrap(), |value| {
+                    Constant::U16(value as u16)
+                });
+                self.compile_expr(args.get(1).unwrap());
+            }
+
+            "enter" => {
+                self.push_byte(Opcode::Enter as u8);
+                self.compile_fixed_constant(args.first().unwrap(), |value| {
+                    Constant::U16(value as u16)
+                });
+            }
+
+            "leave" => {
+                self.push_byte(Opcode::Leave as u8);
+            }
+
+            "load8" => self.compile_load(
+                [Opcode::Load8, Opcode::LoadOffset8, Opcode::LoadIndexed8],
+                args,
+            ),
+
+            "load16" => self.compile_load(
+                [Opcode::Load16, Opcode::LoadOffset16, Opcode::LoadIndexed16],
+                args,
+            ),
+
+            "load32" => self.compile_load(
+                [Opcode::Load32, Opcode::LoadOffset32, Opcode::LoadIndexed32],
+                args,
+            ),
+
+            "load64" => self.compile_load(
+                [Opcode::Load64, Opcode::LoadOffset64, Opcode::LoadIndexed64],
+                args,
+            ),
+
+            "store8" => self.compile_store(
+                [Opcode::Store8, Opcode::StoreOffset8, Opcode::StoreIndexed8],
+                args,
+            ),
+
+            "store16" => self.compile_store(
+                [
+                    Opcode::Store16,
+                    Opcode::StoreOffset16,
+                    Opcode::StoreIndexed16,
+                ],
+                args,
+            ),
+
+            "store32" => self.compile_store(
+                [
+                    Opcode::Store32,
+                    Opcode::StoreOffset32,
+                    Opcode::StoreIndexed32,
+                ],
+                args,
+            ),
+
+            "store64" => self.compile_store(
+                [
+                    Opcode::Store64,
+                    Opcode::StoreOffset64,
+                    Opcode::StoreIndexed64,
+                ],
+                args,
+            ),
+
+            "jmp" => {
+                if let Some(Expression::AsmReg(_, _)) = args.first() {
+                    self.push_byte(Opcode::JmpReg as u8);
+                } else {
+                    self.push_byte(Opcode::Jmp as u8);
+                }
+
+                self.compile_expr(args.first().unwrap());
+            }
+
+            "jz" => {
+                if let Some(Expression::AsmReg(_, _)) = args.first() {
+                    self.push_byte(Opcode::JzReg as u8);
+                } else {
+                    self.push_byte(Opcode::Jz as u8);
+                }
+
+                self.compile_expr(args.first().unwrap());
+            }
+
+            "jnz" => {
+                if let Some(Expression::AsmReg(_, _)) = args.first() {
+                    self.push_byte(Opcode::JnzReg as u8);
+                } else {
+                    self.push_byte(Opcode::Jnz as u8);
+                }
+
+                self.compile_expr(args.first().unwrap());
+            }
+
+            "je" => {
+                if let Some(Expression::AsmReg(_, _)) = args.get(1) {
+                    self.push_byte(Opcode::JeReg as u8);
+                } else {
+                    self.push_byte(Opcode::Je as u8);
+                }
+
+                self.compile_fixed_constant(args.first().unwrap(), Constant::U64);
+                self.compile_expr(args.get(1).unwrap());
+            }
+
+            "jne" => {
+                if let Some(Expression::AsmReg(_, _)) = args.get(1) {
+                    self.push_byte(Opcode::JneReg as u8);
+                } else {
+                    self.push_byte(Opcode::Jne as u8);
+                }
+
+                self.compile_fixed_constant(args.first().unwrap(), Constant::U64);
+                self.compile_expr(args.get(1).unwrap());
+            }
+
+            _ => unreachable!("parser accepts only known instructions"),
+        }
+    }
+
+    fn calculate_comptime_expr(&self, expr: &Expression) -> u64 {
+        match expr {
+            Expression::ComptimeExpr { expr, span: _ } => self.calculate_comptime_expr(expr),
+
+            Expression::BinaryExpr {
+                op,
+                lhs,
+                rhs,
+                span: _,
+            } => {
+                let lhs = self.calculate_comptime_expr(lhs);
                 let rhs = self.calculate_comptime_expr(rhs);
 
                 match op.as_str() {
@@ -841,17 +1218,23 @@ impl Codegen {
                             lhs % rhs
                         }
                     }
-                    _ => unreachable!(),
+                    _ => unreachable!("parser accepts only arithmetic operators"),
                 }
             }
 
-            Expression::LabelRef(label, _) => self.labels.get(label).unwrap().ptr,
+            // analyzer allows only data labels defined above comptime expression
+            Expression::LabelRef(label, _) => {
+                self.labels
+                    .get(label)
+                    .expect("comptime label is defined before")
+                    .ptr
+            }
 
             Expression::UIntConstant(value, _) => *value,
 
             Expression::CurrentPtr(_) => self.pc,
 
-            _ => unreachable!(),
+            _ => unreachable!("analyzer allows only numbers, labels and current pointer"),
         }
     }
 }
@@ -860,6 +1243,7 @@ impl Codegen {
 mod tests {
     use super::*;
     use crate::assembly::{lexer::Lexer, parser::Parser};
+    use crate::vm::DebugInfo;
 
     #[test]
     fn codegen_label_def_test() {
@@ -1087,4 +1471,223 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn codegen_setvec_expr_test() {
+        const FILENAME: &str = "test";
+        const CODE: &str = "handler: setvec $7, handler iret";
+
+        let mut lexer = Lexer::new(FILENAME, CODE);
+        let tokens = lexer.tokenize().unwrap();
+
+        let mut parser = Parser::new(FILENAME, CODE, &tokens);
+        let ast = parser.parse().unwrap();
+
+        let mut codegen = Codegen::new(false);
+
+        for ref expr in ast {
+            codegen.compile_expr(expr);
+        }
+
+        assert_eq!(codegen.pc, 1 + 8 + 8 + 1);
+        assert_eq!(
+            codegen.constants_refs,
+            HashMap::from([(1, String::from("7"))])
+        );
+        assert_eq!(
+            codegen.labels_refs,
+            HashMap::from([(9, String::from("handler"))])
+        );
+        assert_eq!(codegen.output[0], Opcode::SetVector as u8);
+        assert_eq!(codegen.output[17], Opcode::InterruptReturn as u8);
+    }
+
+    #[test]
+    fn codegen_indirect_jumps_test() {
+        const FILENAME: &str = "test";
+        const CODE: &str = "label: jmp %r1 call %r2 jne $5, %r3 jz label";
+
+        let mut lexer = Lexer::new(FILENAME, CODE);
+        let tokens = lexer.tokenize().unwrap();
+
+        let mut parser = Parser::new(FILENAME, CODE, &tokens);
+        let ast = parser.parse().unwrap();
+
+        let mut codegen = Codegen::new(false);
+
+        for ref expr in ast {
+            codegen.compile_expr(expr);
+        }
+
+        assert_eq!(codegen.pc, 2 + 2 + (1 + 8 + 1) + (1 + 8));
+        assert_eq!(
+            codegen.output[..4],
+            [Opcode::JmpReg as u8, 1, Opcode::CallReg as u8, 2]
+        );
+        assert_eq!(codegen.output[4], Opcode::JneReg as u8);
+        assert_eq!(codegen.output[13], 3);
+        assert_eq!(codegen.output[14], Opcode::Jz as u8);
+        assert_eq!(
+            codegen.labels_refs,
+            HashMap::from([(15, String::from("label"))])
+        );
+    }
+
+    #[test]
+    fn codegen_lea_test() {
+        const FILENAME: &str = "test";
+        const CODE: &str = "label: lea %r3, label";
+
+        let mut lexer = Lexer::new(FILENAME, CODE);
+        let tokens = lexer.tokenize().unwrap();
+
+        let mut parser = Parser::new(FILENAME, CODE, &tokens);
+        let ast = parser.parse().unwrap();
+
+        let mut codegen = Codegen::new(false);
+
+        for ref expr in ast {
+            codegen.compile_expr(expr);
+        }
+
+        assert_eq!(codegen.pc, 1 + 1 + 8);
+        assert_eq!(codegen.output[..2], [Opcode::Lea as u8, 3]);
+        assert_eq!(
+            codegen.labels_refs,
+            HashMap::from([(2, String::from("label"))])
+        );
+        assert!(codegen.constants_refs.is_empty());
+    }
+
+    #[test]
+    fn codegen_memory_operands_test() {
+        const FILENAME: &str = "test";
+        const CODE: &str = "load64 %r0, [%r1 + $16] store8 [%r1 + %r2 * $4], %r3 load16 %r0, %r1";
+
+        let mut lexer = Lexer::new(FILENAME, CODE);
+        let tokens = lexer.tokenize().unwrap();
+
+        let mut parser = Parser::new(FILENAME, CODE, &tokens);
+        let ast = parser.parse().unwrap();
+
+        let mut codegen = Codegen::new(false);
+
+        for ref expr in ast {
+            codegen.compile_expr(expr);
+        }
+
+        assert_eq!(
+            codegen.output,
+            [
+                vec![Opcode::LoadOffset64 as u8, 0, 1],
+                16_u64.to_be_bytes().to_vec(),
+                vec![Opcode::StoreIndexed8 as u8, 1, 2, 4, 3],
+                vec![Opcode::Load16 as u8, 0, 1],
+            ]
+            .concat()
+        );
+        assert!(codegen.constants.is_empty());
+    }
+
+    #[test]
+    fn codegen_frame_instructions_test() {
+        const FILENAME: &str = "test";
+        const CODE: &str = "enter $16 setframe64 $8, %r0 poke8 $1, %r1 frame8 %r2, $8 leave";
+
+        let mut lexer = Lexer::new(FILENAME, CODE);
+        let tokens = lexer.tokenize().unwrap();
+
+        let mut parser = Parser::new(FILENAME, CODE, &tokens);
+        let ast = parser.parse().unwrap();
+
+        let mut codegen = Codegen::new(false);
+
+        for ref expr in ast {
+            codegen.compile_expr(expr);
+        }
+
+        assert_eq!(codegen.pc, 9 + 10 + 10 + 10 + 1);
+        assert_eq!(codegen.output[0], Opcode::Enter as u8);
+        assert_eq!(codegen.output[9], Opcode::SetFrame64 as u8);
+        assert_eq!(codegen.output[18], 0);
+        assert_eq!(codegen.output[19], Opcode::Poke8 as u8);
+        assert_eq!(codegen.output[29], Opcode::Frame8 as u8);
+        assert_eq!(codegen.output[39], Opcode::Leave as u8);
+
+        // offsets are always read as u16 values
+        assert_eq!(
+            codegen.constants,
+            HashMap::from([
+                (String::from("16:u16"), Constant::U16(16)),
+                (String::from("8:u16"), Constant::U16(8)),
+                (String::from("1:u16"), Constant::U16(1)),
+            ])
+        );
+        assert_eq!(
+            codegen.constants_refs.get(&31),
+            Some(&String::from("8:u16"))
+        );
+    }
+
+    #[test]
+    fn codegen_trap_instructions_test() {
+        const FILENAME: &str = "test";
+        const CODE: &str = "msg: assert %r1, msg trap msg";
+
+        let mut lexer = Lexer::new(FILENAME, CODE);
+        let tokens = lexer.tokenize().unwrap();
+
+        let mut parser = Parser::new(FILENAME, CODE, &tokens);
+        let ast = parser.parse().unwrap();
+
+        let mut codegen = Codegen::new(true);
+
+        for ref expr in ast {
+            codegen.compile_expr(expr);
+        }
+
+        // assertions are kept in release mode
+        assert_eq!(codegen.pc, 10 + 9);
+        assert_eq!(codegen.output[0], Opcode::Assert as u8);
+        assert_eq!(codegen.output[1], 1);
+        assert_eq!(codegen.output[10], Opcode::Trap as u8);
+        assert_eq!(codegen.labels_refs.get(&2), Some(&String::from("msg")));
+        assert_eq!(codegen.labels_refs.get(&11), Some(&String::from("msg")));
+    }
+
+    #[test]
+    fn codegen_debug_info_test() {
+        const FILENAME: &str = "test";
+        const CODE: &str = "section .data msg: ascii \"hi\"\nsection .text entry _start\n_start: call func\nret\nfunc: ret";
+        const METADATA_LENGTH: usize = 3 * 8 + 1;
+
+        let mut lexer = Lexer::new(FILENAME, CODE);
+        let tokens = lexer.tokenize().unwrap();
+
+        let mut parser = Parser::new(FILENAME, CODE, &tokens);
+        let ast = parser.parse().unwrap();
+
+        let mut codegen = Codegen::new(false);
+        let binary = codegen.compile(&ast).to_vec();
+        let program = binary[METADATA_LENGTH..].to_vec();
+
+        // data labels are not included
+        assert_eq!(codegen.symbols.len(), 2);
+        assert_eq!(codegen.symbols[0].1, "_start");
+        assert_eq!(codegen.symbols[1].1, "func");
+
+        let (start, func) = (codegen.symbols[0].0, codegen.symbols[1].0);
+
+        assert_eq!(program[start as usize], Opcode::Call as u8);
+        assert_eq!(program[func as usize], Opcode::Return as u8);
+        assert_eq!(
+            codegen.debug_info("test.asm", CODE),
+            format!(
+                "source test.asm\nbinary {:x}\nlabel {start} _start\nlabel {func} func\n\
+                line {start} 3\nline {} 4\nline {func} 5\n",
+                DebugInfo::checksum(&binary),
+                start + 9
+            )
+        );
+    }
 }
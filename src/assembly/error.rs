@@ -4,7 +4,7 @@ use thiserror::Error;
 use super::Source;
 
 pub fn position_to_span(from: usize, to: usize) -> SourceSpan {
-    (from, to.wrapping_sub(from)).into()
+    (from, to.saturating_sub(from)).into()
 }
 
 #[derive(Debug, Error, Diagnostic)]
@@ -32,6 +32,15 @@ pub enum AssemblyError {
         span: SourceSpan,
     },
 
+    #[error("String constant is not terminated")]
+    #[diagnostic(severity(Error), code(mvm::asm::unterminated_string))]
+    UnterminatedString {
+        #[source_code]
+        src: Source,
+        #[label("closing `\"` is missing")]
+        span: SourceSpan,
+    },
+
     #[error("{error}")]
     #[diagnostic(severity(Error), code(mvm::asm::constant_error))]
     InvalidConstant {
@@ -72,20 +72,43 @@ impl Lexer {
                 macros::std_constant!("syscall"), // kept for old examples
                 macros::std_constant!("int_syscall"),
                 macros::std_constant!("int_accinc"),
+                macros::std_constant!("int_fault"),
                 // syscalls
                 macros::std_constant!("sys_exit"),
                 macros::std_constant!("sys_read"),
                 macros::std_constant!("sys_write"),
                 macros::std_constant!("sys_alloc"),
                 macros::std_constant!("sys_free"),
+                macros::std_constant!("sys_open"),
+                macros::std_constant!("sys_close"),
+                macros::std_constant!("sys_fread"),
+                macros::std_constant!("sys_fwrite"),
+                macros::std_constant!("sys_seek"),
+                macros::std_constant!("sys_stat"),
+                macros::std_constant!("sys_unlink"),
+                macros::std_constant!("sys_readdir"),
+                macros::std_constant!("sys_argc"),
+                macros::std_constant!("sys_argv"),
+                macros::std_constant!("sys_envp"),
+                macros::std_constant!("sys_sbrk"),
+                macros::std_constant!("sys_realloc"),
+                macros::std_constant!("sys_calloc"),
+                macros::std_constant!("sys_memstats"),
             ]),
             std_instructions: HashMap::from([
                 macros::std_instruction!("halt"),
                 macros::std_instruction!("ret"),
                 macros::std_instruction!("call"),
+                macros::std_instruction!("fcall"),
+                macros::std_instruction!("fret"),
                 macros::std_instruction!("int"),
+                macros::std_instruction!("setvec"),
+                macros::std_instruction!("iret"),
                 macros::std_instruction!("dbg"),
+                macros::std_instruction!("trap"),
+                macros::std_instruction!("assert"),
                 macros::std_instruction!("mov"),
+                macros::std_instruction!("lea"),
                 macros::std_instruction!("load8"),
                 macros::std_instruction!("load16"),
                 macros::std_instruction!("load32"),
@@ -94,6 +117,9 @@ impl Lexer {
                 macros::std_instruction!("store16"),
                 macros::std_instruction!("store32"),
                 macros::std_instruction!("store64"),
+                macros::std_instruction!("memcpy"),
+                macros::std_instruction!("memset"),
+                macros::std_instruction!("memcmp"),
                 macros::std_instruction!("push8"),
                 macros::std_instruction!("push16"),
                 macros::std_instruction!("push32"),
@@ -110,6 +136,16 @@ impl Lexer {
                 macros::std_instruction!("peek16"),
                 macros::std_instruction!("peek32"),
                 macros::std_instruction!("peek64"),
+                macros::std_instruction!("setframe8"),
+                macros::std_instruction!("setframe16"),
+                macros::std_instruction!("setframe32"),
+                macros::std_instruction!("setframe64"),
+                macros::std_instruction!("poke8"),
+                macros::std_instruction!("poke16"),
+                macros::std_instruction!("poke32"),
+                macros::std_instruction!("poke64"),
+                macros::std_instruction!("enter"),
+                macros::std_instruction!("leave"),
                 macros::std_instruction!("add"),
                 macros::std_instruction!("sub"),
                 macros::std_instruction!("mul"),
@@ -275,6 +311,10 @@ impl Lexer {
                     self.skip_char();
 
                     while self.peek_char() != '"' {
+                        if self.is_eof() {
+                            break;
+                        }
+
                         if self.peek_char() == '\\' {
                             self.skip_char();
 
@@ -297,6 +337,14 @@ impl Lexer {
                         self.skip_char();
                     }
 
+                    if self.is_eof() {
+                        self.error(AssemblyError::UnterminatedString {
+                            src: self.src.clone(),
+                            span: error::position_to_span(string_offset, self.position),
+                        });
+                        break;
+                    }
+
                     self.skip_char(); // skipping the `"` char
 
                     output.push(Token::new(
@@ -874,4 +922,16 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn lexer_unterminated_string_test() {
+        let mut lexer = Lexer::new("test", "ascii \"hello");
+        let errors = lexer.tokenize().unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(
+            errors[0],
+            AssemblyError::UnterminatedString { span, .. } if span == (6, 6).into()
+        ));
+    }
 }
@@ -7,3 +7,83 @@ pub mod parser;
 pub mod semantic;
 
 pub type Source = NamedSource<String>;
+
+/// Runs all assembler stages, returns binary with its code generator (for debug info)
+/// or rendered error reports.
+pub fn assemble(
+    filename: &str,
+    source: &str,
+    release_mode: bool,
+) -> Result<(Vec<u8>, codegen::Codegen), Vec<String>> {
+    let render = |errors: &[error::AssemblyError]| {
+        let reporter = miette::GraphicalReportHandler::new();
+
+        errors
+            .iter()
+            .map(|err| {
+                let mut buffer = String::new();
+                let _ = reporter.render_report(&mut buffer, err);
+
+                buffer
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut lexer = lexer::Lexer::new(filename, source);
+    let tokens = lexer.tokenize().map_err(render)?;
+
+    let mut parser = parser::Parser::new(filename, source, &tokens);
+    let ast = parser.parse().map_err(render)?;
+
+    let mut analyzer = semantic::Analyzer::new(filename, source);
+    analyzer.analyze(&ast).map_err(render)?;
+
+    let mut codegen = codegen::Codegen::new(release_mode);
+    let binary = codegen.compile(&ast).to_vec();
+
+    Ok((binary, codegen))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assemble_halt_test() {
+        const CODE: &str = "section .data\nsection .text\nentry _start\n_start:\n  halt";
+
+        assert!(assemble("test", CODE, false).is_ok());
+    }
+
+    #[test]
+    fn assemble_malformed_sources_test() {
+        const SOURCES: &[&str] = &[
+            // unknown label in instruction argument
+            "section .data\nsection .text\n_start:\n  setvec $4, a",
+            "section .data\nsection .text\n_start:\n  jmp a",
+            // unterminated string
+            "section .data\n  str:\n    ascii \"hello",
+            // operator without operands
+            " t-",
+            // label definition in compile time expression
+            "section .data\n  str:\n    [. - str:]",
+            "section .data\n  value:\n    [$sys_write + 1]",
+            // labels which are unknown at compile time expression
+            "section .data\n  fp:\n    [func]\nsection .text\nfunc:\n  halt",
+            "section .data\n  a:\n    [b - a]\n  b:\n    $1",
+            // `lea` takes only label address
+            "section .data\nsection .text\n_start:\n  lea %r0, $5",
+            "section .data\nsection .text\n_start:\n  lea %r0, %r1",
+            "section .data\nsection .text\n_start:\n  lea _start, _start",
+            // memory operand without instruction
+            "section .data\nsection .text\n_start:\n  [%r1 + $8]",
+        ];
+
+        for source in SOURCES {
+            match assemble("test", source, false) {
+                Ok(_) => panic!("source is assembled: {source:?}"),
+                Err(reports) => assert!(!reports.is_empty()),
+            }
+        }
+    }
+}
@@ -28,6 +28,15 @@ pub enum Expression {
         span: SourceSpan,
     },
 
+    /// Memory operand of load/store instructions: `[%base + $disp]` or `[%base + %index * $scale]`
+    MemoryOperand {
+        base: Box<Expression>,
+        index: Option<Box<Expression>>,
+        scale: Option<Box<Expression>>,
+        displacement: Option<Box<Expression>>,
+        span: SourceSpan,
+    },
+
     Instruction {
         name: String,
         args: Vec<Expression>,
@@ -61,6 +70,7 @@ impl Expression {
             Expression::LabelDef { span, .. } => *span,
             Expression::Directive { span, .. } => *span,
             Expression::ComptimeExpr { span, .. } => *span,
+            Expression::MemoryOperand { span, .. } => *span,
             Expression::Instruction { span, .. } => *span,
             Expression::BinaryExpr { span, .. } => *span,
             Expression::UIntConstant(_, span) => *span,
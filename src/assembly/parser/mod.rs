@@ -121,6 +121,79 @@ impl<'tokens> Parser<'tokens> {
 }
 
 impl<'tokens> Parser<'tokens> {
+    /// Parses memory operand after opening bracket:
+    /// `[%base]`, `[%base + $disp]`, `[%base - $disp]` or `[%base + %index * $scale]`
+    fn memory_operand(&mut self, expr_offset: usize) -> Expression {
+        let base = Box::new(self.term());
+
+        let mut index = None;
+        let mut scale = None;
+        let mut displacement = None;
+
+        let current = self.peek_token().clone();
+
+        if current.token_type == TokenType::Operator && ["+", "-"].contains(&current.value.as_str())
+        {
+            self.skip_token();
+
+            match self.term() {
+                Expression::AsmReg(name, span) if current.value == "+" => {
+                    index = Some(Box::new(Expression::AsmReg(name, span)));
+
+                    let operator = self.peek_token().clone();
+
+                    if operator.token_type == TokenType::Operator && operator.value == "*" {
+                        self.skip_token();
+                        scale = Some(Box::new(self.term()));
+                    }
+                }
+
+                // negative displacement is stored as wrapped value
+                Expression::UIntConstant(value, span) if current.value == "-" => {
+                    displacement = Some(Box::new(Expression::UIntConstant(
+                        value.wrapping_neg(),
+                        span,
+                    )));
+                }
+
+                Expression::None => {
+                    let span = self.peek_token().span;
+
+                    self.error(AssemblyError::UnknownExpression {
+                        error: "Unknown expression found".to_string(),
+                        src: self.src.clone(),
+                        span,
+                    });
+                }
+
+                expr if current.value == "-" => {
+                    self.error(AssemblyError::UnsupportedExpression {
+                        error: "Unsupported memory operand found".to_string(),
+                        label: "only numeric displacement can be subtracted".to_string(),
+                        src: self.src.clone(),
+                        span: expr.get_span(),
+                    });
+                }
+
+                expr => displacement = Some(Box::new(expr)),
+            }
+        }
+
+        let span_end = self.peek_token().span.offset() + self.peek_token().span.len();
+
+        self.skip_expected(TokenType::RBrack).unwrap_or_else(|err| {
+            self.error(*err);
+        });
+
+        Expression::MemoryOperand {
+            base,
+            index,
+            scale,
+            displacement,
+            span: error::position_to_span(expr_offset, span_end),
+        }
+    }
+
     fn term(&mut self) -> Expression {
         // let expr_offset = self.peek_token().span.offset();
         let current = self.peek_token().clone();
@@ -198,7 +271,7 @@ impl<'tokens> Parser<'tokens> {
 
                     match current.value.as_str() {
                         // no arguments instructions
-                        "halt" | "ret" => {
+                        "halt" | "ret" | "fret" | "iret" | "leave" => {
                             return Expression::Instruction {
                                 name: current.value,
                                 args,
@@ -207,8 +280,9 @@ impl<'tokens> Parser<'tokens> {
                         }
 
                         // 1 argument instructions
-                        "call" | "int" | "push8" | "push16" | "push32" | "push64" | "pop8"
-                        | "pop16" | "pop32" | "pop64" | "jmp" | "jz" | "jnz" | "dbg" => {
+                        "call" | "fcall" | "int" | "push8" | "push16" | "push32" | "push64"
+                        | "pop8" | "pop16" | "pop32" | "pop64" | "jmp" | "jz" | "jnz" | "dbg"
+                        | "enter" | "trap" => {
                             let last_arg = self.expression();
                             let last_arg_span = last_arg.get_span();
 
@@ -225,10 +299,12 @@ impl<'tokens> Parser<'tokens> {
                         }
 
                         // 2 argument instructions
-                        "mov" | "frame8" | "frame16" | "frame32" | "frame64" | "peek8"
+                        "mov" | "lea" | "frame8" | "frame16" | "frame32" | "frame64" | "peek8"
                         | "load8" | "load16" | "load32" | "load64" | "store8" | "store16"
                         | "store32" | "store64" | "peek16" | "peek32" | "peek64" | "add"
-                        | "xadd" | "sub" | "mul" | "div" | "cmp" | "je" | "jne" => {
+                        | "xadd" | "sub" | "mul" | "div" | "cmp" | "je" | "jne" | "setvec"
+                        | "setframe8" | "setframe16" | "setframe32" | "setframe64" | "poke8"
+                        | "poke16" | "poke32" | "poke64" | "assert" => {
                             args.push(self.expression());
 
                             if let Err(err) = self.skip_expected(TokenType::Comma) {
@@ -255,7 +331,45 @@ impl<'tokens> Parser<'tokens> {
                             };
                         }
 
-                        _ => unimplemented!(),
+                        // 3 argument instructions
+                        "memcpy" | "memset" | "memcmp" => {
+                            args.push(self.expression());
+
+                            for _ in 0..2 {
+                                if let Err(err) = self.skip_expected(TokenType::Comma) {
+                                    self.error(*err);
+
+                                    self.skip_token();
+                                    self.skip_token();
+
+                                    return Expression::None;
+                                }
+
+                                args.push(self.expression());
+                            }
+
+                            let last_arg_span = args.last().unwrap().get_span();
+
+                            return Expression::Instruction {
+                                name: current.value,
+                                args,
+                                span: error::position_to_span(
+                                    current.span.offset(),
+                                    last_arg_span.offset() + last_arg_span.len(),
+                                ),
+                            };
+                        }
+
+                        _ => {
+                            self.error(AssemblyError::UnsupportedExpression {
+                                error: format!("Instruction `{}` is not supported", current.value),
+                                label: "unknown arguments count".to_string(),
+                                src: self.src.clone(),
+                                span: current.span,
+                            });
+
+                            return Expression::None;
+                        }
                     }
                 }
 
@@ -331,7 +445,17 @@ impl<'tokens> Parser<'tokens> {
                         };
                     }
 
-                    _ => unimplemented!(),
+                    _ => {
+                        self.skip_token();
+                        self.error(AssemblyError::UnsupportedExpression {
+                            error: format!("Keyword `{}` is not supported", current.value),
+                            label: "unknown keyword".to_string(),
+                            src: self.src.clone(),
+                            span: current.span,
+                        });
+
+                        return Expression::None;
+                    }
                 },
 
                 TokenType::Label => {
@@ -349,6 +473,10 @@ impl<'tokens> Parser<'tokens> {
                     let expr_start = self.position;
                     self.skip_token();
 
+                    if self.expect(TokenType::AsmReg) {
+                        return self.memory_operand(current.span.offset());
+                    }
+
                     let expr = self.expression();
                     let expr_length = expr.get_span().len() + 2;
 
@@ -733,4 +861,67 @@ mod tests {
 
         assert_eq!(ast, [Expression::CurrentPtr((0, 1).into())]);
     }
+
+    #[test]
+    fn parser_memory_operand_test() {
+        const FILENAME: &str = "test";
+        const CODE: &str = "[%r1 + %r2 * $8] [%r1 - $16]";
+
+        let mut lexer = Lexer::new(FILENAME, CODE);
+        let tokens = lexer.tokenize().unwrap();
+
+        let mut parser = Parser::new(FILENAME, CODE, &tokens);
+        let ast = parser.parse().unwrap();
+
+        assert_eq!(
+            ast,
+            [
+                Expression::MemoryOperand {
+                    base: Box::new(Expression::AsmReg(String::from("r1"), (1, 3).into())),
+                    index: Some(Box::new(Expression::AsmReg(
+                        String::from("r2"),
+                        (7, 3).into()
+                    ))),
+                    scale: Some(Box::new(Expression::UIntConstant(8, (13, 2).into()))),
+                    displacement: None,
+                    span: (0, 16).into()
+                },
+                Expression::MemoryOperand {
+                    base: Box::new(Expression::AsmReg(String::from("r1"), (18, 3).into())),
+                    index: None,
+                    scale: None,
+                    displacement: Some(Box::new(Expression::UIntConstant(
+                        16_u64.wrapping_neg(),
+                        (24, 3).into()
+                    ))),
+                    span: (17, 11).into()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parser_instr_3_arg_test() {
+        const FILENAME: &str = "test";
+        const CODE: &str = "memcpy %r0, %r1, %r2";
+
+        let mut lexer = Lexer::new(FILENAME, CODE);
+        let tokens = lexer.tokenize().unwrap();
+
+        let mut parser = Parser::new(FILENAME, CODE, &tokens);
+        let ast = parser.parse().unwrap();
+
+        assert_eq!(
+            ast,
+            [Expression::Instruction {
+                name: String::from("memcpy"),
+                args: vec![
+                    Expression::AsmReg(String::from("r0"), (7, 3).into()),
+                    Expression::AsmReg(String::from("r1"), (12, 3).into()),
+                    Expression::AsmReg(String::from("r2"), (17, 3).into()),
+                ],
+                span: (0, CODE.len()).into()
+            }]
+        );
+    }
 }
@@ -1,7 +1,7 @@
 use super::{Source, error::AssemblyError, parser::expressions::Expression};
 
 use miette::{NamedSource, SourceSpan};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 mod macros;
 
@@ -12,6 +12,8 @@ pub struct Analyzer {
 
     section: Section,
     labels: HashMap<String, SourceSpan>,
+    /// Data section labels defined above current expression (known at compile time)
+    data_labels: HashSet<String>,
 
     labels_analyzed: bool,
     comptime_mode: bool,
@@ -43,6 +45,7 @@ impl Analyzer {
             errors: Vec::new(),
             section: Section::None,
             labels: HashMap::new(),
+            data_labels: HashSet::new(),
             labels_analyzed: false,
             comptime_mode: false,
         }
@@ -56,7 +59,7 @@ impl Analyzer {
 
         self.labels_analyzed = true;
 
-        ast.iter().for_each(|expr| self.visit_expression(expr));
+        ast.iter().for_each(|expr| self.visit_statement(expr));
 
         if !self.errors.is_empty() {
             return Err(&self.errors);
@@ -71,6 +74,19 @@ impl Analyzer {
 }
 
 impl Analyzer {
+    fn visit_statement(&mut self, expression: &Expression) {
+        if let Expression::MemoryOperand { span, .. } = expression {
+            self.error(AssemblyError::NotAllowed {
+                label: String::from("memory operands are allowed only as instruction arguments"),
+                src: self.src.clone(),
+                span: *span,
+            });
+            return;
+        }
+
+        self.visit_expression(expression);
+    }
+
     fn visit_expression(&mut self, expression: &Expression) {
         match expression {
             Expression::SectionDef { id, span } => match Section::try_from(id.as_str()) {
@@ -124,6 +140,10 @@ impl Analyzer {
 
             Expression::LabelDef { id, span } => {
                 if self.labels_analyzed {
+                    if self.section == Section::Data {
+                        self.data_labels.insert(id.clone());
+                    }
+
                     return;
                 };
 
@@ -182,7 +202,7 @@ impl Analyzer {
                 let prev_mode = self.comptime_mode;
                 self.comptime_mode = true;
 
-                self.visit_expression(expr);
+                self.visit_comptime_operand(expr);
 
                 self.comptime_mode = prev_mode;
             }
@@ -194,15 +214,19 @@ impl Analyzer {
             } => {
                 // arguments lengths are verified in parser
 
+                args.iter()
+                    .filter(|arg| matches!(arg, Expression::LabelRef(_, _)))
+                    .for_each(|arg| self.visit_expression(arg));
+
                 match name.as_str() {
-                    "call" => macros::assert_arg!(
+                    "call" | "fcall" => macros::assert_arg!(
                         self,
-                        "label",
+                        "label or register",
                         args.first().unwrap(),
-                        Expression::LabelRef(_, _)
+                        Expression::LabelRef(_, _) | Expression::AsmReg(_, _)
                     ),
 
-                    "ret" => {}
+                    "halt" | "ret" | "fret" | "iret" | "leave" => {}
 
                     "int" => {
                         let arg = args.first().unwrap();
@@ -219,6 +243,23 @@ impl Analyzer {
                         }
                     }
 
+                    "setvec" => {
+                        let vector = args.first().unwrap();
+                        let label = args.get(1).unwrap();
+
+                        macros::assert_arg!(
+                            self,
+                            "u8",
+                            vector,
+                            Expression::UIntConstant(_, _) | Expression::AsmConstant(_, _)
+                        );
+                        macros::assert_arg!(self, "label", label, Expression::LabelRef(_, _));
+
+                        if let Expression::UIntConstant(value, span) = vector {
+                            macros::verify_boundary!(self, *value, *span, u8);
+                        }
+                    }
+
                     "dbg" => {
                         macros::assert_arg!(
                             self,
@@ -228,6 +269,21 @@ impl Analyzer {
                         );
                     }
 
+                    "trap" => macros::assert_arg!(
+                        self,
+                        "label",
+                        args.first().unwrap(),
+                        Expression::LabelRef(_, _)
+                    ),
+
+                    "assert" => {
+                        let value = args.first().unwrap();
+                        let label = args.get(1).unwrap();
+
+                        macros::assert_arg!(self, "register", value, Expression::AsmReg(_, _));
+                        macros::assert_arg!(self, "label", label, Expression::LabelRef(_, _));
+                    }
+
                     "mov" => {
                         assert!(args.len() == 2);
 
@@ -270,21 +326,40 @@ impl Analyzer {
                         });
                     }
 
-                    "load8" | "load16" | "load32" | "load64" | "store8" | "store16" | "store32"
-                    | "store64" => {
+                    "load8" | "load16" | "load32" | "load64" => {
+                        let dest = args.first().unwrap();
+                        let address = args.get(1).unwrap();
+
+                        macros::assert_arg!(self, "register", dest, Expression::AsmReg(_, _));
                         macros::assert_arg!(
                             self,
-                            "register",
-                            args.first().unwrap(),
-                            Expression::AsmReg(_, _)
+                            "register or memory operand",
+                            address,
+                            Expression::AsmReg(_, _) | Expression::MemoryOperand { .. }
                         );
 
+                        self.visit_expression(address);
+                    }
+
+                    "store8" | "store16" | "store32" | "store64" => {
+                        let address = args.first().unwrap();
+                        let src = args.get(1).unwrap();
+
                         macros::assert_arg!(
                             self,
-                            "register",
-                            args.get(1).unwrap(),
-                            Expression::AsmReg(_, _)
+                            "register or memory operand",
+                            address,
+                            Expression::AsmReg(_, _) | Expression::MemoryOperand { .. }
                         );
+                        macros::assert_arg!(self, "register", src, Expression::AsmReg(_, _));
+
+                        self.visit_expression(address);
+                    }
+
+                    "memcpy" | "memset" | "memcmp" => {
+                        for arg in args {
+                            macros::assert_arg!(self, "register", arg, Expression::AsmReg(_, _));
+                        }
                     }
 
                     "push8" | "push16" | "push32" | "push64" => macros::assert_arg!(
@@ -324,6 +399,29 @@ impl Analyzer {
                         }
                     }
 
+                    "setframe8" | "setframe16" | "setframe32" | "setframe64" | "poke8"
+                    | "poke16" | "poke32" | "poke64" => {
+                        let address = args.first().unwrap();
+                        let src = args.get(1).unwrap();
+
+                        macros::assert_arg!(self, "u16", address, Expression::UIntConstant(_, _));
+                        macros::assert_arg!(self, "register", src, Expression::AsmReg(_, _));
+
+                        if let Expression::UIntConstant(value, span) = address {
+                            macros::verify_boundary!(self, *value, *span, u16);
+                        }
+                    }
+
+                    "enter" => {
+                        let size = args.first().unwrap();
+
+                        macros::assert_arg!(self, "u16", size, Expression::UIntConstant(_, _));
+
+                        if let Expression::UIntConstant(value, span) = size {
+                            macros::verify_boundary!(self, *value, *span, u16);
+                        }
+                    }
+
                     "add" | "sub" | "mul" | "div" | "cmp" => {
                         let dest = args.first().unwrap();
                         let src = args.get(1).unwrap();
@@ -344,6 +442,14 @@ impl Analyzer {
                         }
                     }
 
+                    "lea" => {
+                        let dest = args.first().unwrap();
+                        let label = args.get(1).unwrap();
+
+                        macros::assert_arg!(self, "register", dest, Expression::AsmReg(_, _));
+                        macros::assert_arg!(self, "label", label, Expression::LabelRef(_, _));
+                    }
+
                     "xadd" => {
                         let dest = args.first().unwrap();
                         let src = args.get(1).unwrap();
@@ -354,7 +460,12 @@ impl Analyzer {
 
                     "jmp" | "jz" | "jnz" => {
                         let label = args.first().unwrap();
-                        macros::assert_arg!(self, "label", label, Expression::LabelRef(_, _));
+                        macros::assert_arg!(
+                            self,
+                            "label or register",
+                            label,
+                            Expression::LabelRef(_, _) | Expression::AsmReg(_, _)
+                        );
                     }
 
                     "je" | "jne" => {
@@ -362,13 +473,52 @@ impl Analyzer {
                         let label = args.get(1).unwrap();
 
                         macros::assert_arg!(self, "u64", value, Expression::UIntConstant(_, _));
-                        macros::assert_arg!(self, "label", label, Expression::LabelRef(_, _));
+                        macros::assert_arg!(
+                            self,
+                            "label or register",
+                            label,
+                            Expression::LabelRef(_, _) | Expression::AsmReg(_, _)
+                        );
                     }
 
                     _ => unimplemented!(),
                 }
             }
 
+            Expression::MemoryOperand {
+                base,
+                index,
+                scale,
+                displacement,
+                span: _,
+            } => {
+                macros::assert_arg!(self, "register", base.as_ref(), Expression::AsmReg(_, _));
+
+                if let Some(index) = index {
+                    macros::assert_arg!(self, "register", index.as_ref(), Expression::AsmReg(_, _));
+                }
+
+                if let Some(scale) = scale {
+                    match scale.as_ref() {
+                        Expression::UIntConstant(1 | 2 | 4 | 8, _) => {}
+                        _ => self.error(AssemblyError::InvalidArgument {
+                            label: "scale expected to be 1, 2, 4 or 8".to_string(),
+                            src: self.src.clone(),
+                            span: scale.get_span(),
+                        }),
+                    }
+                }
+
+                if let Some(displacement) = displacement {
+                    macros::assert_arg!(
+                        self,
+                        "number",
+                        displacement.as_ref(),
+                        Expression::UIntConstant(_, _) | Expression::AsmConstant(_, _)
+                    );
+                }
+            }
+
             Expression::BinaryExpr {
                 op: _,
                 lhs,
@@ -389,8 +539,8 @@ impl Analyzer {
                     return;
                 }
 
-                self.visit_expression(lhs);
-                self.visit_expression(rhs);
+                self.visit_comptime_operand(lhs);
+                self.visit_comptime_operand(rhs);
             }
 
             Expression::UIntConstant(_, _) => {}
@@ -401,42 +551,63 @@ impl Analyzer {
             }),
 
             Expression::AsmConstant(_, _) => {}
-            Expression::AsmReg(_, span) => {
-                if self.comptime_mode {
-                    self.error(AssemblyError::ComptimeException {
-                        error: String::from("Runtime element found in compile time mode"),
-                        label: String::from("registers values are unknown at compile time"),
-                        src: self.src.clone(),
-                        span: *span,
-                    });
-                }
+            Expression::AsmReg(_, span) if self.comptime_mode => {
+                self.error(AssemblyError::ComptimeException {
+                    error: String::from("Runtime element found in compile time mode"),
+                    label: String::from("registers values are unknown at compile time"),
+                    src: self.src.clone(),
+                    span: *span,
+                });
             }
 
-            Expression::LabelRef(label_name, span) => {
-                if !self.labels.contains_key(label_name) {
-                    self.error(AssemblyError::UnknownLabel {
-                        name: label_name.clone(),
-                        src: self.src.clone(),
-                        span: *span,
-                    });
-                }
+            Expression::LabelRef(label_name, span) if !self.labels.contains_key(label_name) => {
+                self.error(AssemblyError::UnknownLabel {
+                    name: label_name.clone(),
+                    src: self.src.clone(),
+                    span: *span,
+                });
             }
 
-            Expression::CurrentPtr(span) => {
-                if !self.comptime_mode {
-                    self.error(AssemblyError::ComptimeException {
-                        error: String::from(
-                            "Usage of comptime expression without compile time mode",
-                        ),
-                        label: "current pointer is allowed only in comptime expression: \"[EXPR]\""
-                            .to_string(),
-                        src: self.src.clone(),
-                        span: *span,
-                    });
-                }
+            // comptime expressions are calculated in place, so later labels are unknown yet
+            Expression::LabelRef(label_name, span)
+                if self.comptime_mode && !self.data_labels.contains(label_name) =>
+            {
+                self.error(AssemblyError::ComptimeException {
+                    error: format!("Label `{label_name}` is unknown at compile time"),
+                    label: String::from("only `.data` labels defined above are allowed here"),
+                    src: self.src.clone(),
+                    span: *span,
+                });
+            }
+
+            Expression::CurrentPtr(span) if !self.comptime_mode => {
+                self.error(AssemblyError::ComptimeException {
+                    error: String::from("Usage of comptime expression without compile time mode"),
+                    label: "current pointer is allowed only in comptime expression: \"[EXPR]\""
+                        .to_string(),
+                    src: self.src.clone(),
+                    span: *span,
+                });
             }
 
             _ => {}
         }
     }
+
+    fn visit_comptime_operand(&mut self, expression: &Expression) {
+        match expression {
+            Expression::BinaryExpr { .. }
+            | Expression::UIntConstant(_, _)
+            | Expression::LabelRef(_, _)
+            | Expression::CurrentPtr(_)
+            | Expression::AsmReg(_, _) => self.visit_expression(expression),
+
+            _ => self.error(AssemblyError::ComptimeException {
+                error: String::from("Unsupported element found in compile time mode"),
+                label: String::from("only numbers, labels and current pointer are allowed here"),
+                src: self.src.clone(),
+                span: expression.get_span(),
+            }),
+        }
+    }
 }
@@ -1,5 +1,6 @@
-use clap::{Command, arg};
+use clap::{ArgAction, Command, arg};
 use colored::Colorize;
+use mvm::vm::{self, DebugInfo, MvmError};
 
 pub fn cli() -> Command {
     Command::new("mvm")
@@ -13,7 +14,33 @@ pub fn cli() -> Command {
                 .about("run virtual machine with compiled program")
                 .arg(arg!(-m <MEMSIZE> "machine memory size in bytes").required(false))
                 .arg(arg!(-s <STACKSIZE> "stack size in bytes").required(false))
-                .arg(arg!(<PROGRAM> "path to program binary file")),
+                .arg(
+                    arg!(--"max-memory" <BYTES> "allow heap to grow up to this memory size")
+                        .required(false),
+                )
+                .arg(
+                    arg!(--"fs-root" <DIR> "sandbox directory for filesystem system calls")
+                        .required(false),
+                )
+                .arg(
+                    arg!(-e --env <NAME> "pass environment variable (`NAME` or `NAME=VALUE`)")
+                        .required(false)
+                        .action(ArgAction::Append),
+                )
+                .arg(arg!(--"self-modifying" "allow program to write to its own code"))
+                .arg(arg!(--"nx-data" "forbid execution of data section and constants"))
+                .arg(arg!(--"no-decode-cache" "decode instructions on every execution (slower)"))
+                .arg(arg!(--"heap-check" "guard heap blocks, detect double free and report leaks"))
+                .arg(arg!(--"mem-stats" "print stack and heap usage peaks at exit"))
+                .arg(arg!(--profile "print hot spots and write folded call stacks at exit"))
+                .arg(arg!(--coverage "write lcov coverage report at exit (requires debug info)"))
+                .arg(arg!(--"no-verify" "skip bytecode verification before execution"))
+                .arg(arg!(<PROGRAM> "path to program binary file"))
+                .arg(
+                    arg!([ARGS] ... "arguments passed to program (after `--`)")
+                        .last(true)
+                        .required(false),
+                ),
         )
         .subcommand(
             Command::new("compile")
@@ -21,14 +48,87 @@ pub fn cli() -> Command {
                 .arg(arg!(<ASM> "assembly file path"))
                 .arg_required_else_help(true),
         )
+        .subcommand(
+            Command::new("verify")
+                .about("check compiled program bytecode without running it")
+                .arg(arg!(<PROGRAM> "path to program binary file"))
+                .arg_required_else_help(true),
+        )
+        .subcommand(
+            Command::new("test")
+                .about("compile and run programs, compare their output with expectations")
+                .arg(arg!(<DIR> "directory with assembly programs"))
+                .arg_required_else_help(true),
+        )
 }
 
 pub fn error(message: impl std::fmt::Display) {
     eprintln!("{} {}", "Error:".red().bold(), message);
 }
 
-pub fn vm_error(message: impl std::fmt::Display) {
-    eprintln!("{} {}", "MVM PANIC:".red().bold(), message);
+/// Prints machine error with faulting instruction and registers (if error has runtime context)
+pub fn vm_error(error: &MvmError, debug_info: &DebugInfo) {
+    eprintln!("{} {}", "MVM PANIC:".red().bold(), error.inner());
+
+    let Some(context) = error.context() else {
+        return;
+    };
+
+    let address = context.address;
+    let mut location = vec![(
+        "instruction",
+        format!("{address} ({})", debug_info.locate(address)),
+    )];
+
+    if let (Some(source), Some(line)) = (&debug_info.source, debug_info.lines.get(&address)) {
+        location.push(("source", format!("{source}:{line}")));
+    }
+
+    location.push((
+        "opcode",
+        context
+            .opcode
+            .map(|opcode| format!("{opcode:?}"))
+            .unwrap_or(String::from("unknown")),
+    ));
+
+    location.push((
+        "operands",
+        context
+            .operands
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<Vec<_>>()
+            .join(" "),
+    ));
+
+    report("Error location:", &location);
+
+    let registers = context
+        .registers
+        .iter()
+        .enumerate()
+        .map(|(index, value)| {
+            (
+                format!("%{}", vm::register_to_str(index as u64)),
+                format!("{value} ({value:#x})"),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    report("Registers:", &registers);
+}
+
+pub fn warning(message: impl std::fmt::Display) {
+    eprintln!("{} {}", "Warning:".yellow().bold(), message);
+}
+
+pub fn report(title: impl std::fmt::Display, entries: &[(impl std::fmt::Display, String)]) {
+    eprintln!("{}", title.to_string().cyan().bold());
+
+    for (name, value) in entries {
+        eprintln!("  {:<24} {value}", name.to_string());
+    }
 }
 
 pub fn info(start: impl AsRef<str>, message: impl std::fmt::Display) {
@@ -0,0 +1,8 @@
+//! MVM (Mealet's Virtual Machine) - simple and fast virtual machine written in Rust.
+//! Library part exposes the assembler and the virtual machine (used by the binary and fuzz targets).
+
+pub mod assembly;
+pub mod vm;
+
+pub const MEMSIZE_DEFAULT: usize = 1024;
+pub const STACKSIZE_DEFAULT: usize = 256;
@@ -2,13 +2,10 @@
 //! Source code, licsense, examples and etc: https://github.com/mealet/mvm
 
 use colored::Colorize;
+use mvm::{MEMSIZE_DEFAULT, STACKSIZE_DEFAULT, assembly, vm};
 
-mod assembly;
 mod cli;
-mod vm;
-
-pub const MEMSIZE_DEFAULT: usize = 1024;
-pub const STACKSIZE_DEFAULT: usize = 256;
+mod runner;
 
 fn main() {
     let cli = cli::cli().try_get_matches().unwrap_or_else(|e| {
@@ -45,6 +42,15 @@ fn main() {
                 eprintln!("  {bin} compile hello_world.asm");
                 eprintln!("  {bin} run hello_world.mvm");
                 eprintln!("  {bin} run hello_world.mvm -m 1024 -s 256");
+                eprintln!("  {bin} run files.mvm --fs-root ./sandbox");
+                eprintln!("  {bin} run args.mvm -e HOME -- arg1 arg2");
+                eprintln!("  {bin} run hello_world.mvm --nx-data");
+                eprintln!("  {bin} run allocator_usage.mvm --max-memory 65536");
+                eprintln!("  {bin} run allocator_usage.mvm --heap-check --mem-stats");
+                eprintln!("  {bin} run callbacks.mvm --profile");
+                eprintln!("  {bin} run strings_comparison.mvm --coverage");
+                eprintln!("  {bin} verify hello_world.mvm");
+                eprintln!("  {bin} test examples");
 
                 if e.kind() == clap::error::ErrorKind::DisplayHelp {
                     std::process::exit(0);
@@ -66,63 +72,49 @@ fn main() {
                 std::process::exit(1);
             });
 
-            let reporter = miette::GraphicalReportHandler::new();
-
             cli::info("Compiling", format!("assembly file ({})", path_to_asm));
 
-            let mut lexer = assembly::lexer::Lexer::new("TEST", &code);
-            let tokens = lexer.tokenize().unwrap_or_else(|errors| {
-                for err in errors {
-                    let mut buffer = String::new();
-                    let _ = reporter.render_report(&mut buffer, err);
-
-                    eprintln!("{}", buffer);
-                }
-
-                std::process::exit(1);
-            });
-
-            let mut parser = assembly::parser::Parser::new("TEST", &code, &tokens);
-            let ast = parser.parse().unwrap_or_else(|errors| {
-                for err in errors {
-                    let mut buffer = String::new();
-                    let _ = reporter.render_report(&mut buffer, err);
-
-                    eprintln!("{}", buffer);
-                }
-
-                std::process::exit(1);
-            });
-
-            let mut analyzer = assembly::semantic::Analyzer::new("TEST", &code);
-            analyzer.analyze(&ast).unwrap_or_else(|errors| {
-                for err in errors {
-                    let mut buffer = String::new();
-                    let _ = reporter.render_report(&mut buffer, err);
-
-                    eprintln!("{}", buffer);
-                }
-
-                std::process::exit(1);
-            });
-
             // release mode flag
             let release_mode = sub_matches.get_flag("release");
 
-            let mut codegen = assembly::codegen::Codegen::new(release_mode);
-            let code = codegen.compile(&ast);
+            let (binary, codegen) =
+                assembly::assemble("TEST", &code, release_mode).unwrap_or_else(|reports| {
+                    for report in reports {
+                        eprintln!("{}", report);
+                    }
+
+                    std::process::exit(1);
+                });
 
             cli::info("Writing", "generated code to binary");
 
             let new_file = path_to_asm.replace(".asm", ".mvm");
 
-            std::fs::write(&new_file, code).unwrap_or_else(|err| {
+            std::fs::write(&new_file, binary).unwrap_or_else(|err| {
                 cli::error(format!(
                     "Unable to write generated code to binary file [{}]",
                     err
                 ));
             });
 
+            // labels and source lines for profiler and coverage reports
+            let debug_file = path_to_asm.replace(".asm", ".dbg");
+
+            if release_mode {
+                // debug info of previous build would describe another binary
+                let _ = std::fs::remove_file(&debug_file);
+            } else {
+                let source_path = std::fs::canonicalize(path_to_asm)
+                    .map(|path| path.display().to_string())
+                    .unwrap_or(path_to_asm.to_owned());
+
+                let debug_info = codegen.debug_info(&source_path, &code);
+
+                std::fs::write(&debug_file, debug_info).unwrap_or_else(|err| {
+                    cli::error(format!("Unable to write debug info file [{}]", err));
+                });
+            }
+
             cli::info(
                 "Successfully",
                 format!(
@@ -157,22 +149,7 @@ fn main() {
             let mut memsize = memsize.parse::<usize>().unwrap_or(0);
             let mut stacksize = stacksize.parse::<usize>().unwrap_or(0);
 
-            let mut metadata = Vec::new();
-
-            let mut lptr = 0;
-            let mut rptr = 1;
-
-            while let Some(lhs) = program.get(lptr)
-                && let Some(rhs) = program.get(rptr)
-                && (*lhs != 0xFF || *rhs != vm::Opcode::DataSection as u8)
-            {
-                metadata.push(*lhs);
-
-                lptr += 1;
-                rptr += 1;
-            }
-
-            metadata.push(255);
+            let metadata = read_metadata(&program);
 
             if !metadata.is_empty() && memsize != 0 && stacksize != 0 {
                 if metadata.len() < METADATA_MINIMUM_LENGTH {
@@ -203,6 +180,12 @@ fn main() {
                 ]) as usize;
             }
 
+            // constant pool size is optional, older binaries don't have it
+            let constants_size = metadata
+                .get(16..24)
+                .map(|bytes| u64::from_be_bytes(bytes.try_into().expect("8 bytes slice")))
+                .unwrap_or(0);
+
             memsize = if memsize == 0 {
                 MEMSIZE_DEFAULT
             } else {
@@ -214,25 +197,333 @@ fn main() {
                 stacksize
             };
 
-            program = program[metadata.len()..].to_vec();
+            let debug_info = read_debug_info(program_path, &program);
+
+            program = program.get(metadata.len()..).unwrap_or_default().to_vec();
+
+            if !sub_matches.get_flag("no-verify") && !verify_program(&program, &debug_info) {
+                cli::error("Program verification failed (use `--no-verify` to run it anyway)");
+                std::process::exit(1);
+            }
 
             let mut vm = vm::VM::new(memsize, stacksize).unwrap_or_else(|err| {
                 cli::error(format!("Unable to create VM instance [{}]", err));
                 std::process::exit(1);
             });
 
+            if let Some(fs_root) = sub_matches.get_one::<String>("fs-root") {
+                vm.filesystem.set_root(fs_root).unwrap_or_else(|err| {
+                    cli::error(format!("Unable to set filesystem root [{}]", err));
+                    std::process::exit(1);
+                });
+            }
+
+            if let Some(max_memory) = sub_matches.get_one::<String>("max-memory") {
+                let max_memory = max_memory.parse::<usize>().unwrap_or_else(|err| {
+                    cli::error(format!("Invalid memory limit [{}]", err));
+                    std::process::exit(1);
+                });
+
+                if max_memory < memsize {
+                    cli::error(format!(
+                        "Memory limit ({max_memory}) is less than memory size ({memsize})"
+                    ));
+                    std::process::exit(1);
+                }
+
+                vm.memory_limit = Some(max_memory);
+            }
+
+            vm.constants_size = constants_size;
+            vm.self_modifying = sub_matches.get_flag("self-modifying");
+            vm.no_exec_data = sub_matches.get_flag("nx-data");
+            vm.decode_cache.enabled = !sub_matches.get_flag("no-decode-cache");
+            vm.heap_check.enabled = sub_matches.get_flag("heap-check");
+            vm.profiler.enabled = sub_matches.get_flag("profile");
+            vm.coverage.enabled = sub_matches.get_flag("coverage");
+
+            vm.debug_info = debug_info;
+
+            vm.arguments = std::iter::once(program_path.clone())
+                .chain(
+                    sub_matches
+                        .get_many::<String>("ARGS")
+                        .unwrap_or_default()
+                        .cloned(),
+                )
+                .collect();
+
+            vm.environment = sub_matches
+                .get_many::<String>("env")
+                .unwrap_or_default()
+                .filter_map(|name| {
+                    if name.contains('=') {
+                        return Some(name.clone());
+                    }
+
+                    std::env::var(name)
+                        .ok()
+                        .map(|value| format!("{name}={value}"))
+                })
+                .collect();
+
             vm.insert_program(&program).unwrap_or_else(|err| {
                 cli::error(format!("Unable to load the program [{}]", err));
+                std::process::exit(1);
             });
 
-            vm.run().unwrap_or_else(|err| {
-                cli::vm_error(err);
+            let result = vm.run();
+
+            if sub_matches.get_flag("mem-stats") {
+                let heap = vm.heap_stats();
+
+                cli::report(
+                    "Memory statistics:",
+                    &[
+                        ("memory size", format!("{} bytes", vm.memory.len())),
+                        (
+                            "stack peak",
+                            format!("{} / {} bytes", vm.stack_peak_depth(), vm.stack_size),
+                        ),
+                        ("heap in use", format!("{} bytes", heap.used)),
+                        ("heap peak", format!("{} bytes", heap.peak_used)),
+                        ("heap free", format!("{} bytes", heap.free)),
+                        ("largest free block", format!("{} bytes", heap.largest_free)),
+                    ],
+                );
+            }
+
+            if vm.profiler.enabled {
+                print_profile(&vm, program_path);
+            }
+
+            if vm.coverage.enabled {
+                write_coverage(&vm, program_path);
+            }
+
+            result.unwrap_or_else(|err| {
+                cli::vm_error(&err, &vm.debug_info);
                 std::process::exit(1);
             });
 
+            if vm.heap_check.enabled {
+                let leaks = vm.heap_check_report().unwrap_or_else(|err| {
+                    cli::vm_error(&err, &vm.debug_info);
+                    std::process::exit(1);
+                });
+
+                if !leaks.is_empty() {
+                    let leaked_bytes = leaks.iter().map(|leak| leak.size).sum::<usize>();
+
+                    cli::warning(format!(
+                        "{} heap block(s) leaked ({leaked_bytes} bytes):",
+                        leaks.len()
+                    ));
+
+                    for leak in leaks {
+                        eprintln!(
+                            "  block `{}` of {} bytes allocated at {}",
+                            leak.ptr, leak.size, leak.alloc_site
+                        );
+                    }
+                }
+            }
+
             std::process::exit(vm.exit_code as i32);
         }
 
+        Some(("verify", sub_matches)) => {
+            let program_path = sub_matches
+                .get_one::<String>("PROGRAM")
+                .expect("no program path found");
+            let program = std::fs::read(program_path).unwrap_or_else(|err| {
+                cli::error(format!("Unable to read binary program [{}]", err));
+                std::process::exit(1);
+            });
+
+            let metadata = read_metadata(&program);
+            let debug_info = read_debug_info(program_path, &program);
+
+            if !verify_program(
+                program.get(metadata.len()..).unwrap_or_default(),
+                &debug_info,
+            ) {
+                std::process::exit(1);
+            }
+
+            cli::info("Successfully", format!("verified program: {program_path}"));
+        }
+
+        Some(("test", sub_matches)) => {
+            let tests_dir = sub_matches
+                .get_one::<String>("DIR")
+                .expect("tests directory required");
+
+            let passed = runner::run(std::path::Path::new(tests_dir)).unwrap_or_else(|err| {
+                cli::error(err);
+                std::process::exit(1);
+            });
+
+            std::process::exit(if passed { 0 } else { 1 });
+        }
+
         _ => unreachable!(),
     }
 }
+
+/// Binary metadata: everything before data section (including `0xFF` separator)
+fn read_metadata(program: &[u8]) -> Vec<u8> {
+    let mut metadata = Vec::new();
+
+    let mut lptr = 0;
+    let mut rptr = 1;
+
+    while let Some(lhs) = program.get(lptr)
+        && let Some(rhs) = program.get(rptr)
+        && (*lhs != 0xFF || *rhs != vm::Opcode::DataSection as u8)
+    {
+        metadata.push(*lhs);
+
+        lptr += 1;
+        rptr += 1;
+    }
+
+    metadata.push(255);
+    metadata
+}
+
+/// Reads debug info from `.dbg` file next to the program.
+/// Debug info is optional (it's produced only in debug mode), stale file is ignored.
+fn read_debug_info(program_path: &str, program: &[u8]) -> vm::DebugInfo {
+    let debug_file = program_path.replace(".mvm", ".dbg");
+
+    let Ok(debug_info) = std::fs::read_to_string(&debug_file) else {
+        return vm::DebugInfo::default();
+    };
+
+    let debug_info = vm::DebugInfo::parse(&debug_info);
+
+    if !debug_info.matches(program) {
+        cli::warning(format!(
+            "Debug info `{debug_file}` doesn't match the program and will be ignored (recompile it in debug mode)"
+        ));
+
+        return vm::DebugInfo::default();
+    }
+
+    debug_info
+}
+
+/// Prints verifier errors with their locations, returns `true` if program is valid
+fn verify_program(program: &[u8], debug_info: &vm::DebugInfo) -> bool {
+    let Err(errors) = vm::verify(program) else {
+        return true;
+    };
+
+    for err in errors {
+        match err.address() {
+            Some(address) => cli::error(format!("{err} ({})", debug_info.locate(address))),
+            None => cli::error(err),
+        }
+    }
+
+    false
+}
+
+fn print_profile(vm: &vm::VM, program_path: &str) {
+    const HOT_SPOTS_LIMIT: usize = 10;
+
+    let total = vm.profiler.total.max(1);
+    let percent = |count: u64| format!("{count} ({:.1}%)", count as f64 * 100.0 / total as f64);
+
+    cli::report(
+        "Profile:",
+        &[("instructions executed", vm.profiler.total.to_string())],
+    );
+
+    let opcodes = vm
+        .profiler
+        .hot_opcodes(HOT_SPOTS_LIMIT)
+        .into_iter()
+        .map(|(opcode, count)| (format!("{opcode:?}"), percent(count)))
+        .collect::<Vec<_>>();
+
+    cli::report("Hot opcodes:", &opcodes);
+
+    let addresses = vm
+        .profiler
+        .hot_addresses(HOT_SPOTS_LIMIT)
+        .into_iter()
+        .map(|(address, count)| {
+            (
+                format!("{address} ({})", vm.debug_info.locate(address)),
+                percent(count),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    cli::report("Hot addresses:", &addresses);
+
+    let functions = vm
+        .profiler
+        .functions()
+        .into_iter()
+        .take(HOT_SPOTS_LIMIT)
+        .map(|function| {
+            let name = function
+                .address
+                .map(|address| vm.debug_info.locate(address))
+                .unwrap_or(vm::ROOT_FRAME.to_owned());
+
+            (
+                name,
+                format!(
+                    "self {}, total {}",
+                    percent(function.self_count),
+                    percent(function.total_count)
+                ),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    cli::report("Functions:", &functions);
+
+    let folded_file = format!("{}.folded", program_path.trim_end_matches(".mvm"));
+
+    match std::fs::write(&folded_file, vm.profiler.folded_stacks(&vm.debug_info)) {
+        Ok(()) => eprintln!("Folded call stacks are written to {folded_file}"),
+        Err(err) => cli::error(format!("Unable to write folded call stacks [{}]", err)),
+    }
+}
+
+fn write_coverage(vm: &vm::VM, program_path: &str) {
+    let Some((report, summary)) = vm.coverage_report() else {
+        cli::warning("Coverage report requires debug info file (compile program in debug mode)");
+        return;
+    };
+
+    let percent = |hit: usize, found: usize| {
+        format!(
+            "{hit} / {found} ({:.1}%)",
+            hit as f64 * 100.0 / found.max(1) as f64
+        )
+    };
+
+    cli::report(
+        "Coverage:",
+        &[
+            ("lines", percent(summary.lines_hit, summary.lines_found)),
+            (
+                "branches",
+                percent(summary.branches_hit, summary.branches_found),
+            ),
+        ],
+    );
+
+    let lcov_file = format!("{}.lcov", program_path.trim_end_matches(".mvm"));
+
+    match std::fs::write(&lcov_file, report) {
+        Ok(()) => eprintln!("Coverage report is written to {lcov_file}"),
+        Err(err) => cli::error(format!("Unable to write coverage report [{}]", err)),
+    }
+}
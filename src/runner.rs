@@ -0,0 +1,573 @@
+//! **Test Runner** (`mvm test <dir>`) assembles every `.asm` program in directory, runs it in a
+//! separate VM process and compares its output and exit code with expectations.
+//! Expectations are written in source comments:
+//!
+//! ```asm
+//! ; expect-stdout: Hello, World!
+//! ; expect-stderr: some error
+//! ; expect-exit: 1
+//! ; stdin: input line
+//! ; run-flags: --fs-root . --heap-check
+//! ; args: hello world
+//! ```
+//!
+//! Output and stdin directives can be repeated, each one adds a line. Sidecar files
+//! (`<name>.stdout`, `<name>.stderr`, `<name>.stdin` and `<name>.exit`) take precedence over
+//! comments. Output is compared only if it's expected, exit code is expected to be 0 by default.
+//!
+//! Programs are compiled and executed in temporary directory (removed after the run), so tests
+//! directory is left untouched. Relative `--fs-root` flag is resolved from tests directory.
+
+use colored::Colorize;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::cli;
+use mvm::assembly;
+
+/// Time given to program before it is killed
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Expectations {
+    pub stdin: String,
+    pub args: Vec<String>,
+    pub flags: Vec<String>,
+
+    pub stdout: Option<String>,
+    pub stderr: Option<String>,
+    pub exit_code: i32,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Output {
+    pub stdout: String,
+    pub stderr: String,
+    /// `None` if program was killed (by signal or timeout)
+    pub exit_code: Option<i32>,
+    pub timed_out: bool,
+}
+
+impl Expectations {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut expectations = Self::default();
+
+        for line in source.lines() {
+            let Some(comment) = line.trim_start().strip_prefix(';') else {
+                continue;
+            };
+
+            let Some((directive, value)) = comment.trim_start().split_once(':') else {
+                continue;
+            };
+
+            // only one separating space is skipped, so lines can start with whitespace
+            let value = value.strip_prefix(' ').unwrap_or(value);
+
+            match directive.trim_end() {
+                "expect-stdout" => push_line(&mut expectations.stdout, value),
+                "expect-stderr" => push_line(&mut expectations.stderr, value),
+
+                "expect-exit" => {
+                    expectations.exit_code = value
+                        .trim()
+                        .parse()
+                        .map_err(|err| format!("Invalid expected exit code [{}]", err))?;
+                }
+
+                "stdin" => {
+                    expectations.stdin.push_str(value);
+                    expectations.stdin.push('\n');
+                }
+
+                "run-flags" => expectations
+                    .flags
+                    .extend(value.split_whitespace().map(str::to_owned)),
+
+                "args" => expectations
+                    .args
+                    .extend(value.split_whitespace().map(str::to_owned)),
+
+                _ => {}
+            }
+        }
+
+        Ok(expectations)
+    }
+
+    fn load_sidecars(&mut self, source_path: &Path) -> Result<(), String> {
+        let read = |extension: &str| {
+            let path = source_path.with_extension(extension);
+
+            match std::fs::read_to_string(&path) {
+                Ok(content) => Ok(Some(content)),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(err) => Err(format!("Unable to read `{}` [{}]", path.display(), err)),
+            }
+        };
+
+        if let Some(stdout) = read("stdout")? {
+            self.stdout = Some(stdout);
+        }
+
+        if let Some(stderr) = read("stderr")? {
+            self.stderr = Some(stderr);
+        }
+
+        if let Some(stdin) = read("stdin")? {
+            self.stdin = stdin;
+        }
+
+        if let Some(exit_code) = read("exit")? {
+            self.exit_code = exit_code
+                .trim()
+                .parse()
+                .map_err(|err| format!("Invalid expected exit code [{}]", err))?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns descriptions of failed expectations
+    pub fn check(&self, output: &Output) -> Vec<String> {
+        let mut failures = Vec::new();
+
+        if output.timed_out {
+            failures.push(format!("timed out after {} seconds", TIMEOUT.as_secs()));
+        } else if output.exit_code != Some(self.exit_code) {
+            failures.push(format!(
+                "exit code {} (expected {})",
+                output
+                    .exit_code
+                    .map(|code| code.to_string())
+                    .unwrap_or(String::from("none")),
+                self.exit_code
+            ));
+
+            // unexpected errors (e.g. failed assertions) are shown as they are
+            if self.stderr.is_none() && !output.stderr.is_empty() {
+                failures.push(format!("stderr:\n{}", indent(&output.stderr)));
+            }
+        }
+
+        let streams = [
+            ("stdout", &self.stdout, &output.stdout),
+            ("stderr", &self.stderr, &output.stderr),
+        ];
+
+        for (name, expected, actual) in streams {
+            if let Some(expected) = expected
+                && expected != actual
+            {
+                failures.push(format!("{name} differs:\n{}", diff(expected, actual)));
+            }
+        }
+
+        failures
+    }
+}
+
+/// Runs all tests in directory and prints report, returns `true` if all of them passed
+pub fn run(dir: &Path) -> Result<bool, String> {
+    let mut sources = std::fs::read_dir(dir)
+        .map_err(|err| {
+            format!(
+                "Unable to read tests directory `{}` [{}]",
+                dir.display(),
+                err
+            )
+        })?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "asm"))
+        .collect::<Vec<_>>();
+
+    sources.sort();
+
+    let build_dir = std::env::temp_dir().join(format!("mvm-test-{}", std::process::id()));
+
+    std::fs::create_dir_all(&build_dir).map_err(|err| {
+        format!(
+            "Unable to create build directory `{}` [{}]",
+            build_dir.display(),
+            err
+        )
+    })?;
+
+    let mut failed = 0;
+
+    for source_path in &sources {
+        let name = source_path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+
+        let failures = match run_test(source_path, &build_dir) {
+            Ok(failures) => failures,
+            Err(err) => vec![err],
+        };
+
+        if failures.is_empty() {
+            cli::info("PASS", &name);
+            continue;
+        }
+
+        failed += 1;
+        println!("{} {}", "FAIL".red().bold(), name);
+
+        for failure in failures {
+            for line in failure.lines() {
+                println!("  {line}");
+            }
+        }
+    }
+
+    let _ = std::fs::remove_dir_all(&build_dir);
+
+    let summary = format!("{} passed, {} failed", sources.len() - failed, failed);
+
+    if failed == 0 {
+        cli::info("Result:", summary);
+    } else {
+        println!("{} {}", "Result:".red().bold(), summary);
+    }
+
+    Ok(failed == 0)
+}
+
+fn run_test(source_path: &Path, build_dir: &Path) -> Result<Vec<String>, String> {
+    let source = std::fs::read_to_string(source_path)
+        .map_err(|err| format!("Unable to read assembly source code [{}]", err))?;
+
+    let mut expectations = Expectations::parse(&source)?;
+    expectations.load_sidecars(source_path)?;
+
+    let program_path = build(source_path, &source, build_dir)?;
+
+    let output = execute(&program_path, source_path, &expectations)
+        .map_err(|err| format!("Unable to run the program [{}]", err))?;
+
+    Ok(expectations.check(&output))
+}
+
+/// Compiles program into build directory with its debug info (for `--profile` and `--coverage`
+/// flags), returns path of the binary
+fn build(source_path: &Path, source: &str, build_dir: &Path) -> Result<PathBuf, String> {
+    let (binary, codegen) = assembly::assemble("TEST", source, false)
+        .map_err(|reports| format!("compilation failed:\n{}", reports.join("\n")))?;
+
+    let program_path = build_dir
+        .join(source_path.file_name().unwrap_or_default())
+        .with_extension("mvm");
+
+    std::fs::write(&program_path, binary)
+        .map_err(|err| format!("Unable to write binary file [{}]", err))?;
+
+    let source_name = std::fs::canonicalize(source_path)
+        .unwrap_or(source_path.to_owned())
+        .display()
+        .to_string();
+
+    std::fs::write(
+        program_path.with_extension("dbg"),
+        codegen.debug_info(&source_name, source),
+    )
+    .map_err(|err| format!("Unable to write debug info file [{}]", err))?;
+
+    Ok(program_path)
+}
+
+/// Runs program with `mvm run` in its directory
+fn execute(
+    program_path: &Path,
+    source_path: &Path,
+    expectations: &Expectations,
+) -> std::io::Result<Output> {
+    let program_dir = program_path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let program_name = program_path.file_name().unwrap_or_default();
+
+    let source_dir = source_path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+
+    let mut command = Command::new(std::env::current_exe()?);
+
+    command
+        .arg("run")
+        .arg(program_name)
+        .args(resolve_flags(
+            &expectations.flags,
+            &std::path::absolute(source_dir)?,
+        ))
+        .current_dir(program_dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    if !expectations.args.is_empty() {
+        command.arg("--").args(&expectations.args);
+    }
+
+    let mut child = command.spawn()?;
+
+    // streams are handled in threads, so filled pipes don't block the program
+    let stdin = child.stdin.take().map(|mut pipe| {
+        let input = expectations.stdin.clone();
+
+        thread::spawn(move || {
+            let _ = pipe.write_all(input.as_bytes());
+        })
+    });
+
+    let stdout = child.stdout.take().map(capture);
+    let stderr = child.stderr.take().map(capture);
+
+    let started = Instant::now();
+    let mut timed_out = false;
+
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+
+        if started.elapsed() > TIMEOUT {
+            timed_out = true;
+            child.kill()?;
+
+            break child.wait()?;
+        }
+
+        thread::sleep(Duration::from_millis(5));
+    };
+
+    if let Some(stdin) = stdin {
+        let _ = stdin.join();
+    }
+
+    let join = |handle: Option<JoinHandle<String>>| {
+        handle
+            .and_then(|handle| handle.join().ok())
+            .unwrap_or_default()
+    };
+
+    Ok(Output {
+        stdout: join(stdout),
+        stderr: join(stderr),
+        exit_code: status.code(),
+        timed_out,
+    })
+}
+
+/// Makes relative `--fs-root` value start from tests directory
+fn resolve_flags(flags: &[String], source_dir: &Path) -> Vec<String> {
+    let resolve = |root: &str| source_dir.join(root).display().to_string();
+    let mut resolved = Vec::new();
+    let mut flags = flags.iter();
+
+    while let Some(flag) = flags.next() {
+        if let Some(root) = flag.strip_prefix("--fs-root=") {
+            resolved.push(format!("--fs-root={}", resolve(root)));
+            continue;
+        }
+
+        resolved.push(flag.clone());
+
+        if flag == "--fs-root"
+            && let Some(root) = flags.next()
+        {
+            resolved.push(resolve(root));
+        }
+    }
+
+    resolved
+}
+
+fn capture(mut pipe: impl Read + Send + 'static) -> JoinHandle<String> {
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        let _ = pipe.read_to_end(&mut buffer);
+
+        String::from_utf8_lossy(&buffer).into_owned()
+    })
+}
+
+fn indent(text: &str) -> String {
+    text.lines().map(|line| format!("  {line}\n")).collect()
+}
+
+fn push_line(stream: &mut Option<String>, line: &str) {
+    let stream = stream.get_or_insert_default();
+
+    stream.push_str(line);
+    stream.push('\n');
+}
+
+/// Line by line difference: `-` for expected lines, `+` for actual ones
+pub fn diff(expected: &str, actual: &str) -> String {
+    let expected = expected.split_inclusive('\n').collect::<Vec<_>>();
+    let actual = actual.split_inclusive('\n').collect::<Vec<_>>();
+
+    let display = |line: &str| match line.strip_suffix('\n') {
+        Some(line) => line.escape_debug().to_string(),
+        None => format!("{} (no newline at end)", line.escape_debug()),
+    };
+
+    let mut output = String::new();
+
+    for index in 0..expected.len().max(actual.len()) {
+        match (expected.get(index), actual.get(index)) {
+            (Some(expected), Some(actual)) if expected == actual => {
+                output.push_str(&format!("  {}\n", display(expected)));
+            }
+
+            (expected, actual) => {
+                if let Some(expected) = expected {
+                    output.push_str(&format!("- {}\n", display(expected)));
+                }
+
+                if let Some(actual) = actual {
+                    output.push_str(&format!("+ {}\n", display(actual)));
+                }
+            }
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expectations_parse_test() {
+        let expectations = Expectations::parse(
+            "; Example program\n\
+            ; expect-stdout: Hello,\n\
+            ;expect-stdout:   World!\n\
+            ; expect-stdout:\n\
+            ; expect-exit: 3\n\
+            ; stdin: input\n\
+            ; run-flags: --fs-root . --heap-check\n\
+            ; args: one two\n\
+            mov %r0, $0 ; expect-stderr: not a directive\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            expectations,
+            Expectations {
+                stdin: String::from("input\n"),
+                args: vec![String::from("one"), String::from("two")],
+                flags: vec![
+                    String::from("--fs-root"),
+                    String::from("."),
+                    String::from("--heap-check")
+                ],
+                stdout: Some(String::from("Hello,\n  World!\n\n")),
+                stderr: None,
+                exit_code: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn expectations_invalid_exit_code_test() {
+        assert_eq!(
+            Expectations::parse("; expect-exit: one\n"),
+            Err(String::from(
+                "Invalid expected exit code [invalid digit found in string]"
+            ))
+        );
+    }
+
+    #[test]
+    fn expectations_check_test() {
+        let expectations = Expectations::parse("; expect-stdout: ok\n").unwrap();
+
+        let mut output = Output {
+            stdout: String::from("ok\n"),
+            stderr: String::from("ignored"),
+            exit_code: Some(0),
+            timed_out: false,
+        };
+
+        assert!(expectations.check(&output).is_empty());
+
+        output.stdout = String::from("fail\n");
+        output.exit_code = None;
+
+        assert_eq!(
+            expectations.check(&output),
+            vec![
+                String::from("exit code none (expected 0)"),
+                String::from("stderr:\n  ignored\n"),
+                String::from("stdout differs:\n- ok\n+ fail\n"),
+            ]
+        );
+    }
+
+    #[test]
+    fn build_test() -> Result<(), String> {
+        let root = std::env::temp_dir().join(format!("mvm-runner-build-{}", std::process::id()));
+        let (source_dir, build_dir) = (root.join("tests"), root.join("build"));
+
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&source_dir).map_err(|err| err.to_string())?;
+        std::fs::create_dir_all(&build_dir).map_err(|err| err.to_string())?;
+
+        let source = "section .data\nsection .text\nentry _start\n_start:\n  ret\n";
+        let source_path = source_dir.join("ret.asm");
+
+        // binary of the same name is kept by user
+        std::fs::write(&source_path, source).map_err(|err| err.to_string())?;
+        std::fs::write(source_dir.join("ret.mvm"), "user file").map_err(|err| err.to_string())?;
+
+        let program_path = build(&source_path, source, &build_dir)?;
+
+        let mut files = std::fs::read_dir(&source_dir)
+            .map_err(|err| err.to_string())?
+            .filter_map(|entry| entry.ok().map(|entry| entry.file_name()))
+            .collect::<Vec<_>>();
+        files.sort();
+
+        assert_eq!(files, ["ret.asm", "ret.mvm"]);
+        assert_eq!(
+            std::fs::read_to_string(source_dir.join("ret.mvm")).ok(),
+            Some(String::from("user file"))
+        );
+
+        assert_eq!(program_path, build_dir.join("ret.mvm"));
+        assert!(program_path.with_extension("dbg").is_file());
+
+        let _ = std::fs::remove_dir_all(&root);
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_flags_test() {
+        let flags = ["--heap-check", "--fs-root", ".", "--fs-root=/abs"].map(String::from);
+
+        assert_eq!(
+            resolve_flags(&flags, Path::new("/tests")),
+            ["--heap-check", "--fs-root", "/tests/.", "--fs-root=/abs"]
+        );
+    }
+
+    #[test]
+    fn diff_test() {
+        assert_eq!(
+            diff("same\nold\nend\n", "same\nnew\nend\nextra"),
+            "  same\n- old\n+ new\n  end\n+ extra (no newline at end)\n"
+        );
+        assert_eq!(diff("a\0\n", ""), "- a\\0\n");
+    }
+}
@@ -5,14 +5,31 @@
 #![allow(unused)]
 
 use super::error::MvmError;
+use std::collections::{BTreeMap, BTreeSet};
 use thiserror::Error;
 
+/// Units are kept ordered by address, free units are also indexed by size for the best fit lookup.
+/// Released memory is coalesced with free neighbours immediately, so there are never two adjacent
+/// free units.
 #[derive(Debug)]
 pub struct MvmAllocator {
     mem_start: usize,
     mem_end: usize,
 
-    allocated: Vec<AllocatorUnit>,
+    allocated: BTreeMap<usize, AllocatorUnit>,
+    free: BTreeSet<(usize, usize)>,
+
+    used: usize,
+    peak_used: usize,
+}
+
+/// Heap usage in bytes (units sizes are aligned)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocatorStats {
+    pub used: usize,
+    pub peak_used: usize,
+    pub free: usize,
+    pub largest_free: usize,
 }
 
 #[derive(Debug, Error)]
@@ -25,6 +42,9 @@ pub enum AllocatorError {
 
     #[error("restricted unit access is not allowed: `{0}`")]
     RestrictedUnitAccess(usize),
+
+    #[error("invalid pointer is being reallocated: `{0}`")]
+    InvalidReallocPointer(usize),
 }
 
 #[derive(Debug)]
@@ -35,98 +55,151 @@ struct AllocatorUnit {
     pub restricted: bool,
 }
 
+const ALLOCATOR_ALIGN: usize = 4;
+
 impl MvmAllocator {
     pub fn new(mem_start: usize, mem_end: usize) -> Self {
-        let global_unit = AllocatorUnit {
-            address: mem_start,
-            size: mem_end - mem_start,
-            free: true,
-            restricted: true,
+        let mut allocator = Self {
+            mem_start,
+            mem_end,
+            allocated: BTreeMap::new(),
+            free: BTreeSet::new(),
+            used: 0,
+            peak_used: 0,
         };
 
-        let allocated = vec![global_unit];
+        allocator.release(mem_start, mem_end - mem_start, true);
+        allocator
+    }
+
+    pub fn allocate(&mut self, size: usize) -> Result<usize, AllocatorError> {
+        let aligned_size = Self::align(size)?;
 
-        Self {
-            mem_start,
-            mem_end,
-            allocated,
+        // best fit: smallest free unit which is big enough (lowest address between equal ones)
+
+        let &(unit_size, address) = self
+            .free
+            .range((aligned_size, 0)..)
+            .next()
+            .ok_or(AllocatorError::OutOfMemory)?;
+
+        let unit = self.take_free(address);
+
+        // splitting bigger unit
+
+        if unit_size > aligned_size {
+            self.insert_free(
+                address + aligned_size,
+                unit_size - aligned_size,
+                unit.restricted,
+            );
         }
+
+        self.allocated.insert(
+            address,
+            AllocatorUnit {
+                address,
+                size: aligned_size,
+                free: false,
+                restricted: false,
+            },
+        );
+
+        self.set_used(self.used + aligned_size);
+
+        Ok(address)
     }
 
-    pub fn allocate(&mut self, size: usize) -> Result<usize, AllocatorError> {
-        const ALLOCATOR_ALIGN: usize = 4;
+    /// Size of allocated unit (aligned), `None` if pointer isn't allocated
+    pub fn size_of(&self, ptr: usize) -> Option<usize> {
+        self.allocated
+            .get(&ptr)
+            .filter(|unit| !unit.free && !unit.restricted)
+            .map(|unit| unit.size)
+    }
 
-        let aligned_size = (size + (ALLOCATOR_ALIGN - 1)) & !(ALLOCATOR_ALIGN - 1);
-        let mut idx = 0;
+    pub fn stats(&self) -> AllocatorStats {
+        AllocatorStats {
+            used: self.used,
+            peak_used: self.peak_used,
+            free: self.free.iter().map(|(size, _)| size).sum(),
+            largest_free: self.free.last().map(|(size, _)| *size).unwrap_or(0),
+        }
+    }
 
-        while let Some(unit) = self.allocated.get_mut(idx) {
-            // exact length unit
+    /// Addresses and sizes of allocated units
+    pub fn units(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.allocated
+            .values()
+            .filter(|unit| !unit.free && !unit.restricted)
+            .map(|unit| (unit.address, unit.size))
+    }
 
-            if unit.size == aligned_size && unit.free {
-                unit.free = false;
-                return Ok(unit.address);
-            }
+    /// Resizes allocated unit in place if it's possible (shrinking or absorbing next free unit),
+    /// otherwise allocates a new one and frees the old unit.
+    /// Allocator doesn't touch memory, so moved data must be copied by caller.
+    pub fn reallocate(&mut self, ptr: usize, size: usize) -> Result<usize, AllocatorError> {
+        let unit_size = self
+            .size_of(ptr)
+            .ok_or(AllocatorError::InvalidReallocPointer(ptr))?;
+
+        let aligned_size = Self::align(size)?;
 
-            // bigger unit (splitting)
-
-            if unit.size > aligned_size && unit.free {
-                let left_size = aligned_size;
-                let right_size = unit.size - left_size;
-
-                let left_addr = unit.address;
-                let right_addr = left_addr + left_size;
-
-                let right_restricted = unit.restricted;
-
-                self.allocated.splice(
-                    idx..(idx + 1),
-                    [
-                        AllocatorUnit {
-                            address: left_addr,
-                            size: left_size,
-                            free: false,
-                            restricted: false,
-                        },
-                        AllocatorUnit {
-                            address: right_addr,
-                            size: right_size,
-                            free: true,
-                            restricted: right_restricted,
-                        },
-                    ],
-                );
-
-                return Ok(left_addr);
+        // shrinking (released tail becomes a free unit)
+
+        if aligned_size <= unit_size {
+            if aligned_size < unit_size {
+                self.set_size(ptr, aligned_size);
+                self.release(ptr + aligned_size, unit_size - aligned_size, false);
             }
 
-            // otherwise trying to merge units
+            return Ok(ptr);
+        }
 
-            if unit.free {
-                let current_addr = unit.address;
-                let current_size = unit.size;
-
-                let next = self.allocated.get_mut(idx + 1);
-
-                if let Some(next) = next
-                    && next.free
-                {
-                    let merged_size = current_size + next.size;
-                    let merged_unit = AllocatorUnit {
-                        address: current_addr,
-                        size: merged_size,
-                        free: true,
-                        restricted: next.restricted,
-                    };
-
-                    self.allocated.splice(idx..(idx + 2), [merged_unit]);
-                    continue;
-                }
+        // growing in place by taking space from the next free unit
+
+        let required = aligned_size - unit_size;
+        let next_addr = ptr + unit_size;
+
+        if let Some(next) = self.allocated.get(&next_addr)
+            && next.free
+            && next.size >= required
+        {
+            let next = self.take_free(next_addr);
+
+            if next.size > required {
+                self.insert_free(next_addr + required, next.size - required, next.restricted);
             }
 
-            idx += 1;
+            self.set_size(ptr, aligned_size);
+            return Ok(ptr);
         }
 
-        Err(AllocatorError::OutOfMemory)
+        // moving to the new unit
+
+        let new_ptr = self.allocate(size)?;
+        self.deallocate(ptr)?;
+
+        Ok(new_ptr)
+    }
+
+    /// Adds free memory range to the end of managed memory (used by growable heap).
+    /// Gap between previous end and `start` is kept as reserved unit.
+    pub fn extend(&mut self, start: usize, end: usize) {
+        if start > self.mem_end {
+            self.allocated.insert(
+                self.mem_end,
+                AllocatorUnit {
+                    address: self.mem_end,
+                    size: start - self.mem_end,
+                    free: false,
+                    restricted: true,
+                },
+            );
+        }
+
+        self.release(start, end - start, true);
+        self.mem_end = end;
     }
 
     pub fn deallocate(&mut self, ptr: usize) -> Result<(), AllocatorError> {
@@ -134,24 +207,98 @@ impl MvmAllocator {
             return Err(AllocatorError::InvalidFreePointer(ptr));
         }
 
-        let mut iterator = self.allocated.iter_mut();
+        let unit = self
+            .allocated
+            .get(&ptr)
+            .ok_or(AllocatorError::InvalidFreePointer(ptr))?;
 
-        for unit in iterator {
-            if unit.address == ptr {
-                if unit.restricted {
-                    return Err(AllocatorError::RestrictedUnitAccess(ptr));
-                }
+        if unit.restricted {
+            return Err(AllocatorError::RestrictedUnitAccess(ptr));
+        }
 
-                unit.free = true;
-                return Ok(());
-            }
+        // double free (freed unit is either still here or merged into its free neighbour)
+        if unit.free {
+            return Err(AllocatorError::InvalidFreePointer(ptr));
+        }
 
-            if unit.address > ptr {
-                break;
-            }
+        let unit = self.allocated.remove(&ptr).unwrap();
+
+        self.set_used(self.used - unit.size);
+        self.release(unit.address, unit.size, false);
+
+        Ok(())
+    }
+
+    fn align(size: usize) -> Result<usize, AllocatorError> {
+        // zero sized units would share address with their neighbour
+        size.max(1)
+            .checked_next_multiple_of(ALLOCATOR_ALIGN)
+            .ok_or(AllocatorError::OutOfMemory)
+    }
+
+    fn set_size(&mut self, address: usize, size: usize) {
+        if let Some(unit) = self.allocated.get_mut(&address) {
+            let used = self.used - unit.size + size;
+
+            unit.size = size;
+            self.set_used(used);
         }
+    }
 
-        Err(AllocatorError::InvalidFreePointer(ptr))
+    fn set_used(&mut self, used: usize) {
+        self.used = used;
+        self.peak_used = self.peak_used.max(used);
+    }
+
+    fn insert_free(&mut self, address: usize, size: usize, restricted: bool) {
+        self.free.insert((size, address));
+        self.allocated.insert(
+            address,
+            AllocatorUnit {
+                address,
+                size,
+                free: true,
+                restricted,
+            },
+        );
+    }
+
+    fn take_free(&mut self, address: usize) -> AllocatorUnit {
+        let unit = self.allocated.remove(&address).unwrap();
+        self.free.remove(&(unit.size, unit.address));
+
+        unit
+    }
+
+    /// Marks memory range as free, merging it with free neighbours.
+    /// Merged unit keeps `restricted` flag of its first part, so freed pointer is never
+    /// mistaken for untouched memory.
+    fn release(&mut self, mut address: usize, mut size: usize, mut restricted: bool) {
+        let previous = self
+            .allocated
+            .range(..address)
+            .next_back()
+            .map(|(_, unit)| (unit.address, unit.size, unit.free));
+
+        if let Some((prev_addr, prev_size, true)) = previous
+            && prev_addr + prev_size == address
+        {
+            let prev = self.take_free(prev_addr);
+
+            address = prev.address;
+            size += prev.size;
+            restricted = prev.restricted;
+        }
+
+        if let Some(next) = self.allocated.get(&(address + size))
+            && next.free
+        {
+            let next = self.take_free(address + size);
+
+            size += next.size;
+        }
+
+        self.insert_free(address, size, restricted);
     }
 }
 
@@ -164,19 +311,113 @@ mod tests {
     const MEM_LEN: usize = MEM_END - MEM_START;
     const ALLOCA_LEN: usize = 4;
 
+    /// Units must cover whole memory without gaps, free units must be indexed and never adjacent
+    fn verify_units(allocator: &MvmAllocator) {
+        let mut expected_addr = allocator.mem_start;
+        let mut previous_free = false;
+        let mut free_count = 0;
+
+        for (address, unit) in &allocator.allocated {
+            assert_eq!(*address, unit.address);
+            assert_eq!(unit.address, expected_addr);
+            assert!(unit.size > 0);
+            assert!(!(previous_free && unit.free));
+
+            if unit.free {
+                assert!(allocator.free.contains(&(unit.size, unit.address)));
+                free_count += 1;
+            }
+
+            expected_addr += unit.size;
+            previous_free = unit.free;
+        }
+
+        assert_eq!(expected_addr, allocator.mem_end);
+        assert_eq!(allocator.free.len(), free_count);
+    }
+
+    /// Simple deterministic generator for stress tests
+    fn next_random(seed: &mut u64) -> u64 {
+        *seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        *seed >> 33
+    }
+
+    /// Previous allocator policy kept for comparison: first fit over address ordered units,
+    /// free neighbours are merged only when allocation scan passes them.
+    struct FirstFitAllocator {
+        units: Vec<(usize, usize, bool)>, // address, size, free
+    }
+
+    impl FirstFitAllocator {
+        fn new(mem_start: usize, mem_end: usize) -> Self {
+            Self {
+                units: vec![(mem_start, mem_end - mem_start, true)],
+            }
+        }
+
+        fn allocate(&mut self, size: usize) -> Option<usize> {
+            let aligned_size = MvmAllocator::align(size).ok()?;
+            let mut idx = 0;
+
+            while let Some(&(address, unit_size, free)) = self.units.get(idx) {
+                if free && unit_size >= aligned_size {
+                    self.units[idx].1 = aligned_size;
+                    self.units[idx].2 = false;
+
+                    if unit_size > aligned_size {
+                        self.units.insert(
+                            idx + 1,
+                            (address + aligned_size, unit_size - aligned_size, true),
+                        );
+                    }
+
+                    return Some(address);
+                }
+
+                if free && let Some(&(_, next_size, true)) = self.units.get(idx + 1) {
+                    self.units[idx].1 += next_size;
+                    self.units.remove(idx + 1);
+                    continue;
+                }
+
+                idx += 1;
+            }
+
+            None
+        }
+
+        fn deallocate(&mut self, ptr: usize) {
+            let unit = self.units.iter_mut().find(|unit| unit.0 == ptr).unwrap();
+            unit.2 = true;
+        }
+
+        /// Sizes of contiguous free ranges (adjacent free units are counted as one)
+        fn free_ranges(&self) -> Vec<usize> {
+            self.units
+                .chunk_by(|lhs, rhs| lhs.2 == rhs.2)
+                .filter(|chunk| chunk[0].2)
+                .map(|chunk| chunk.iter().map(|unit| unit.1).sum())
+                .collect()
+        }
+    }
+
     #[test]
     fn allocator_base_test() {
         let mut allocator = MvmAllocator::new(MEM_START, MEM_END);
         let ptr = allocator.allocate(ALLOCA_LEN);
 
         assert_eq!(ptr.unwrap(), 0);
-        assert_eq!(allocator.allocated[0].size, ALLOCA_LEN);
-        assert_eq!(allocator.allocated[0].address, 0);
-        assert_eq!(allocator.allocated[0].free, false);
+        assert_eq!(allocator.allocated[&0].size, ALLOCA_LEN);
+        assert_eq!(allocator.allocated[&0].address, 0);
+        assert!(!allocator.allocated[&0].free);
 
-        assert_eq!(allocator.allocated[1].size, MEM_LEN - ALLOCA_LEN);
-        assert_eq!(allocator.allocated[1].address, ALLOCA_LEN);
-        assert_eq!(allocator.allocated[1].free, true);
+        assert_eq!(allocator.allocated[&ALLOCA_LEN].size, MEM_LEN - ALLOCA_LEN);
+        assert_eq!(allocator.allocated[&ALLOCA_LEN].address, ALLOCA_LEN);
+        assert!(allocator.allocated[&ALLOCA_LEN].free);
+
+        verify_units(&allocator);
     }
 
     #[test]
@@ -187,40 +428,46 @@ mod tests {
         let ptr2 = allocator.allocate(ALLOCA_LEN);
 
         assert_eq!(ptr1.unwrap(), 0);
-        assert_eq!(allocator.allocated[0].size, ALLOCA_LEN);
-        assert_eq!(allocator.allocated[0].address, 0);
-        assert_eq!(allocator.allocated[0].free, false);
+        assert_eq!(allocator.allocated[&0].size, ALLOCA_LEN);
+        assert!(!allocator.allocated[&0].free);
 
         assert_eq!(ptr2.unwrap(), ALLOCA_LEN);
-        assert_eq!(allocator.allocated[1].size, ALLOCA_LEN);
-        assert_eq!(allocator.allocated[1].address, ALLOCA_LEN);
-        assert_eq!(allocator.allocated[1].free, false);
+        assert_eq!(allocator.allocated[&ALLOCA_LEN].size, ALLOCA_LEN);
+        assert!(!allocator.allocated[&ALLOCA_LEN].free);
+
+        let rest = ALLOCA_LEN * 2;
 
-        assert_eq!(allocator.allocated[2].size, MEM_LEN - (ALLOCA_LEN * 2));
-        assert_eq!(allocator.allocated[2].address, ALLOCA_LEN * 2);
-        assert_eq!(allocator.allocated[2].free, true);
+        assert_eq!(allocator.allocated[&rest].size, MEM_LEN - rest);
+        assert!(allocator.allocated[&rest].free);
+
+        verify_units(&allocator);
     }
 
     #[test]
     fn allocator_merge_test() {
         let mut allocator = MvmAllocator::new(MEM_START, MEM_END);
 
-        let ptr = allocator.allocate(ALLOCA_LEN);
-        let _ = allocator.allocate(ALLOCA_LEN);
+        let ptr = allocator.allocate(ALLOCA_LEN).unwrap();
+        let ptr2 = allocator.allocate(ALLOCA_LEN).unwrap();
+        let _ = allocator.allocate(ALLOCA_LEN).unwrap();
+
+        // At this point we have 3 allocated units with the same size.
+        // Freeing first two of them should merge them into one free unit right away.
+
+        allocator.deallocate(ptr).unwrap();
+        allocator.deallocate(ptr2).unwrap();
 
-        // At this point we have 2 allocated units with the same size.
-        // Now we're going to manually free them and try to allocate new one with double size.
-        // It should merge previous units and return their ptr.
+        assert_eq!(allocator.allocated[&0].size, ALLOCA_LEN * 2);
+        assert!(allocator.allocated[&0].free);
+        assert!(!allocator.allocated.contains_key(&ptr2));
 
-        allocator.allocated[0].free = true;
-        allocator.allocated[1].free = true;
+        verify_units(&allocator);
 
         let new_ptr = allocator.allocate(ALLOCA_LEN * 2);
 
-        assert_eq!(new_ptr.unwrap(), ptr.unwrap());
-        assert_eq!(allocator.allocated[0].address, 0);
-        assert_eq!(allocator.allocated[0].size, ALLOCA_LEN * 2);
-        assert_eq!(allocator.allocated[0].free, false);
+        assert_eq!(new_ptr.unwrap(), ptr);
+        assert_eq!(allocator.allocated[&0].size, ALLOCA_LEN * 2);
+        assert!(!allocator.allocated[&0].free);
     }
 
     #[test]
@@ -230,16 +477,16 @@ mod tests {
         let ptr = allocator.allocate(ALLOCA_LEN).unwrap();
 
         assert_eq!(ptr, 0);
-        assert_eq!(allocator.allocated[0].address, 0);
-        assert_eq!(allocator.allocated[0].size, ALLOCA_LEN);
-        assert_eq!(allocator.allocated[0].free, false);
+        assert_eq!(allocator.allocated[&0].size, ALLOCA_LEN);
+        assert!(!allocator.allocated[&0].free);
 
         let result = allocator.deallocate(ptr);
 
+        // unit is merged back with the rest of memory
         assert!(result.is_ok());
-        assert_eq!(allocator.allocated[0].address, 0);
-        assert_eq!(allocator.allocated[0].size, ALLOCA_LEN);
-        assert_eq!(allocator.allocated[0].free, true);
+        assert_eq!(allocator.allocated.len(), 1);
+        assert_eq!(allocator.allocated[&0].size, MEM_LEN);
+        assert!(allocator.allocated[&0].free);
     }
 
     #[test]
@@ -249,23 +496,16 @@ mod tests {
         let ptr = allocator.allocate(ALLOCA_LEN).unwrap();
 
         assert_eq!(ptr, 0);
-        assert_eq!(allocator.allocated[0].address, 0);
-        assert_eq!(allocator.allocated[0].size, ALLOCA_LEN);
-        assert_eq!(allocator.allocated[0].free, false);
-
-        let result = allocator.deallocate(ptr);
-
-        assert!(result.is_ok());
-        assert_eq!(allocator.allocated[0].address, 0);
-        assert_eq!(allocator.allocated[0].size, ALLOCA_LEN);
-        assert_eq!(allocator.allocated[0].free, true);
+        assert!(allocator.deallocate(ptr).is_ok());
+        assert!(allocator.allocated[&0].free);
 
         let new_ptr = allocator.allocate(ALLOCA_LEN).unwrap();
 
         assert_eq!(new_ptr, 0);
-        assert_eq!(allocator.allocated[0].address, 0);
-        assert_eq!(allocator.allocated[0].size, ALLOCA_LEN);
-        assert_eq!(allocator.allocated[0].free, false);
+        assert_eq!(allocator.allocated[&0].size, ALLOCA_LEN);
+        assert!(!allocator.allocated[&0].free);
+
+        verify_units(&allocator);
     }
 
     #[test]
@@ -273,10 +513,334 @@ mod tests {
         let mut allocator = MvmAllocator::new(MEM_START, MEM_END);
 
         let ptr = allocator.allocate(MEM_LEN + 1);
+        assert!(matches!(ptr, Err(AllocatorError::OutOfMemory)));
 
+        let ptr = allocator.allocate(usize::MAX);
         assert!(matches!(ptr, Err(AllocatorError::OutOfMemory)));
     }
 
+    #[test]
+    fn allocator_zero_size_test() {
+        let mut allocator = MvmAllocator::new(MEM_START, MEM_END);
+
+        let ptr1 = allocator.allocate(0).unwrap();
+        let ptr2 = allocator.allocate(0).unwrap();
+
+        assert_ne!(ptr1, ptr2);
+        assert_eq!(allocator.size_of(ptr1), Some(ALLOCATOR_ALIGN));
+
+        verify_units(&allocator);
+    }
+
+    #[test]
+    fn allocator_best_fit_test() {
+        let mut allocator = MvmAllocator::new(MEM_START, MEM_END);
+
+        let big = allocator.allocate(ALLOCA_LEN * 3).unwrap();
+        let _ = allocator.allocate(ALLOCA_LEN).unwrap();
+        let small = allocator.allocate(ALLOCA_LEN).unwrap();
+        let _ = allocator.allocate(ALLOCA_LEN).unwrap();
+
+        allocator.deallocate(big).unwrap();
+        allocator.deallocate(small).unwrap();
+
+        // first fit would split the leading 12 byte hole here
+        assert_eq!(allocator.allocate(ALLOCA_LEN).unwrap(), small);
+        assert_eq!(allocator.allocate(ALLOCA_LEN * 3).unwrap(), big);
+
+        verify_units(&allocator);
+    }
+
+    #[test]
+    fn allocator_extend_test() {
+        let mut allocator = MvmAllocator::new(MEM_START, MEM_END);
+
+        assert!(allocator.allocate(MEM_LEN).is_ok());
+        assert!(matches!(
+            allocator.allocate(ALLOCA_LEN),
+            Err(AllocatorError::OutOfMemory)
+        ));
+
+        // reserved gap between old end and new range (e.g. stack)
+        allocator.extend(MEM_END + 16, MEM_END + 32);
+
+        assert_eq!(allocator.allocate(ALLOCA_LEN).unwrap(), MEM_END + 16);
+        assert!(matches!(
+            allocator.deallocate(MEM_END),
+            Err(AllocatorError::RestrictedUnitAccess(MEM_END))
+        ));
+
+        // contiguous extension is merged with previous free unit
+        allocator.extend(MEM_END + 32, MEM_END + 48);
+
+        verify_units(&allocator);
+        assert_eq!(allocator.allocate(24).unwrap(), MEM_END + 16 + ALLOCA_LEN);
+    }
+
+    #[test]
+    fn allocator_realloc_shrink_test() {
+        let mut allocator = MvmAllocator::new(MEM_START, MEM_END);
+
+        let ptr = allocator.allocate(ALLOCA_LEN * 4).unwrap();
+        let next = allocator.allocate(ALLOCA_LEN).unwrap();
+
+        assert_eq!(allocator.reallocate(ptr, ALLOCA_LEN).unwrap(), ptr);
+        assert_eq!(allocator.size_of(ptr), Some(ALLOCA_LEN));
+
+        verify_units(&allocator);
+
+        // released tail is reused
+        assert_eq!(allocator.allocate(ALLOCA_LEN).unwrap(), ALLOCA_LEN);
+        assert_eq!(allocator.size_of(next), Some(ALLOCA_LEN));
+    }
+
+    #[test]
+    fn allocator_realloc_in_place_test() {
+        let mut allocator = MvmAllocator::new(MEM_START, MEM_END);
+
+        let ptr = allocator.allocate(ALLOCA_LEN).unwrap();
+
+        // next unit is the free rest of memory
+        assert_eq!(allocator.reallocate(ptr, ALLOCA_LEN * 3).unwrap(), ptr);
+        assert_eq!(allocator.size_of(ptr), Some(ALLOCA_LEN * 3));
+
+        let rest = ALLOCA_LEN * 3;
+
+        assert_eq!(allocator.allocated[&rest].size, MEM_LEN - rest);
+        verify_units(&allocator);
+
+        // whole free unit is taken
+        assert_eq!(allocator.reallocate(ptr, MEM_LEN).unwrap(), ptr);
+        assert_eq!(allocator.allocated.len(), 1);
+        assert!(matches!(
+            allocator.allocate(ALLOCA_LEN),
+            Err(AllocatorError::OutOfMemory)
+        ));
+    }
+
+    #[test]
+    fn allocator_realloc_move_test() {
+        let mut allocator = MvmAllocator::new(MEM_START, MEM_END);
+
+        let ptr = allocator.allocate(ALLOCA_LEN).unwrap();
+        let _ = allocator.allocate(ALLOCA_LEN).unwrap();
+
+        let new_ptr = allocator.reallocate(ptr, ALLOCA_LEN * 2).unwrap();
+
+        assert_eq!(new_ptr, ALLOCA_LEN * 2);
+        assert_eq!(allocator.size_of(new_ptr), Some(ALLOCA_LEN * 2));
+        assert_eq!(allocator.size_of(ptr), None);
+        assert!(allocator.allocated[&0].free);
+
+        // failed reallocation keeps the unit
+        assert!(matches!(
+            allocator.reallocate(new_ptr, MEM_LEN),
+            Err(AllocatorError::OutOfMemory)
+        ));
+        assert_eq!(allocator.size_of(new_ptr), Some(ALLOCA_LEN * 2));
+
+        assert!(matches!(
+            allocator.reallocate(ptr, ALLOCA_LEN),
+            Err(AllocatorError::InvalidReallocPointer(0))
+        ));
+
+        verify_units(&allocator);
+    }
+
+    #[test]
+    fn allocator_stress_test() {
+        const HEAP_LEN: usize = 64 * 1024;
+
+        let mut allocator = MvmAllocator::new(MEM_START, HEAP_LEN);
+        let mut live = Vec::new();
+        let mut seed = 42;
+
+        for step in 0..20_000_u32 {
+            let roll = next_random(&mut seed);
+
+            if !roll.is_multiple_of(3) || live.is_empty() {
+                let size = (next_random(&mut seed) % 256) as usize + 1;
+
+                if let Ok(ptr) = allocator.allocate(size) {
+                    live.push(ptr);
+                }
+            } else {
+                let idx = next_random(&mut seed) as usize % live.len();
+                allocator.deallocate(live.swap_remove(idx)).unwrap();
+            }
+
+            if step.is_multiple_of(1000) {
+                verify_units(&allocator);
+            }
+        }
+
+        verify_units(&allocator);
+
+        // everything is coalesced back into one unit
+        for ptr in live {
+            allocator.deallocate(ptr).unwrap();
+        }
+
+        assert_eq!(allocator.allocated.len(), 1);
+        assert_eq!(allocator.allocate(HEAP_LEN).unwrap(), MEM_START);
+    }
+
+    #[test]
+    fn allocator_fragmentation_test() {
+        const HEAP_LEN: usize = 4096;
+        const BLOCK_LEN: usize = 16;
+
+        let mut allocator = MvmAllocator::new(MEM_START, HEAP_LEN);
+
+        let blocks = (0..HEAP_LEN / BLOCK_LEN)
+            .map(|_| allocator.allocate(BLOCK_LEN).unwrap())
+            .collect::<Vec<_>>();
+
+        // freeing every second block leaves holes which can't fit bigger unit
+        for ptr in blocks.iter().step_by(2) {
+            allocator.deallocate(*ptr).unwrap();
+        }
+
+        assert_eq!(allocator.free.len(), blocks.len() / 2);
+        assert!(allocator.allocate(BLOCK_LEN * 2).is_err());
+
+        // freeing the rest merges holes on free, without waiting for the next allocation
+        for ptr in blocks.iter().skip(1).step_by(2) {
+            allocator.deallocate(*ptr).unwrap();
+        }
+
+        assert_eq!(allocator.free.len(), 1);
+        assert_eq!(allocator.allocated[&MEM_START].size, HEAP_LEN);
+
+        // churn with growing and shrinking units keeps heap usable
+        let mut ptr = allocator.allocate(BLOCK_LEN).unwrap();
+
+        for size in (1..HEAP_LEN / 2)
+            .step_by(97)
+            .chain((1..HEAP_LEN / 2).rev().step_by(89))
+        {
+            ptr = allocator.reallocate(ptr, size).unwrap();
+            let _ = allocator.allocate(BLOCK_LEN).unwrap();
+        }
+
+        verify_units(&allocator);
+    }
+
+    #[test]
+    fn allocator_first_fit_comparison_test() {
+        const HEAP_LEN: usize = 64 * 1024;
+
+        let mut allocator = MvmAllocator::new(MEM_START, HEAP_LEN);
+        let mut first_fit = FirstFitAllocator::new(MEM_START, HEAP_LEN);
+
+        // the same churn is replayed on both allocators (pointers are kept in pairs)
+        let mut live: Vec<(usize, usize)> = Vec::new();
+        let mut failed = (0, 0);
+        let mut seed = 7;
+
+        for _ in 0..20_000 {
+            let roll = next_random(&mut seed);
+
+            if !roll.is_multiple_of(3) || live.is_empty() {
+                let size = (next_random(&mut seed) % 512) as usize + 1;
+
+                match (allocator.allocate(size), first_fit.allocate(size)) {
+                    (Ok(ptr), Some(first_fit_ptr)) => live.push((ptr, first_fit_ptr)),
+                    (Ok(ptr), None) => {
+                        failed.1 += 1;
+                        allocator.deallocate(ptr).unwrap();
+                    }
+                    (Err(_), Some(first_fit_ptr)) => {
+                        failed.0 += 1;
+                        first_fit.deallocate(first_fit_ptr);
+                    }
+                    (Err(_), None) => {
+                        failed.0 += 1;
+                        failed.1 += 1;
+                    }
+                }
+            } else {
+                let idx = next_random(&mut seed) as usize % live.len();
+                let (ptr, first_fit_ptr) = live.swap_remove(idx);
+
+                allocator.deallocate(ptr).unwrap();
+                first_fit.deallocate(first_fit_ptr);
+            }
+        }
+
+        verify_units(&allocator);
+
+        let stats = allocator.stats();
+        let first_fit_ranges = first_fit.free_ranges();
+
+        let fragments = (allocator.free.len(), first_fit_ranges.len());
+        let largest_free = (
+            stats.largest_free,
+            first_fit_ranges.iter().max().copied().unwrap_or(0),
+        );
+
+        // best fit with coalescing leaves fewer holes, bigger free block and fails less often
+        assert!(fragments.0 < fragments.1, "fragments: {fragments:?}");
+        assert!(
+            largest_free.0 >= largest_free.1,
+            "largest free: {largest_free:?}"
+        );
+        assert!(failed.0 <= failed.1, "failed allocations: {failed:?}");
+    }
+
+    #[test]
+    fn allocator_double_free_test() {
+        let mut allocator = MvmAllocator::new(MEM_START, MEM_END);
+
+        let ptr1 = allocator.allocate(ALLOCA_LEN).unwrap();
+        let ptr2 = allocator.allocate(ALLOCA_LEN).unwrap();
+        let _ = allocator.allocate(ALLOCA_LEN).unwrap();
+
+        // freed unit stays separate
+        allocator.deallocate(ptr2).unwrap();
+
+        assert!(matches!(
+            allocator.deallocate(ptr2),
+            Err(AllocatorError::InvalidFreePointer(ALLOCA_LEN))
+        ));
+
+        // second unit is merged into the first one, so its pointer is no longer unit head
+        allocator.deallocate(ptr1).unwrap();
+
+        assert!(matches!(
+            allocator.deallocate(ptr1),
+            Err(AllocatorError::InvalidFreePointer(0))
+        ));
+        assert!(matches!(
+            allocator.deallocate(ptr2),
+            Err(AllocatorError::InvalidFreePointer(ALLOCA_LEN))
+        ));
+
+        verify_units(&allocator);
+    }
+
+    #[test]
+    fn allocator_stats_test() {
+        let mut allocator = MvmAllocator::new(MEM_START, MEM_END);
+
+        let ptr1 = allocator.allocate(ALLOCA_LEN * 2).unwrap();
+        let ptr2 = allocator.allocate(ALLOCA_LEN).unwrap();
+        let _ = allocator.allocate(ALLOCA_LEN).unwrap();
+
+        allocator.deallocate(ptr1).unwrap();
+        // unit is moved, so both units are counted in peak
+        allocator.reallocate(ptr2, ALLOCA_LEN * 3).unwrap();
+
+        let stats = allocator.stats();
+
+        assert_eq!(stats.used, ALLOCA_LEN * 4);
+        assert_eq!(stats.peak_used, ALLOCA_LEN * 5);
+        assert_eq!(stats.free, MEM_LEN - ALLOCA_LEN * 4);
+        assert_eq!(stats.largest_free, ALLOCA_LEN * 3);
+
+        verify_units(&allocator);
+    }
+
     #[test]
     fn allocator_invalid_free_ptr_test() {
         let mut allocator = MvmAllocator::new(MEM_START, MEM_END);
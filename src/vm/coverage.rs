@@ -0,0 +1,236 @@
+//! **Mvm Coverage** records executed instructions and outcomes of conditional jumps
+//! (`mvm run --coverage`). Report is built in `lcov` format from debug info source lines, so it
+//! requires program compiled in debug mode.
+
+use std::collections::{BTreeMap, HashMap};
+
+use super::{Opcode, VM};
+
+#[derive(Debug, Default)]
+pub struct Coverage {
+    pub enabled: bool,
+
+    hits: HashMap<u64, u64>,
+    /// Conditional jumps outcomes by address: (taken, not taken)
+    branches: HashMap<u64, (u64, u64)>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CoverageSummary {
+    pub lines_found: usize,
+    pub lines_hit: usize,
+    pub branches_found: usize,
+    pub branches_hit: usize,
+}
+
+/// Line hits and outcomes of its conditional jumps (`None` if jump wasn't executed)
+#[derive(Debug, Default)]
+struct LineCoverage {
+    hits: u64,
+    branches: Vec<Option<(u64, u64)>>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_branch(opcode: Opcode) -> bool {
+        matches!(
+            opcode,
+            Opcode::Jz
+                | Opcode::Jnz
+                | Opcode::Je
+                | Opcode::Jne
+                | Opcode::JzReg
+                | Opcode::JnzReg
+                | Opcode::JeReg
+                | Opcode::JneReg
+        )
+    }
+
+    pub fn record(&mut self, address: u64) {
+        *self.hits.entry(address).or_default() += 1;
+    }
+
+    pub fn record_branch(&mut self, address: u64, taken: bool) {
+        let outcomes = self.branches.entry(address).or_default();
+
+        if taken {
+            outcomes.0 += 1;
+        } else {
+            outcomes.1 += 1;
+        }
+    }
+
+    pub fn hits(&self, address: u64) -> u64 {
+        self.hits.get(&address).copied().unwrap_or(0)
+    }
+}
+
+impl VM {
+    /// Builds `lcov` tracefile, returns `None` if there's no source lines in debug info.
+    /// Conditional jumps which were never executed are found by decoding instructions.
+    pub fn coverage_report(&self) -> Option<(String, CoverageSummary)> {
+        let source = self.debug_info.source.as_ref()?;
+
+        if self.debug_info.lines.is_empty() {
+            return None;
+        }
+
+        let mut lines: BTreeMap<usize, LineCoverage> = BTreeMap::new();
+
+        for (address, line) in &self.debug_info.lines {
+            let hits = self.coverage.hits(*address);
+            let entry = lines.entry(*line).or_default();
+
+            entry.hits = entry.hits.max(hits);
+
+            if self
+                .decode(*address)
+                .is_ok_and(|instruction| Coverage::is_branch(instruction.opcode))
+            {
+                let outcomes = self.coverage.branches.get(address).copied();
+                entry
+                    .branches
+                    .push(outcomes.or((hits > 0).then_some((0, 0))));
+            }
+        }
+
+        let mut summary = CoverageSummary::default();
+        let mut report = format!("TN:\nSF:{source}\n");
+
+        for (line, coverage) in &lines {
+            for (block, outcomes) in coverage.branches.iter().enumerate() {
+                for branch in 0..2 {
+                    let taken = match outcomes {
+                        Some((taken, _)) if branch == 0 => taken.to_string(),
+                        Some((_, not_taken)) => not_taken.to_string(),
+                        None => String::from("-"),
+                    };
+
+                    summary.branches_found += 1;
+                    summary.branches_hit += usize::from(!matches!(taken.as_str(), "-" | "0"));
+
+                    report.push_str(&format!("BRDA:{line},{block},{branch},{taken}\n"));
+                }
+            }
+        }
+
+        for (line, coverage) in &lines {
+            summary.lines_found += 1;
+            summary.lines_hit += usize::from(coverage.hits > 0);
+
+            report.push_str(&format!("DA:{line},{}\n", coverage.hits));
+        }
+
+        report.push_str(&format!(
+            "BRF:{}\nBRH:{}\nLF:{}\nLH:{}\nend_of_record\n",
+            summary.branches_found, summary.branches_hit, summary.lines_found, summary.lines_hit
+        ));
+
+        Some((report, summary))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{DebugInfo, MvmError, R0};
+    use super::*;
+
+    #[test]
+    fn coverage_branches_test() {
+        let mut coverage = Coverage::new();
+
+        coverage.record(10);
+        coverage.record(10);
+        coverage.record_branch(10, true);
+        coverage.record_branch(10, false);
+        coverage.record_branch(10, false);
+
+        assert_eq!(coverage.hits(10), 2);
+        assert_eq!(coverage.hits(11), 0);
+        assert_eq!(coverage.branches.get(&10), Some(&(1, 2)));
+
+        assert!(Coverage::is_branch(Opcode::JneReg));
+        assert!(!Coverage::is_branch(Opcode::Jmp));
+    }
+
+    #[test]
+    fn coverage_report_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(256, 64)?;
+
+        #[rustfmt::skip]
+        let program = [
+            // 0: mov %r0, $0 (constant at 31)
+            Opcode::Mov8 as u8, R0 as u8, 0, 0, 0, 0, 0, 0, 0, 31,
+            // 10: cmp %r0, $1 (constant at 32)
+            Opcode::Cmp8 as u8, R0 as u8, 0, 0, 0, 0, 0, 0, 0, 32,
+            // 20: jnz 30
+            Opcode::Jnz as u8, 0, 0, 0, 0, 0, 0, 0, 30,
+            // 29: halt (skipped)
+            Opcode::Halt as u8,
+            // 30: halt
+            Opcode::Halt as u8,
+            // 31: constants
+            0,
+            1,
+        ];
+
+        vm.insert_program(&program)?;
+        vm.coverage.enabled = true;
+        vm.debug_info = DebugInfo::parse(
+            "source test.asm\nline 0 1\nline 10 2\nline 20 3\nline 29 4\nline 30 5\nline 31 6\n",
+        );
+
+        vm.run()?;
+
+        let (report, summary) = vm.coverage_report().unwrap();
+
+        assert_eq!(
+            report,
+            "TN:\nSF:test.asm\nBRDA:3,0,0,1\nBRDA:3,0,1,0\n\
+            DA:1,1\nDA:2,1\nDA:3,1\nDA:4,0\nDA:5,1\nDA:6,0\n\
+            BRF:2\nBRH:1\nLF:6\nLH:4\nend_of_record\n"
+        );
+        assert_eq!(
+            summary,
+            CoverageSummary {
+                lines_found: 6,
+                lines_hit: 4,
+                branches_found: 2,
+                branches_hit: 1,
+            }
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn coverage_jump_to_next_instruction_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(256, 64)?;
+
+        #[rustfmt::skip]
+        let program = [
+            // 0: mov %r0, $0 (constant at 30)
+            Opcode::Mov8 as u8, R0 as u8, 0, 0, 0, 0, 0, 0, 0, 30,
+            // 10: cmp %r0, $1 (constant at 31)
+            Opcode::Cmp8 as u8, R0 as u8, 0, 0, 0, 0, 0, 0, 0, 31,
+            // 20: jnz 29 (taken, but target is the next instruction)
+            Opcode::Jnz as u8, 0, 0, 0, 0, 0, 0, 0, 29,
+            // 29: halt
+            Opcode::Halt as u8,
+            // 30: constants
+            0,
+            1,
+        ];
+
+        vm.insert_program(&program)?;
+        vm.coverage.enabled = true;
+        vm.run()?;
+
+        assert_eq!(vm.coverage.branches.get(&20), Some(&(1, 0)));
+
+        Ok(())
+    }
+}
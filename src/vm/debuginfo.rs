@@ -0,0 +1,118 @@
+//! **Debug Info** is an optional symbol table produced by assembler in debug mode (`.dbg` file
+//! next to the binary). It is used only for reports, so unknown or malformed lines are skipped.
+//! File keeps checksum of the binary, so debug info left from previous build can be detected.
+
+use std::collections::BTreeMap;
+
+#[derive(Debug, Default)]
+pub struct DebugInfo {
+    /// Path to assembly source file
+    pub source: Option<String>,
+    /// Checksum of the binary (with metadata) which debug info was produced for
+    pub checksum: Option<u64>,
+    /// Text section labels by address
+    pub labels: BTreeMap<u64, String>,
+    /// Source lines (starting from 1) of text section instructions by address
+    pub lines: BTreeMap<u64, usize>,
+}
+
+impl DebugInfo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(source: &str) -> Self {
+        let mut info = Self::new();
+
+        for line in source.lines() {
+            if let Some(path) = line.strip_prefix("source ") {
+                info.source = Some(path.to_owned());
+                continue;
+            }
+
+            if let Some(checksum) = line.strip_prefix("binary ") {
+                info.checksum = u64::from_str_radix(checksum, 16).ok();
+                continue;
+            }
+
+            let mut parts = line.split_whitespace();
+
+            let (Some(kind), Some(Ok(address)), Some(value)) = (
+                parts.next(),
+                parts.next().map(str::parse::<u64>),
+                parts.next(),
+            ) else {
+                continue;
+            };
+
+            match kind {
+                "label" => {
+                    info.labels.insert(address, value.to_owned());
+                }
+
+                "line" => {
+                    if let Ok(line) = value.parse() {
+                        info.lines.insert(address, line);
+                    }
+                }
+
+                _ => {}
+            }
+        }
+
+        info
+    }
+
+    /// FNV-1a hash of the binary
+    pub fn checksum(binary: &[u8]) -> u64 {
+        binary.iter().fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+        })
+    }
+
+    /// Checks that debug info was produced for this binary
+    pub fn matches(&self, binary: &[u8]) -> bool {
+        self.checksum == Some(Self::checksum(binary))
+    }
+
+    /// Human readable location: `label`, `label+offset` or address itself
+    pub fn locate(&self, address: u64) -> String {
+        match self.labels.range(..=address).next_back() {
+            Some((label_addr, name)) if *label_addr == address => name.clone(),
+            Some((label_addr, name)) => format!("{name}+{}", address - label_addr),
+            None => format!("{address:#x}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_info_parse_test() {
+        let info = DebugInfo::parse(
+            "source /tmp/my program.asm\nbinary 00ff\nlabel 10 _start\nlabel 25 func\nline 10 4\nline 12 x\nlabel x broken\n",
+        );
+
+        assert_eq!(info.source.as_deref(), Some("/tmp/my program.asm"));
+        assert_eq!(info.checksum, Some(0xff));
+        assert_eq!(info.labels.len(), 2);
+        assert_eq!(info.labels.get(&25), Some(&String::from("func")));
+        assert_eq!(info.lines, BTreeMap::from([(10, 4)]));
+
+        assert_eq!(info.locate(10), "_start");
+        assert_eq!(info.locate(30), "func+5");
+        assert_eq!(info.locate(4), "0x4");
+    }
+
+    #[test]
+    fn debug_info_checksum_test() {
+        let binary = [0x01, 0xff, 0x02, 0x00];
+        let info = DebugInfo::parse(&format!("binary {:x}\n", DebugInfo::checksum(&binary)));
+
+        assert!(info.matches(&binary));
+        assert!(!info.matches(&[0x01, 0xff, 0x02, 0x01]));
+        assert!(!DebugInfo::new().matches(&binary));
+    }
+}
@@ -0,0 +1,267 @@
+//! **Mvm Decoder** translates bytecode into instructions with already parsed operands.
+//! Decoded instructions of the text section are cached, so each one is decoded only once.
+//! Any write to the text section (self-modifying code) invalidates the cache.
+
+use super::{MvmError, Opcode, R_INSTRUCTION_POINTER, VM};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    pub opcode: Opcode,
+    pub operands: [u64; 4],
+
+    /// Encoded length in bytes (including opcode)
+    pub length: u64,
+}
+
+#[derive(Debug)]
+pub struct DecodeCache {
+    pub enabled: bool,
+
+    start: u64,
+    entries: Vec<Option<Instruction>>,
+}
+
+impl Default for DecodeCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DecodeCache {
+    pub fn new() -> Self {
+        Self {
+            enabled: true,
+            start: 0,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Clears cache and sets range of cached addresses (`end` is exclusive)
+    pub fn reset(&mut self, start: u64, end: u64) {
+        self.start = start;
+        self.entries = vec![None; end.saturating_sub(start) as usize];
+    }
+
+    pub fn invalidate(&mut self) {
+        self.entries.fill(None);
+    }
+
+    pub fn get(&self, address: u64) -> Option<Instruction> {
+        let index = address.checked_sub(self.start)?;
+
+        self.entries.get(index as usize).copied().flatten()
+    }
+
+    pub fn insert(&mut self, address: u64, instruction: Instruction) {
+        if !self.enabled {
+            return;
+        }
+
+        let Some(index) = address.checked_sub(self.start) else {
+            return;
+        };
+
+        // instruction must be fully inside of cached range
+        if index + instruction.length > self.entries.len() as u64 {
+            return;
+        }
+
+        self.entries[index as usize] = Some(instruction);
+    }
+}
+
+impl VM {
+    /// Decodes instruction at instruction pointer and moves pointer after it.
+    /// On decoding error pointer is moved only after opcode byte.
+    pub fn fetch_instruction(&mut self) -> Result<Instruction, MvmError> {
+        let instruction_ptr = self.get_register(R_INSTRUCTION_POINTER)?;
+
+        let instruction = match self.decode_cache.get(instruction_ptr) {
+            Some(instruction) => instruction,
+            None => {
+                self.set_register(R_INSTRUCTION_POINTER, instruction_ptr.wrapping_add(1))?;
+
+                let instruction = self.decode(instruction_ptr)?;
+                self.decode_cache.insert(instruction_ptr, instruction);
+
+                instruction
+            }
+        };
+
+        self.set_register(
+            R_INSTRUCTION_POINTER,
+            instruction_ptr.wrapping_add(instruction.length),
+        )?;
+
+        Ok(instruction)
+    }
+
+    pub fn decode(&self, address: u64) -> Result<Instruction, MvmError> {
+        let byte = self.memory.get_u8(address)?;
+
+        // entry point jump is executed as regular `jmp`
+        if self.text_section && byte == 0xFF {
+            return Ok(Instruction {
+                opcode: Opcode::Jmp,
+                operands: [self.memory.get_u64(address.wrapping_add(1))?, 0, 0, 0],
+                length: 9,
+            });
+        }
+
+        let opcode = Opcode::try_from(byte)?;
+
+        let mut operands = [0; 4];
+        let mut length = 1;
+
+        for (operand, width) in operands.iter_mut().zip(opcode.operands()) {
+            let operand_addr = address.wrapping_add(length);
+
+            *operand = match width {
+                1 => self.memory.get_u8(operand_addr)? as u64,
+                _ => self.memory.get_u64(operand_addr)?,
+            };
+
+            length += *width as u64;
+        }
+
+        Ok(Instruction {
+            opcode,
+            operands,
+            length,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::{R_ACCUMULATOR, R1, R2, R3, R4, R5, R6};
+
+    #[test]
+    fn decode_instruction_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(64, 16)?;
+
+        let program = [
+            Opcode::Mov8 as u8,
+            R1 as u8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            12,
+            Opcode::MovR2R as u8,
+            R1 as u8,
+            R1 as u8,
+            Opcode::Halt as u8,
+        ];
+
+        vm.insert_program(&program)?;
+
+        assert_eq!(
+            vm.fetch_instruction()?,
+            Instruction {
+                opcode: Opcode::Mov8,
+                operands: [R1, 12, 0, 0],
+                length: 10
+            }
+        );
+        assert_eq!(vm.get_register(R_INSTRUCTION_POINTER)?, 10);
+        assert_eq!(vm.decode_cache.get(0).map(|i| i.length), Some(10));
+
+        assert_eq!(vm.fetch_instruction()?.operands, [R1, R1, 0, 0]);
+        assert_eq!(vm.fetch_instruction()?.opcode, Opcode::Halt);
+
+        Ok(())
+    }
+
+    #[test]
+    fn decode_invalid_opcode_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(64, 16)?;
+
+        vm.insert_program(&[0xee, Opcode::Halt as u8])?;
+
+        assert!(matches!(
+            vm.fetch_instruction(),
+            Err(MvmError::InvalidOpcode(0xee))
+        ));
+        assert_eq!(vm.get_register(R_INSTRUCTION_POINTER)?, 1);
+        assert_eq!(vm.decode_cache.get(0), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn decode_self_modifying_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(64, 16)?;
+
+        vm.self_modifying = true;
+
+        let program = [
+            // mov %r3, %r1 (second operand is rewritten to %r5)
+            Opcode::MovR2R as u8,
+            R3 as u8,
+            R1 as u8,
+            // store8 %r2, %r4
+            Opcode::Store8 as u8,
+            R2 as u8,
+            R4 as u8,
+            // sub %accumulator, %r6
+            Opcode::SubR2R as u8,
+            R_ACCUMULATOR as u8,
+            R6 as u8,
+            // jnz 0
+            Opcode::Jnz as u8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            Opcode::Halt as u8,
+        ];
+
+        vm.insert_program(&program)?;
+
+        vm.set_register(R1, 111)?;
+        vm.set_register(R2, 2)?;
+        vm.set_register(R4, R5)?;
+        vm.set_register(R5, 222)?;
+        vm.set_register(R6, 1)?;
+        vm.set_register(R_ACCUMULATOR, 2)?;
+
+        vm.run()?;
+
+        assert_eq!(vm.get_register(R3)?, 222);
+
+        Ok(())
+    }
+
+    #[test]
+    fn decode_cache_range_test() {
+        let mut cache = DecodeCache::new();
+        let instruction = Instruction {
+            opcode: Opcode::Jmp,
+            operands: [0; 4],
+            length: 9,
+        };
+
+        cache.reset(4, 16);
+
+        cache.insert(0, instruction);
+        cache.insert(8, instruction);
+        cache.insert(4, instruction);
+
+        assert_eq!(cache.get(0), None);
+        assert_eq!(cache.get(8), None);
+        assert_eq!(cache.get(4), Some(instruction));
+
+        cache.invalidate();
+
+        assert_eq!(cache.get(4), None);
+    }
+}
@@ -1,13 +1,45 @@
 use thiserror::Error;
 
+use super::{Opcode, REGISTERS_COUNT};
+
+/// Machine state at the moment of runtime error
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// Address of faulting instruction
+    pub address: u64,
+    /// Decoded opcode, `None` if instruction can't be decoded
+    pub opcode: Option<Opcode>,
+    /// Encoded operands of instruction (without opcode byte)
+    pub operands: Vec<u8>,
+    pub registers: [u64; REGISTERS_COUNT],
+}
+
 #[derive(Debug, Error)]
 pub enum MvmError {
+    /// Error raised by instruction with machine state at that moment (produced by `VM::step`)
+    #[error("{error} (instruction: {})", context.address)]
+    Runtime {
+        #[source]
+        error: Box<MvmError>,
+        context: Box<ErrorContext>,
+    },
+
     #[error("invalid opcode provided (value: {0})")]
     InvalidOpcode(u8),
 
     #[error("segmentation fault (address: {0})")]
     SegmentationFault(u64),
 
+    #[error("protection fault: {access} access to `{region}` region (address: {address})")]
+    ProtectionFault {
+        address: u64,
+        region: &'static str,
+        access: &'static str,
+    },
+
+    #[error("invalid register index: {0}")]
+    InvalidRegister(u64),
+
     #[error("memory is out of bounds")]
     OutOfBounds,
 
@@ -29,9 +61,15 @@ pub enum MvmError {
     #[error("unknown interrupt is being called")]
     UnknownInterrupt,
 
+    #[error("interrupt vector is reserved by machine: {0}")]
+    ReservedInterrupt(u8),
+
     #[error("unknown system call catched: {0}")]
     UnknownSystemCall(u64),
 
+    #[error("{kind}: {message}")]
+    Trap { kind: &'static str, message: String },
+
     #[error("catched division by zero")]
     DivisionByZero,
 
@@ -44,6 +82,27 @@ pub enum MvmError {
     #[error("allocator error [{0}]")]
     AllocatorError(#[from] super::allocator::AllocatorError),
 
+    #[error("heap check failed [{0}]")]
+    HeapError(#[from] super::heapcheck::HeapError),
+
     #[error("io error [{0}]")]
     IOError(#[from] std::io::Error),
 }
+
+impl MvmError {
+    /// Error without runtime context
+    pub fn inner(&self) -> &MvmError {
+        match self {
+            MvmError::Runtime { error, .. } => error.inner(),
+            _ => self,
+        }
+    }
+
+    /// Machine state at the moment of error, if it was raised by instruction
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            MvmError::Runtime { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+}
@@ -1,114 +1,174 @@
+use std::cmp::Ordering;
+use std::io::Write;
+
 use colored::Colorize;
 
 use super::{
     MvmError, Opcode, R_ACCUMULATOR, R_FRAME_POINTER, R_INSTRUCTION_POINTER, R_STACK_POINTER, VM,
+    decoder::Instruction,
 };
 
 impl VM {
-    pub fn execute_instruction(&mut self, instruction: u8) -> Result<(), MvmError> {
-        if self.text_section && instruction == 0xFF {
-            let address = self.fetch_u64()?;
-            self.set_register(R_INSTRUCTION_POINTER, address)?;
-
-            return Ok(());
-        }
+    pub fn execute_instruction(&mut self, instruction: Instruction) -> Result<(), MvmError> {
+        let [first, second, third, fourth] = instruction.operands;
 
-        let opcode = Opcode::try_from(instruction)?;
+        self.jumped = false;
 
-        match opcode {
+        match instruction.opcode {
             Opcode::Halt => {
                 self.running = false;
                 let _ = self.step_back()?;
             }
             Opcode::Return => {
-                self.pop_state()?;
+                self.pop_frame()?;
             }
             Opcode::Call => {
-                let address = self.fetch_u64()?;
+                let address = first;
+
+                self.push_frame()?;
+                self.jump(address)?;
+            }
+            Opcode::CallReg => {
+                let address = self.get_register(first)?;
+
+                self.push_frame()?;
+                self.jump(address)?;
+            }
+            Opcode::FullCallReg => {
+                let address = self.get_register(first)?;
 
                 self.push_state()?;
-                self.set_register(R_INSTRUCTION_POINTER, address)?;
+                self.jump(address)?;
+            }
+            Opcode::FullReturn => {
+                self.pop_state()?;
+            }
+            Opcode::FullCall => {
+                let address = first;
+
+                self.push_state()?;
+                self.jump(address)?;
             }
             Opcode::Interrupt => {
-                let address = self.fetch_u64()?;
+                let address = first;
                 let vector = self.memory.get_u8(address)?;
 
                 if let Some(handler) = self.interrupt_handlers[vector as usize] {
                     self.push_state()?;
-                    handler(self)?;
+
+                    // fault is raised by `int` instruction, so handler's saved state is dropped
+                    if let Err(err) = handler(self) {
+                        let accumulator = self.pop_state()?;
+                        self.set_register(R_ACCUMULATOR, accumulator)?;
+
+                        return Err(err);
+                    }
+                } else if let Some(handler_addr) = self.interrupt_vectors[vector as usize] {
+                    self.push_state()?;
+                    self.jump(handler_addr)?;
                 } else {
                     return Err(MvmError::UnknownInterrupt);
                 }
             }
+            Opcode::SetVector => {
+                let vector_addr = first;
+                let handler_addr = second;
+
+                let vector = self.memory.get_u8(vector_addr)?;
+
+                if self.interrupt_handlers[vector as usize].is_some() {
+                    return Err(MvmError::ReservedInterrupt(vector));
+                }
+
+                self.interrupt_vectors[vector as usize] = Some(handler_addr);
+            }
+            Opcode::InterruptReturn => {
+                let accumulator = self.pop_state()?;
+                self.set_register(R_ACCUMULATOR, accumulator)?;
+            }
             Opcode::Debug => {
-                let reg = self.fetch_u8()? as u64;
+                let reg = first;
                 let value = self.get_register(reg)?;
                 let reg_name = super::register_to_str(reg);
 
-                eprintln!(
+                writeln!(
+                    self.streams.stderr,
                     "{} %{reg_name} = {value}",
                     "[DEBUG]".truecolor(128, 128, 128)
-                );
+                )?;
             }
 
-            Opcode::DataSection => {
-                while let Ok(instr) = self.fetch_u8() {
-                    if instr == 0xff
-                        && let Ok(next_instr) = self.fetch_u8()
-                        && next_instr == Opcode::TextSection as u8
-                    {
-                        let _ = self.step_back()?;
-                        return Ok(());
-                    }
+            Opcode::Trap => {
+                return Err(self.trap("trap", first));
+            }
+            Opcode::Assert => {
+                let value = self.get_register(first)?;
+
+                if value == 0 {
+                    return Err(self.trap("assertion failed", second));
                 }
+            }
+
+            Opcode::DataSection => {
+                // text section start (its `0xff` byte) is found by `insert_program`
+                let (text_start, program_end) = self.memory.code;
 
-                if self.peek_byte().is_err() {
+                if text_start == 0 || text_start >= program_end {
                     return Err(MvmError::NoTextSection);
                 }
+
+                self.set_register(R_INSTRUCTION_POINTER, text_start + 1)?;
             }
             Opcode::TextSection => {
                 self.text_section = true;
             }
 
             Opcode::Mov8 => {
-                let destination = self.fetch_u8()?;
-                let address = self.fetch_u64()?;
+                let destination = first as u8;
+                let address = second;
 
                 let value = self.memory.get_u8(address)?;
                 self.set_register(destination as u64, value as u64)?;
             }
             Opcode::Mov16 => {
-                let destination = self.fetch_u8()?;
-                let address = self.fetch_u64()?;
+                let destination = first as u8;
+                let address = second;
 
                 let value = self.memory.get_u16(address)?;
                 self.set_register(destination as u64, value as u64)?;
             }
             Opcode::Mov32 => {
-                let destination = self.fetch_u8()?;
-                let address = self.fetch_u64()?;
+                let destination = first as u8;
+                let address = second;
 
                 let value = self.memory.get_u32(address)?;
                 self.set_register(destination as u64, value as u64)?;
             }
             Opcode::Mov64 => {
-                let destination = self.fetch_u8()?;
-                let address = self.fetch_u64()?;
+                let destination = first as u8;
+                let address = second;
 
                 let value = self.memory.get_u64(address)?;
                 self.set_register(destination as u64, value)?;
             }
             Opcode::MovR2R => {
-                let destination = self.fetch_u8()?;
-                let src = self.fetch_u8()?;
+                let destination = first as u8;
+                let src = second as u8;
 
                 let value = self.get_register(src as u64)?;
                 self.set_register(destination as u64, value)?;
             }
 
+            Opcode::Lea => {
+                let destination = first;
+                let address = second;
+
+                self.set_register(destination, address)?;
+            }
+
             Opcode::Load8 => {
-                let dest = self.fetch_u8()?;
-                let addr_reg = self.fetch_u8()?;
+                let dest = first as u8;
+                let addr_reg = second as u8;
 
                 let addr = self.get_register(addr_reg as u64)?;
                 let value = self.memory.get_u8(addr)?;
@@ -117,8 +177,8 @@ impl VM {
             }
 
             Opcode::Load16 => {
-                let dest = self.fetch_u8()?;
-                let addr_reg = self.fetch_u8()?;
+                let dest = first as u8;
+                let addr_reg = second as u8;
 
                 let addr = self.get_register(addr_reg as u64)?;
                 let value = self.memory.get_u16(addr)?;
@@ -127,8 +187,8 @@ impl VM {
             }
 
             Opcode::Load32 => {
-                let dest = self.fetch_u8()?;
-                let addr_reg = self.fetch_u8()?;
+                let dest = first as u8;
+                let addr_reg = second as u8;
 
                 let addr = self.get_register(addr_reg as u64)?;
                 let value = self.memory.get_u32(addr)?;
@@ -137,8 +197,8 @@ impl VM {
             }
 
             Opcode::Load64 => {
-                let dest = self.fetch_u8()?;
-                let addr_reg = self.fetch_u8()?;
+                let dest = first as u8;
+                let addr_reg = second as u8;
 
                 let addr = self.get_register(addr_reg as u64)?;
                 let value = self.memory.get_u64(addr)?;
@@ -147,8 +207,8 @@ impl VM {
             }
 
             Opcode::Store8 => {
-                let addr_reg = self.fetch_u8()?;
-                let src_reg = self.fetch_u8()?;
+                let addr_reg = first as u8;
+                let src_reg = second as u8;
 
                 let addr = self.get_register(addr_reg as u64)?;
                 let value = self.get_register(src_reg as u64)?;
@@ -157,8 +217,8 @@ impl VM {
             }
 
             Opcode::Store16 => {
-                let addr_reg = self.fetch_u8()?;
-                let src_reg = self.fetch_u8()?;
+                let addr_reg = first as u8;
+                let src_reg = second as u8;
 
                 let addr = self.get_register(addr_reg as u64)?;
                 let value = self.get_register(src_reg as u64)?;
@@ -167,8 +227,8 @@ impl VM {
             }
 
             Opcode::Store32 => {
-                let addr_reg = self.fetch_u8()?;
-                let src_reg = self.fetch_u8()?;
+                let addr_reg = first as u8;
+                let src_reg = second as u8;
 
                 let addr = self.get_register(addr_reg as u64)?;
                 let value = self.get_register(src_reg as u64)?;
@@ -177,8 +237,8 @@ impl VM {
             }
 
             Opcode::Store64 => {
-                let addr_reg = self.fetch_u8()?;
-                let src_reg = self.fetch_u8()?;
+                let addr_reg = first as u8;
+                let src_reg = second as u8;
 
                 let addr = self.get_register(addr_reg as u64)?;
                 let value = self.get_register(src_reg as u64)?;
@@ -186,83 +246,233 @@ impl VM {
                 self.memory.set_u64(addr, value)?;
             }
 
+            Opcode::LoadOffset8 => {
+                let addr = self.offset_address(second, third)?;
+                let value = self.memory.get_u8(addr)?;
+
+                self.set_register(first, value as u64)?;
+            }
+
+            Opcode::LoadOffset16 => {
+                let addr = self.offset_address(second, third)?;
+                let value = self.memory.get_u16(addr)?;
+
+                self.set_register(first, value as u64)?;
+            }
+
+            Opcode::LoadOffset32 => {
+                let addr = self.offset_address(second, third)?;
+                let value = self.memory.get_u32(addr)?;
+
+                self.set_register(first, value as u64)?;
+            }
+
+            Opcode::LoadOffset64 => {
+                let addr = self.offset_address(second, third)?;
+                let value = self.memory.get_u64(addr)?;
+
+                self.set_register(first, value)?;
+            }
+
+            Opcode::StoreOffset8 => {
+                let addr = self.offset_address(first, second)?;
+                let value = self.get_register(third)?;
+
+                self.memory.set_u8(addr, value as u8)?;
+            }
+
+            Opcode::StoreOffset16 => {
+                let addr = self.offset_address(first, second)?;
+                let value = self.get_register(third)?;
+
+                self.memory.set_u16(addr, value as u16)?;
+            }
+
+            Opcode::StoreOffset32 => {
+                let addr = self.offset_address(first, second)?;
+                let value = self.get_register(third)?;
+
+                self.memory.set_u32(addr, value as u32)?;
+            }
+
+            Opcode::StoreOffset64 => {
+                let addr = self.offset_address(first, second)?;
+                let value = self.get_register(third)?;
+
+                self.memory.set_u64(addr, value)?;
+            }
+
+            Opcode::LoadIndexed8 => {
+                let addr = self.indexed_address(second, third, fourth)?;
+                let value = self.memory.get_u8(addr)?;
+
+                self.set_register(first, value as u64)?;
+            }
+
+            Opcode::LoadIndexed16 => {
+                let addr = self.indexed_address(second, third, fourth)?;
+                let value = self.memory.get_u16(addr)?;
+
+                self.set_register(first, value as u64)?;
+            }
+
+            Opcode::LoadIndexed32 => {
+                let addr = self.indexed_address(second, third, fourth)?;
+                let value = self.memory.get_u32(addr)?;
+
+                self.set_register(first, value as u64)?;
+            }
+
+            Opcode::LoadIndexed64 => {
+                let addr = self.indexed_address(second, third, fourth)?;
+                let value = self.memory.get_u64(addr)?;
+
+                self.set_register(first, value)?;
+            }
+
+            Opcode::StoreIndexed8 => {
+                let addr = self.indexed_address(first, second, third)?;
+                let value = self.get_register(fourth)?;
+
+                self.memory.set_u8(addr, value as u8)?;
+            }
+
+            Opcode::StoreIndexed16 => {
+                let addr = self.indexed_address(first, second, third)?;
+                let value = self.get_register(fourth)?;
+
+                self.memory.set_u16(addr, value as u16)?;
+            }
+
+            Opcode::StoreIndexed32 => {
+                let addr = self.indexed_address(first, second, third)?;
+                let value = self.get_register(fourth)?;
+
+                self.memory.set_u32(addr, value as u32)?;
+            }
+
+            Opcode::StoreIndexed64 => {
+                let addr = self.indexed_address(first, second, third)?;
+                let value = self.get_register(fourth)?;
+
+                self.memory.set_u64(addr, value)?;
+            }
+
+            Opcode::MemCopy => {
+                let dest = self.get_register(first)?;
+                let src = self.get_register(second)?;
+                let length = self.get_register(third)?;
+
+                self.memory.copy(dest, src, length)?;
+            }
+
+            Opcode::MemSet => {
+                let dest = self.get_register(first)?;
+                let value = self.get_register(second)?;
+                let length = self.get_register(third)?;
+
+                self.memory.fill(dest, value as u8, length)?;
+            }
+
+            Opcode::MemCompare => {
+                let lhs = self.get_register(first)?;
+                let rhs = self.get_register(second)?;
+                let length = self.get_register(third)?;
+
+                let cmp_result = match self.memory.compare(lhs, rhs, length)? {
+                    Ordering::Greater => 1,
+                    Ordering::Less => 2,
+                    Ordering::Equal => 0,
+                };
+
+                self.set_register(R_ACCUMULATOR, cmp_result)?;
+            }
+
             Opcode::Push8 => {
-                let src = self.fetch_u8()?;
+                let src = first as u8;
                 let value = self.get_register(src as u64)?;
 
                 let stack_ptr = self.get_register(R_STACK_POINTER)?;
                 let frame_ptr = self.get_register(R_FRAME_POINTER)?;
 
-                let offset = stack_ptr - frame_ptr;
+                let offset = stack_ptr
+                    .checked_sub(frame_ptr)
+                    .ok_or(MvmError::StackOutOfFrame)?;
 
                 self.stack_push_u8(value as u8)?;
                 self.set_register(src as u64, offset)?;
             }
             Opcode::Push16 => {
-                let src = self.fetch_u8()?;
+                let src = first as u8;
                 let value = self.get_register(src as u64)?;
 
                 let stack_ptr = self.get_register(R_STACK_POINTER)?;
                 let frame_ptr = self.get_register(R_FRAME_POINTER)?;
 
-                let offset = stack_ptr - frame_ptr;
+                let offset = stack_ptr
+                    .checked_sub(frame_ptr)
+                    .ok_or(MvmError::StackOutOfFrame)?;
 
                 self.stack_push_u16(value as u16)?;
                 self.set_register(src as u64, offset)?;
             }
             Opcode::Push32 => {
-                let src = self.fetch_u8()?;
+                let src = first as u8;
                 let value = self.get_register(src as u64)?;
 
                 let stack_ptr = self.get_register(R_STACK_POINTER)?;
                 let frame_ptr = self.get_register(R_FRAME_POINTER)?;
 
-                let offset = stack_ptr - frame_ptr;
+                let offset = stack_ptr
+                    .checked_sub(frame_ptr)
+                    .ok_or(MvmError::StackOutOfFrame)?;
 
                 self.stack_push_u32(value as u32)?;
                 self.set_register(src as u64, offset)?;
             }
             Opcode::Push64 => {
-                let src = self.fetch_u8()?;
+                let src = first as u8;
                 let value = self.get_register(src as u64)?;
 
                 let stack_ptr = self.get_register(R_STACK_POINTER)?;
                 let frame_ptr = self.get_register(R_FRAME_POINTER)?;
 
-                let offset = stack_ptr - frame_ptr;
+                let offset = stack_ptr
+                    .checked_sub(frame_ptr)
+                    .ok_or(MvmError::StackOutOfFrame)?;
 
-                self.stack_push_u32(value as u32)?;
+                self.stack_push_u64(value)?;
                 self.set_register(src as u64, offset)?;
             }
 
             Opcode::Pop8 => {
-                let dest = self.fetch_u8()?;
+                let dest = first as u8;
                 let value = self.stack_pop_u8()?;
 
                 self.set_register(dest as u64, value as u64)?;
             }
             Opcode::Pop16 => {
-                let dest = self.fetch_u8()?;
+                let dest = first as u8;
                 let value = self.stack_pop_u16()?;
 
                 self.set_register(dest as u64, value as u64)?;
             }
             Opcode::Pop32 => {
-                let dest = self.fetch_u8()?;
+                let dest = first as u8;
                 let value = self.stack_pop_u32()?;
 
                 self.set_register(dest as u64, value as u64)?;
             }
             Opcode::Pop64 => {
-                let dest = self.fetch_u8()?;
+                let dest = first as u8;
                 let value = self.stack_pop_u64()?;
 
                 self.set_register(dest as u64, value)?;
             }
 
             Opcode::Frame8 => {
-                let dest = self.fetch_u8()?;
-                let address = self.fetch_u64()?;
+                let dest = first as u8;
+                let address = second;
 
                 let offset = self.memory.get_u16(address)?;
                 let value = self.frame_get_u8(offset)?;
@@ -270,8 +480,8 @@ impl VM {
                 self.set_register(dest as u64, value as u64)?;
             }
             Opcode::Frame16 => {
-                let dest = self.fetch_u8()?;
-                let address = self.fetch_u64()?;
+                let dest = first as u8;
+                let address = second;
 
                 let offset = self.memory.get_u16(address)?;
                 let value = self.frame_get_u16(offset)?;
@@ -279,8 +489,8 @@ impl VM {
                 self.set_register(dest as u64, value as u64)?;
             }
             Opcode::Frame32 => {
-                let dest = self.fetch_u8()?;
-                let address = self.fetch_u64()?;
+                let dest = first as u8;
+                let address = second;
 
                 let offset = self.memory.get_u16(address)?;
                 let value = self.frame_get_u32(offset)?;
@@ -288,8 +498,8 @@ impl VM {
                 self.set_register(dest as u64, value as u64)?;
             }
             Opcode::Frame64 => {
-                let dest = self.fetch_u8()?;
-                let address = self.fetch_u64()?;
+                let dest = first as u8;
+                let address = second;
 
                 let offset = self.memory.get_u16(address)?;
                 let value = self.frame_get_u64(offset)?;
@@ -298,8 +508,8 @@ impl VM {
             }
 
             Opcode::Peek8 => {
-                let dest = self.fetch_u8()?;
-                let address = self.fetch_u64()?;
+                let dest = first as u8;
+                let address = second;
 
                 let offset = self.memory.get_u16(address)?;
                 let value = self.stack_get_u8(offset)?;
@@ -307,8 +517,8 @@ impl VM {
                 self.set_register(dest as u64, value as u64)?;
             }
             Opcode::Peek16 => {
-                let dest = self.fetch_u8()?;
-                let address = self.fetch_u64()?;
+                let dest = first as u8;
+                let address = second;
 
                 let offset = self.memory.get_u16(address)?;
                 let value = self.stack_get_u16(offset)?;
@@ -316,8 +526,8 @@ impl VM {
                 self.set_register(dest as u64, value as u64)?;
             }
             Opcode::Peek32 => {
-                let dest = self.fetch_u8()?;
-                let address = self.fetch_u64()?;
+                let dest = first as u8;
+                let address = second;
 
                 let offset = self.memory.get_u16(address)?;
                 let value = self.stack_get_u32(offset)?;
@@ -325,8 +535,8 @@ impl VM {
                 self.set_register(dest as u64, value as u64)?;
             }
             Opcode::Peek64 => {
-                let dest = self.fetch_u8()?;
-                let address = self.fetch_u64()?;
+                let dest = first as u8;
+                let address = second;
 
                 let offset = self.memory.get_u16(address)?;
                 let value = self.stack_get_u64(offset)?;
@@ -334,41 +544,136 @@ impl VM {
                 self.set_register(dest as u64, value)?;
             }
 
+            Opcode::SetFrame8 => {
+                let address = first;
+                let src = second;
+
+                let offset = self.memory.get_u16(address)?;
+                let value = self.get_register(src)?;
+
+                self.check_frame_write(offset, 1)?;
+                self.frame_set_u8(offset, value as u8)?;
+            }
+            Opcode::SetFrame16 => {
+                let address = first;
+                let src = second;
+
+                let offset = self.memory.get_u16(address)?;
+                let value = self.get_register(src)?;
+
+                self.check_frame_write(offset, 2)?;
+                self.frame_set_u16(offset, value as u16)?;
+            }
+            Opcode::SetFrame32 => {
+                let address = first;
+                let src = second;
+
+                let offset = self.memory.get_u16(address)?;
+                let value = self.get_register(src)?;
+
+                self.check_frame_write(offset, 4)?;
+                self.frame_set_u32(offset, value as u32)?;
+            }
+            Opcode::SetFrame64 => {
+                let address = first;
+                let src = second;
+
+                let offset = self.memory.get_u16(address)?;
+                let value = self.get_register(src)?;
+
+                self.check_frame_write(offset, 8)?;
+                self.frame_set_u64(offset, value)?;
+            }
+            Opcode::Poke8 => {
+                let address = first;
+                let src = second;
+
+                let offset = self.memory.get_u16(address)?;
+                let value = self.get_register(src)?;
+
+                self.check_stack_write(offset, 1)?;
+                self.stack_set_u8(offset, value as u8)?;
+            }
+            Opcode::Poke16 => {
+                let address = first;
+                let src = second;
+
+                let offset = self.memory.get_u16(address)?;
+                let value = self.get_register(src)?;
+
+                self.check_stack_write(offset, 2)?;
+                self.stack_set_u16(offset, value as u16)?;
+            }
+            Opcode::Poke32 => {
+                let address = first;
+                let src = second;
+
+                let offset = self.memory.get_u16(address)?;
+                let value = self.get_register(src)?;
+
+                self.check_stack_write(offset, 4)?;
+                self.stack_set_u32(offset, value as u32)?;
+            }
+            Opcode::Poke64 => {
+                let address = first;
+                let src = second;
+
+                let offset = self.memory.get_u16(address)?;
+                let value = self.get_register(src)?;
+
+                self.check_stack_write(offset, 8)?;
+                self.stack_set_u64(offset, value)?;
+            }
+            Opcode::Enter => {
+                let size = self.memory.get_u16(first)? as u64;
+                let stack_ptr = self.get_register(R_STACK_POINTER)?;
+
+                if stack_ptr.saturating_add(size) > self.stack_end as u64 {
+                    return Err(MvmError::StackOverflow);
+                }
+
+                self.set_register(R_STACK_POINTER, stack_ptr + size)?;
+            }
+            Opcode::Leave => {
+                let frame_ptr = self.get_register(R_FRAME_POINTER)?;
+                self.set_register(R_STACK_POINTER, frame_ptr)?;
+            }
+
             Opcode::Add8 => {
-                let destination = self.fetch_u8()?;
-                let address = self.fetch_u64()?;
+                let destination = first as u8;
+                let address = second;
                 let value = self.memory.get_u8(address)?;
 
                 let dest_value = self.get_register(destination as u64)?;
                 self.set_register(destination as u64, dest_value.wrapping_add(value as u64))?;
             }
             Opcode::Add16 => {
-                let destination = self.fetch_u8()?;
-                let address = self.fetch_u64()?;
+                let destination = first as u8;
+                let address = second;
                 let value = self.memory.get_u16(address)?;
 
                 let dest_value = self.get_register(destination as u64)?;
                 self.set_register(destination as u64, dest_value.wrapping_add(value as u64))?;
             }
             Opcode::Add32 => {
-                let destination = self.fetch_u8()?;
-                let address = self.fetch_u64()?;
+                let destination = first as u8;
+                let address = second;
                 let value = self.memory.get_u32(address)?;
 
                 let dest_value = self.get_register(destination as u64)?;
                 self.set_register(destination as u64, dest_value.wrapping_add(value as u64))?;
             }
             Opcode::Add64 => {
-                let destination = self.fetch_u8()?;
-                let address = self.fetch_u64()?;
+                let destination = first as u8;
+                let address = second;
                 let value = self.memory.get_u64(address)?;
 
                 let dest_value = self.get_register(destination as u64)?;
                 self.set_register(destination as u64, dest_value.wrapping_add(value))?;
             }
             Opcode::AddR2R => {
-                let destination = self.fetch_u8()?;
-                let src = self.fetch_u8()?;
+                let destination = first as u8;
+                let src = second as u8;
 
                 let left = self.get_register(destination as u64)?;
                 let right = self.get_register(src as u64)?;
@@ -376,8 +681,8 @@ impl VM {
                 self.set_register(destination as u64, left.wrapping_add(right))?;
             }
             Opcode::XAdd => {
-                let destination = self.fetch_u8()?;
-                let src = self.fetch_u8()?;
+                let destination = first as u8;
+                let src = second as u8;
 
                 let left = self.get_register(destination as u64)?;
                 let right = self.get_register(src as u64)?;
@@ -387,40 +692,40 @@ impl VM {
             }
 
             Opcode::Sub8 => {
-                let destination = self.fetch_u8()?;
-                let address = self.fetch_u64()?;
+                let destination = first as u8;
+                let address = second;
                 let value = self.memory.get_u8(address)?;
 
                 let dest_value = self.get_register(destination as u64)?;
                 self.set_register(destination as u64, dest_value.wrapping_sub(value as u64))?;
             }
             Opcode::Sub16 => {
-                let destination = self.fetch_u8()?;
-                let address = self.fetch_u64()?;
+                let destination = first as u8;
+                let address = second;
                 let value = self.memory.get_u16(address)?;
 
                 let dest_value = self.get_register(destination as u64)?;
                 self.set_register(destination as u64, dest_value.wrapping_sub(value as u64))?;
             }
             Opcode::Sub32 => {
-                let destination = self.fetch_u8()?;
-                let address = self.fetch_u64()?;
+                let destination = first as u8;
+                let address = second;
                 let value = self.memory.get_u32(address)?;
 
                 let dest_value = self.get_register(destination as u64)?;
                 self.set_register(destination as u64, dest_value.wrapping_sub(value as u64))?;
             }
             Opcode::Sub64 => {
-                let destination = self.fetch_u8()?;
-                let address = self.fetch_u64()?;
+                let destination = first as u8;
+                let address = second;
                 let value = self.memory.get_u64(address)?;
 
                 let dest_value = self.get_register(destination as u64)?;
                 self.set_register(destination as u64, dest_value.wrapping_sub(value))?;
             }
             Opcode::SubR2R => {
-                let destination = self.fetch_u8()?;
-                let src = self.fetch_u8()?;
+                let destination = first as u8;
+                let src = second as u8;
 
                 let left = self.get_register(destination as u64)?;
                 let right = self.get_register(src as u64)?;
@@ -429,40 +734,40 @@ impl VM {
             }
 
             Opcode::Mul8 => {
-                let destination = self.fetch_u8()?;
-                let address = self.fetch_u64()?;
+                let destination = first as u8;
+                let address = second;
                 let value = self.memory.get_u8(address)?;
 
                 let dest_value = self.get_register(destination as u64)?;
                 self.set_register(destination as u64, dest_value.wrapping_mul(value as u64))?;
             }
             Opcode::Mul16 => {
-                let destination = self.fetch_u8()?;
-                let address = self.fetch_u64()?;
+                let destination = first as u8;
+                let address = second;
                 let value = self.memory.get_u16(address)?;
 
                 let dest_value = self.get_register(destination as u64)?;
                 self.set_register(destination as u64, dest_value.wrapping_mul(value as u64))?;
             }
             Opcode::Mul32 => {
-                let destination = self.fetch_u8()?;
-                let address = self.fetch_u64()?;
+                let destination = first as u8;
+                let address = second;
                 let value = self.memory.get_u32(address)?;
 
                 let dest_value = self.get_register(destination as u64)?;
                 self.set_register(destination as u64, dest_value.wrapping_mul(value as u64))?;
             }
             Opcode::Mul64 => {
-                let destination = self.fetch_u8()?;
-                let address = self.fetch_u64()?;
+                let destination = first as u8;
+                let address = second;
                 let value = self.memory.get_u64(address)?;
 
                 let dest_value = self.get_register(destination as u64)?;
                 self.set_register(destination as u64, dest_value.wrapping_mul(value))?;
             }
             Opcode::MulR2R => {
-                let destination = self.fetch_u8()?;
-                let src = self.fetch_u8()?;
+                let destination = first as u8;
+                let src = second as u8;
 
                 let left = self.get_register(destination as u64)?;
                 let right = self.get_register(src as u64)?;
@@ -471,8 +776,8 @@ impl VM {
             }
 
             Opcode::Div8 => {
-                let destination = self.fetch_u8()?;
-                let address = self.fetch_u64()?;
+                let destination = first as u8;
+                let address = second;
                 let value = self.memory.get_u8(address)?;
 
                 if value == 0 {
@@ -483,8 +788,8 @@ impl VM {
                 self.set_register(destination as u64, dest_value.wrapping_div(value as u64))?;
             }
             Opcode::Div16 => {
-                let destination = self.fetch_u8()?;
-                let address = self.fetch_u64()?;
+                let destination = first as u8;
+                let address = second;
                 let value = self.memory.get_u16(address)?;
 
                 if value == 0 {
@@ -495,8 +800,8 @@ impl VM {
                 self.set_register(destination as u64, dest_value.wrapping_div(value as u64))?;
             }
             Opcode::Div32 => {
-                let destination = self.fetch_u8()?;
-                let address = self.fetch_u64()?;
+                let destination = first as u8;
+                let address = second;
                 let value = self.memory.get_u32(address)?;
 
                 if value == 0 {
@@ -507,8 +812,8 @@ impl VM {
                 self.set_register(destination as u64, dest_value.wrapping_div(value as u64))?;
             }
             Opcode::Div64 => {
-                let destination = self.fetch_u8()?;
-                let address = self.fetch_u64()?;
+                let destination = first as u8;
+                let address = second;
                 let value = self.memory.get_u64(address)?;
 
                 if value == 0 {
@@ -519,8 +824,8 @@ impl VM {
                 self.set_register(destination as u64, dest_value.wrapping_div(value))?;
             }
             Opcode::DivR2R => {
-                let destination = self.fetch_u8()?;
-                let src = self.fetch_u8()?;
+                let destination = first as u8;
+                let src = second as u8;
 
                 let left = self.get_register(destination as u64)?;
                 let right = self.get_register(src as u64)?;
@@ -533,8 +838,8 @@ impl VM {
             }
 
             Opcode::Cmp8 => {
-                let reg = self.fetch_u8()?;
-                let addr = self.fetch_u64()?;
+                let reg = first as u8;
+                let addr = second;
 
                 let reg_value = self.get_register(reg as u64)?;
                 let addr_value = self.memory.get_u8(addr)? as u64;
@@ -550,8 +855,8 @@ impl VM {
                 self.set_register(R_ACCUMULATOR, cmp_result)?;
             }
             Opcode::Cmp16 => {
-                let reg = self.fetch_u8()?;
-                let addr = self.fetch_u64()?;
+                let reg = first as u8;
+                let addr = second;
 
                 let reg_value = self.get_register(reg as u64)?;
                 let addr_value = self.memory.get_u16(addr)? as u64;
@@ -567,8 +872,8 @@ impl VM {
                 self.set_register(R_ACCUMULATOR, cmp_result)?;
             }
             Opcode::Cmp32 => {
-                let reg = self.fetch_u8()?;
-                let addr = self.fetch_u64()?;
+                let reg = first as u8;
+                let addr = second;
 
                 let reg_value = self.get_register(reg as u64)?;
                 let addr_value = self.memory.get_u32(addr)? as u64;
@@ -584,8 +889,8 @@ impl VM {
                 self.set_register(R_ACCUMULATOR, cmp_result)?;
             }
             Opcode::Cmp64 => {
-                let reg = self.fetch_u8()?;
-                let addr = self.fetch_u64()?;
+                let reg = first as u8;
+                let addr = second;
 
                 let reg_value = self.get_register(reg as u64)?;
                 let addr_value = self.memory.get_u64(addr)?;
@@ -601,8 +906,8 @@ impl VM {
                 self.set_register(R_ACCUMULATOR, cmp_result)?;
             }
             Opcode::CmpR2R => {
-                let left_reg = self.fetch_u8()?;
-                let right_reg = self.fetch_u8()?;
+                let left_reg = first as u8;
+                let right_reg = second as u8;
 
                 let left_value = self.get_register(left_reg as u64)?;
                 let right_value = self.get_register(right_reg as u64)?;
@@ -619,57 +924,137 @@ impl VM {
             }
 
             Opcode::Jmp => {
-                let addr = self.fetch_u64()?;
-                self.set_register(R_INSTRUCTION_POINTER, addr)?;
+                let addr = first;
+                self.jump(addr)?;
             }
             Opcode::Jz => {
-                let addr = self.fetch_u64()?;
+                let addr = first;
                 let acc_value = self.get_register(R_ACCUMULATOR)?;
 
                 if acc_value == 0 {
-                    self.set_register(R_INSTRUCTION_POINTER, addr)?;
+                    self.jump(addr)?;
                 }
             }
             Opcode::Jnz => {
-                let addr = self.fetch_u64()?;
+                let addr = first;
                 let acc_value = self.get_register(R_ACCUMULATOR)?;
 
                 if acc_value != 0 {
-                    self.set_register(R_INSTRUCTION_POINTER, addr)?;
+                    self.jump(addr)?;
                 }
             }
             Opcode::Je => {
-                let val_addr = self.fetch_u64()?;
-                let label_addr = self.fetch_u64()?;
+                let val_addr = first;
+                let label_addr = second;
 
                 let data_value = self.memory.get_u64(val_addr)?;
                 let acc_value = self.get_register(R_ACCUMULATOR)?;
 
                 if acc_value == data_value {
-                    self.set_register(R_INSTRUCTION_POINTER, label_addr)?;
+                    self.jump(label_addr)?;
                 }
             }
             Opcode::Jne => {
-                let val_addr = self.fetch_u64()?;
-                let label_addr = self.fetch_u64()?;
+                let val_addr = first;
+                let label_addr = second;
+
+                let data_value = self.memory.get_u64(val_addr)?;
+                let acc_value = self.get_register(R_ACCUMULATOR)?;
+
+                if acc_value != data_value {
+                    self.jump(label_addr)?;
+                }
+            }
+
+            Opcode::JmpReg => {
+                let addr = self.get_register(first)?;
+                self.jump(addr)?;
+            }
+            Opcode::JzReg => {
+                let addr = self.get_register(first)?;
+                let acc_value = self.get_register(R_ACCUMULATOR)?;
+
+                if acc_value == 0 {
+                    self.jump(addr)?;
+                }
+            }
+            Opcode::JnzReg => {
+                let addr = self.get_register(first)?;
+                let acc_value = self.get_register(R_ACCUMULATOR)?;
+
+                if acc_value != 0 {
+                    self.jump(addr)?;
+                }
+            }
+            Opcode::JeReg => {
+                let val_addr = first;
+                let addr = self.get_register(second)?;
+
+                let data_value = self.memory.get_u64(val_addr)?;
+                let acc_value = self.get_register(R_ACCUMULATOR)?;
+
+                if acc_value == data_value {
+                    self.jump(addr)?;
+                }
+            }
+            Opcode::JneReg => {
+                let val_addr = first;
+                let addr = self.get_register(second)?;
 
                 let data_value = self.memory.get_u64(val_addr)?;
                 let acc_value = self.get_register(R_ACCUMULATOR)?;
 
                 if acc_value != data_value {
-                    self.set_register(R_INSTRUCTION_POINTER, label_addr)?;
+                    self.jump(addr)?;
                 }
             }
         }
 
         Ok(())
     }
+
+    /// Effective address of `[%base + $disp]` operand
+    fn offset_address(&self, base: u64, displacement: u64) -> Result<u64, MvmError> {
+        Ok(self.get_register(base)?.wrapping_add(displacement))
+    }
+
+    /// Effective address of `[%base + %index * $scale]` operand
+    fn indexed_address(&self, base: u64, index: u64, scale: u64) -> Result<u64, MvmError> {
+        let base = self.get_register(base)?;
+        let index = self.get_register(index)?;
+
+        Ok(base.wrapping_add(index.wrapping_mul(scale)))
+    }
+
+    /// Transfers control to `address` (taken jump, call or program interrupt handler)
+    fn jump(&mut self, address: u64) -> Result<(), MvmError> {
+        self.jumped = true;
+        self.set_register(R_INSTRUCTION_POINTER, address)
+    }
+
+    /// Builds trap error with message from `ascii` string label.
+    /// Message is cut at null byte, memory end or `TRAP_MESSAGE_LIMIT`.
+    fn trap(&self, kind: &'static str, label: u64) -> MvmError {
+        const TRAP_MESSAGE_LIMIT: u64 = 256;
+
+        // `ascii` directive places string address before its bytes
+        let bytes = (0..TRAP_MESSAGE_LIMIT)
+            .map_while(|offset| self.memory.get_u8(label.wrapping_add(8 + offset)).ok())
+            .take_while(|byte| *byte != 0)
+            .collect::<Vec<_>>();
+
+        MvmError::Trap {
+            kind,
+            message: String::from_utf8_lossy(&bytes).into_owned(),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::vm::{R_ACCUMULATOR, R0, R1};
+    use crate::vm::interrupts::{FAULT_DIVISION_BY_ZERO, FAULT_SEGMENTATION};
+    use crate::vm::{R_ACCUMULATOR, R_SYSTEM_CALL, R0, R1, R2, R3, R4, R5};
 
     #[test]
     fn vm_skip_data_section_test() -> Result<(), MvmError> {
@@ -693,6 +1078,45 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn vm_skip_data_section_constants_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(64, 16)?;
+
+        // constant pool ends with `0xff` byte
+        let program = [
+            Opcode::DataSection as u8,
+            0x7f,
+            0xff,
+            0xff,
+            0xff,
+            0xff,
+            0xff,
+            0xff,
+            0xff,
+            0xff,
+            Opcode::TextSection as u8,
+            // mov %r0, 1
+            Opcode::Mov64 as u8,
+            R0 as u8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            1,
+            Opcode::Halt as u8,
+        ];
+
+        vm.constants_size = 8;
+        vm.insert_program(&program)?;
+        vm.run()?;
+
+        assert_eq!(vm.get_register(R0)?, i64::MAX as u64);
+        Ok(())
+    }
+
     #[test]
     fn vm_skip_data_section_error_test() -> Result<(), MvmError> {
         let mut vm = VM::new(64, 16)?;
@@ -886,6 +1310,41 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn instruction_lea_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(64, 16)?;
+
+        let program = [
+            Opcode::DataSection as u8,
+            // -- data section --
+            // -- data section end --
+            0xff,
+            Opcode::TextSection as u8,
+            // -- program --
+            // lea %r0, 13
+            Opcode::Lea as u8,
+            R0 as u8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            13,
+            // -- program end --
+            Opcode::Halt as u8,
+        ];
+
+        vm.insert_program(&program)?;
+        vm.run()?;
+
+        // address itself, not the value (`halt` opcode) placed there
+        assert_eq!(vm.get_register(R0)?, 13);
+
+        Ok(())
+    }
+
     #[test]
     fn instruction_load8_test() -> Result<(), MvmError> {
         let mut vm = VM::new(64, 16)?;
@@ -1139,14 +1598,213 @@ mod tests {
     }
 
     #[test]
-    fn instruction_add8_test() -> Result<(), MvmError> {
+    fn instruction_load_store_offset_test() -> Result<(), MvmError> {
         let mut vm = VM::new(64, 16)?;
 
-        vm.set_register(R0, 123)?;
+        vm.set_register(R0, 40)?;
+        vm.set_register(R1, 0xABCD)?;
+        vm.memory.set_u64(48, 123)?;
 
         let program = [
-            Opcode::DataSection as u8,
-            // -- data section --
+            // load64 %r2, [%r0 + $8]
+            Opcode::LoadOffset64 as u8,
+            R2 as u8,
+            R0 as u8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            8,
+            // store16 [%r0 - $2], %r1
+            Opcode::StoreOffset16 as u8,
+            R0 as u8,
+            0xff,
+            0xff,
+            0xff,
+            0xff,
+            0xff,
+            0xff,
+            0xff,
+            0xfe,
+            R1 as u8,
+            Opcode::Halt as u8,
+        ];
+
+        vm.insert_program(&program)?;
+        vm.run()?;
+
+        assert_eq!(vm.get_register(R2)?, 123);
+        assert_eq!(vm.memory.get_u16(38)?, 0xABCD);
+
+        Ok(())
+    }
+
+    #[test]
+    fn instruction_load_store_indexed_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(64, 16)?;
+
+        vm.set_register(R0, 24)?;
+        vm.set_register(R1, 2)?;
+        vm.set_register(R2, 0x12345678)?;
+        vm.memory.set_u64(40, 321)?;
+
+        let program = [
+            // load64 %r3, [%r0 + %r1 * $8]
+            Opcode::LoadIndexed64 as u8,
+            R3 as u8,
+            R0 as u8,
+            R1 as u8,
+            8,
+            // store32 [%r0 + %r1 * $4], %r2
+            Opcode::StoreIndexed32 as u8,
+            R0 as u8,
+            R1 as u8,
+            4,
+            R2 as u8,
+            // load8 %r4, [%r0 + %r1]
+            Opcode::LoadIndexed8 as u8,
+            R4 as u8,
+            R0 as u8,
+            R1 as u8,
+            1,
+            Opcode::Halt as u8,
+        ];
+
+        vm.insert_program(&program)?;
+        vm.run()?;
+
+        assert_eq!(vm.get_register(R3)?, 321);
+        assert_eq!(vm.memory.get_u32(32)?, 0x12345678);
+        assert_eq!(vm.get_register(R4)?, vm.memory.get_u8(26)? as u64);
+
+        Ok(())
+    }
+
+    #[test]
+    fn instruction_bulk_memory_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(64, 16)?;
+
+        vm.set_register(R0, 32)?;
+        vm.set_register(R1, 0xAB)?;
+        vm.set_register(R2, 4)?;
+        vm.set_register(R3, 40)?;
+
+        let program = [
+            // memset %r0, %r1, %r2
+            Opcode::MemSet as u8,
+            R0 as u8,
+            R1 as u8,
+            R2 as u8,
+            // memcpy %r3, %r0, %r2
+            Opcode::MemCopy as u8,
+            R3 as u8,
+            R0 as u8,
+            R2 as u8,
+            // memcmp %r0, %r3, %r2
+            Opcode::MemCompare as u8,
+            R0 as u8,
+            R3 as u8,
+            R2 as u8,
+            Opcode::Halt as u8,
+        ];
+
+        vm.insert_program(&program)?;
+        vm.run()?;
+
+        assert_eq!(vm.memory.get_u32(32)?, 0xABABABAB);
+        assert_eq!(vm.memory.get_u32(40)?, 0xABABABAB);
+        assert_eq!(vm.get_register(R_ACCUMULATOR)?, 0);
+
+        // different ranges and out of bounds range
+
+        vm.memory.set_u8(41, 0)?;
+        vm.execute_instruction(Instruction {
+            opcode: Opcode::MemCompare,
+            operands: [R0, R3, R2, 0],
+            length: 4,
+        })?;
+
+        assert_eq!(vm.get_register(R_ACCUMULATOR)?, 1);
+
+        vm.set_register(R2, 64)?;
+
+        assert!(matches!(
+            vm.execute_instruction(Instruction {
+                opcode: Opcode::MemCopy,
+                operands: [R3, R0, R2, 0],
+                length: 4,
+            }),
+            Err(MvmError::SegmentationFault(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn instruction_assert_trap_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(128, 32)?;
+
+        vm.set_register(R0, 1)?;
+
+        #[rustfmt::skip]
+        let program = [
+            Opcode::DataSection as u8,
+            // message: ascii "oops\0"
+            0, 0, 0, 0, 0, 0, 0, 1, b'o', b'o', b'p', b's', 0,
+            0xff,
+            Opcode::TextSection as u8,
+            // 16: assert %r0, message
+            Opcode::Assert as u8, R0 as u8, 0, 0, 0, 0, 0, 0, 0, 1,
+            // 26: trap message
+            Opcode::Trap as u8, 0, 0, 0, 0, 0, 0, 0, 1,
+            Opcode::Halt as u8,
+        ];
+
+        vm.insert_program(&program)?;
+
+        let err = vm.run().unwrap_err();
+        let context = err.context().expect("runtime context");
+
+        let MvmError::Trap { kind, message } = err.inner() else {
+            panic!("trap expected");
+        };
+
+        assert_eq!(*kind, "trap");
+        assert_eq!(message, "oops");
+        assert_eq!(context.address, 26);
+        assert_eq!(context.registers[R0 as usize], 1);
+        assert_eq!(context.registers[R_INSTRUCTION_POINTER as usize], 26);
+
+        // failed assertion
+        vm.set_register(R0, 0)?;
+        vm.set_register(R_INSTRUCTION_POINTER, 16)?;
+
+        let err = vm.run().unwrap_err();
+
+        assert!(matches!(
+            err.inner(),
+            MvmError::Trap {
+                kind: "assertion failed",
+                ..
+            }
+        ));
+        assert_eq!(err.context().map(|context| context.address), Some(16));
+
+        Ok(())
+    }
+
+    #[test]
+    fn instruction_add8_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(64, 16)?;
+
+        vm.set_register(R0, 123)?;
+
+        let program = [
+            Opcode::DataSection as u8,
+            // -- data section --
             123,
             // -- data section end --
             0xff,
@@ -2081,6 +2739,40 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn instruction_push64_wide_value_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(64, 16)?;
+
+        vm.set_register(R0, 0x1122_3344_5566_7788)?;
+
+        let program = [
+            Opcode::DataSection as u8,
+            // -- data section --
+            // -- data section end --
+            0xff,
+            Opcode::TextSection as u8,
+            // -- program --
+            // push64 %r0
+            Opcode::Push64 as u8,
+            R0 as u8,
+            // pop64 %r1
+            Opcode::Pop64 as u8,
+            R1 as u8,
+            // -- program end --
+            Opcode::Halt as u8,
+        ];
+
+        vm.insert_program(&program)?;
+
+        let stack_ptr = vm.get_register(R_STACK_POINTER)?;
+        vm.run()?;
+
+        assert_eq!(vm.get_register(R1)?, 0x1122_3344_5566_7788);
+        assert_eq!(vm.get_register(R_STACK_POINTER)?, stack_ptr);
+
+        Ok(())
+    }
+
     #[test]
     fn instruction_pop8_test() -> Result<(), MvmError> {
         let mut vm = VM::new(64, 16)?;
@@ -2505,6 +3197,160 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn instruction_enter_setframe_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(128, 32)?;
+
+        vm.set_register(R0, 0xABCD)?;
+        vm.set_register(R1, 123)?;
+
+        let program = [
+            Opcode::DataSection as u8,
+            // -- data section --
+            0,
+            2,
+            0,
+            16,
+            // -- data section end --
+            0xff,
+            Opcode::TextSection as u8,
+            // -- program --
+            // enter $16
+            Opcode::Enter as u8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            3,
+            // setframe16 $2, %r0
+            Opcode::SetFrame16 as u8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            1,
+            R0 as u8,
+            // poke8 $2, %r1
+            Opcode::Poke8 as u8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            1,
+            R1 as u8,
+            // frame16 %r2, $2
+            Opcode::Frame16 as u8,
+            R2 as u8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            1,
+            // peek8 %r3, $2
+            Opcode::Peek8 as u8,
+            R3 as u8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            1,
+            // -- program end --
+            Opcode::Halt as u8,
+        ];
+
+        vm.insert_program(&program)?;
+
+        let frame_ptr = vm.get_register(R_FRAME_POINTER)?;
+
+        vm.run()?;
+
+        assert_eq!(vm.get_register(R_STACK_POINTER)?, frame_ptr + 16);
+        assert_eq!(vm.get_register(R2)?, 0xABCD);
+        assert_eq!(vm.get_register(R3)?, 123);
+        assert_eq!(vm.memory.get_u8(frame_ptr + 13)?, 123);
+
+        Ok(())
+    }
+
+    #[test]
+    fn instruction_leave_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(64, 32)?;
+
+        vm.stack_push_u64(1)?;
+        vm.stack_push_u64(2)?;
+
+        let program = [Opcode::Leave as u8, Opcode::Halt as u8];
+
+        vm.insert_program(&program)?;
+        vm.run()?;
+
+        assert_eq!(
+            vm.get_register(R_STACK_POINTER)?,
+            vm.get_register(R_FRAME_POINTER)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn instruction_setframe_out_of_frame_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(64, 32)?;
+
+        vm.stack_push_u8(1)?;
+
+        let program = [
+            Opcode::DataSection as u8,
+            // -- data section --
+            0,
+            // -- data section end --
+            0xff,
+            Opcode::TextSection as u8,
+            // -- program --
+            // poke16 $0, %r0
+            Opcode::Poke16 as u8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            1,
+            R0 as u8,
+            // -- program end --
+            Opcode::Halt as u8,
+        ];
+
+        vm.insert_program(&program)?;
+
+        assert!(matches!(
+            vm.run().as_ref().map_err(MvmError::inner),
+            Err(MvmError::StackOutOfFrame)
+        ));
+        assert!(matches!(
+            vm.check_frame_write(0, 2),
+            Err(MvmError::StackOutOfFrame)
+        ));
+        assert!(vm.check_frame_write(0, 1).is_ok());
+
+        Ok(())
+    }
+
     #[test]
     fn instruction_cmp8_test() -> Result<(), MvmError> {
         let mut vm = VM::new(64, 16)?;
@@ -3021,4 +3867,728 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn instruction_call_return_value_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(256, 128)?;
+
+        vm.set_register(R1, 123)?;
+
+        let program = [
+            Opcode::DataSection as u8,
+            // -- data section --
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            123,
+            // -- data section end --
+            0xff,
+            Opcode::TextSection as u8,
+            // -- program --
+
+            // call label
+            Opcode::Call as u8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            24,
+            // mov %r2, %r0
+            Opcode::MovR2R as u8,
+            R2 as u8,
+            R0 as u8,
+            Opcode::Halt as u8,
+            // label:
+            // mov %r0, %r1
+            Opcode::MovR2R as u8,
+            R0 as u8,
+            R1 as u8,
+            // push64 %r3
+            Opcode::Push64 as u8,
+            R3 as u8,
+            Opcode::Return as u8,
+            // -- program end --
+        ];
+
+        vm.insert_program(&program)?;
+
+        let stack_ptr = vm.get_register(R_STACK_POINTER)?;
+        vm.run()?;
+
+        assert_eq!(vm.get_register(R0)?, 123);
+        assert_eq!(vm.get_register(R2)?, 123);
+        assert_eq!(vm.get_register(R_STACK_POINTER)?, stack_ptr);
+        assert_eq!(vm.get_register(R_FRAME_POINTER)?, stack_ptr);
+
+        Ok(())
+    }
+
+    #[test]
+    fn instruction_fcall_fret_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(256, 128)?;
+
+        vm.set_register(R1, 123)?;
+
+        let program = [
+            Opcode::DataSection as u8,
+            // -- data section --
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            123,
+            // -- data section end --
+            0xff,
+            Opcode::TextSection as u8,
+            // -- program --
+
+            // fcall label
+            Opcode::FullCall as u8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            24,
+            // mov %r2, %r0
+            Opcode::MovR2R as u8,
+            R2 as u8,
+            R0 as u8,
+            Opcode::Halt as u8,
+            // label:
+            // mov %r0, %r1
+            Opcode::MovR2R as u8,
+            R0 as u8,
+            R1 as u8,
+            // push64 %r3
+            Opcode::Push64 as u8,
+            R3 as u8,
+            Opcode::FullReturn as u8,
+            // -- program end --
+        ];
+
+        vm.insert_program(&program)?;
+
+        let stack_ptr = vm.get_register(R_STACK_POINTER)?;
+        vm.run()?;
+
+        assert_eq!(vm.get_register(R0)?, 0);
+        assert_eq!(vm.get_register(R2)?, 0);
+        assert_eq!(vm.get_register(R_STACK_POINTER)?, stack_ptr);
+        assert_eq!(vm.get_register(R_FRAME_POINTER)?, stack_ptr);
+
+        Ok(())
+    }
+
+    #[test]
+    fn instruction_indirect_call_jump_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(256, 128)?;
+
+        vm.set_register(R1, 8)?;
+        vm.set_register(R3, 123)?;
+        vm.set_register(R4, 12)?;
+
+        let program = [
+            // call %r1
+            Opcode::CallReg as u8,
+            R1 as u8,
+            // jz %r4
+            Opcode::JzReg as u8,
+            R4 as u8,
+            // mov %r5, %r3
+            Opcode::MovR2R as u8,
+            R5 as u8,
+            R3 as u8,
+            Opcode::Halt as u8,
+            // function:
+            // mov %r0, %r3
+            Opcode::MovR2R as u8,
+            R0 as u8,
+            R3 as u8,
+            Opcode::Return as u8,
+            // jump target:
+            Opcode::Halt as u8,
+        ];
+
+        vm.insert_program(&program)?;
+        vm.run()?;
+
+        assert_eq!(vm.get_register(R0)?, 123);
+        assert_eq!(vm.get_register(R5)?, 0);
+        assert_eq!(vm.get_register(R_INSTRUCTION_POINTER)?, 12);
+
+        Ok(())
+    }
+
+    #[test]
+    fn instruction_setvec_iret_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(256, 128)?;
+
+        vm.set_register(R1, 123)?;
+        vm.set_register(R2, 100)?;
+        vm.set_register(R_ACCUMULATOR, 5)?;
+
+        let program = [
+            Opcode::DataSection as u8,
+            // -- data section --
+            7,
+            // -- data section end --
+            0xff,
+            Opcode::TextSection as u8,
+            // -- program --
+
+            // setvec $7, handler
+            Opcode::SetVector as u8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            1,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            31,
+            // int $7
+            Opcode::Interrupt as u8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            1,
+            Opcode::Halt as u8,
+            // handler:
+            // store8 %r2, %r1
+            Opcode::Store8 as u8,
+            R2 as u8,
+            R1 as u8,
+            // mov %accumulator, %r1
+            Opcode::MovR2R as u8,
+            R_ACCUMULATOR as u8,
+            R1 as u8,
+            Opcode::InterruptReturn as u8,
+            // -- program end --
+        ];
+
+        vm.insert_program(&program)?;
+        vm.run()?;
+
+        assert_eq!(vm.interrupt_vectors[7], Some(31));
+        assert_eq!(vm.memory.get_u8(100)?, 123);
+        assert_eq!(vm.get_register(R_ACCUMULATOR)?, 5);
+        assert_eq!(vm.get_register(R_INSTRUCTION_POINTER)?, 30);
+
+        Ok(())
+    }
+
+    #[test]
+    fn instruction_setvec_reserved_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(256, 128)?;
+
+        let program = [
+            Opcode::DataSection as u8,
+            // -- data section --
+            80,
+            // -- data section end --
+            0xff,
+            Opcode::TextSection as u8,
+            // -- program --
+
+            // setvec $80, 0
+            Opcode::SetVector as u8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            1,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            // -- program end --
+            Opcode::Halt as u8,
+        ];
+
+        vm.insert_program(&program)?;
+
+        assert!(matches!(
+            vm.run().as_ref().map_err(MvmError::inner),
+            Err(MvmError::ReservedInterrupt(80))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn instruction_fault_trap_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(256, 128)?;
+
+        let program = [
+            Opcode::DataSection as u8,
+            // -- data section --
+            1,
+            0,
+            // -- data section end --
+            0xff,
+            Opcode::TextSection as u8,
+            // -- program --
+
+            // setvec $int_fault, handler
+            Opcode::SetVector as u8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            1,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            33,
+            // div %r3, $0
+            Opcode::Div8 as u8,
+            R3 as u8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            2,
+            Opcode::Halt as u8,
+            // handler:
+            Opcode::Halt as u8,
+            // -- program end --
+        ];
+
+        vm.insert_program(&program)?;
+        vm.run()?;
+
+        assert_eq!(vm.get_register(R0)?, FAULT_DIVISION_BY_ZERO);
+        assert_eq!(vm.get_register(R1)?, 22);
+        assert_eq!(vm.get_register(R2)?, 0);
+        assert!(vm.fault_frame.is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn instruction_fault_iret_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(256, 128)?;
+
+        vm.set_register(R3, 200)?;
+        vm.set_register(R4, 201)?;
+
+        let program = [
+            Opcode::DataSection as u8,
+            // -- data section --
+            1,
+            0,
+            // -- data section end --
+            0xff,
+            Opcode::TextSection as u8,
+            // -- program --
+
+            // setvec $int_fault, handler
+            Opcode::SetVector as u8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            1,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            33,
+            // div %r3, $0
+            Opcode::Div8 as u8,
+            R3 as u8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            2,
+            Opcode::Halt as u8,
+            // handler:
+            // store8 %r3, %r0
+            Opcode::Store8 as u8,
+            R3 as u8,
+            R0 as u8,
+            // store8 %r4, %r1
+            Opcode::Store8 as u8,
+            R4 as u8,
+            R1 as u8,
+            Opcode::InterruptReturn as u8,
+            // -- program end --
+        ];
+
+        vm.insert_program(&program)?;
+        vm.run()?;
+
+        assert_eq!(vm.memory.get_u8(200)?, FAULT_DIVISION_BY_ZERO as u8);
+        assert_eq!(vm.memory.get_u8(201)?, 22);
+        assert_eq!(vm.get_register(R_INSTRUCTION_POINTER)?, 32);
+        assert!(vm.fault_frame.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn instruction_fault_unhandled_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(256, 128)?;
+
+        let program = [
+            Opcode::DataSection as u8,
+            // -- data section --
+            1,
+            0,
+            // -- data section end --
+            0xff,
+            Opcode::TextSection as u8,
+            // -- program --
+
+            // div %r3, $0
+            Opcode::Div8 as u8,
+            R3 as u8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            2,
+            Opcode::Halt as u8,
+            // handler:
+            Opcode::Halt as u8,
+            // -- program end --
+        ];
+
+        vm.insert_program(&program)?;
+
+        assert!(matches!(
+            vm.run().as_ref().map_err(MvmError::inner),
+            Err(MvmError::DivisionByZero)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn instruction_fault_double_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(256, 128)?;
+
+        let program = [
+            Opcode::DataSection as u8,
+            // -- data section --
+            1,
+            0,
+            // -- data section end --
+            0xff,
+            Opcode::TextSection as u8,
+            // -- program --
+
+            // setvec $int_fault, handler
+            Opcode::SetVector as u8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            1,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            33,
+            // div %r3, $0
+            Opcode::Div8 as u8,
+            R3 as u8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            2,
+            Opcode::Halt as u8,
+            // handler:
+            // div %r3, $0
+            Opcode::Div8 as u8,
+            R3 as u8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            2,
+            // -- program end --
+        ];
+
+        vm.insert_program(&program)?;
+
+        assert!(matches!(
+            vm.run().as_ref().map_err(MvmError::inner),
+            Err(MvmError::DivisionByZero)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn instruction_fault_in_syscall_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(1024, 512)?;
+
+        let program = [
+            Opcode::DataSection as u8,
+            // -- data section --
+            1,
+            0,
+            80,
+            // -- data section end --
+            0xff,
+            Opcode::TextSection as u8,
+            // -- program --
+
+            // setvec $int_fault, handler
+            Opcode::SetVector as u8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            1,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            33,
+            // int $syscall
+            Opcode::Interrupt as u8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            3,
+            Opcode::Halt as u8,
+            // handler:
+            Opcode::InterruptReturn as u8,
+            // -- program end --
+        ];
+
+        vm.insert_program(&program)?;
+
+        let stack_ptr = vm.get_register(R_STACK_POINTER)?;
+        let frame_ptr = vm.get_register(R_FRAME_POINTER)?;
+
+        // write(stdout, 60000, 8) with buffer out of memory
+        vm.set_register(R_SYSTEM_CALL, 2)?;
+        vm.set_register(R0, 1)?;
+        vm.set_register(R1, 60000)?;
+        vm.set_register(R2, 8)?;
+
+        vm.run()?;
+
+        assert_eq!(vm.get_register(R_INSTRUCTION_POINTER)?, 32);
+        assert_eq!(vm.get_register(R_STACK_POINTER)?, stack_ptr);
+        assert_eq!(vm.get_register(R_FRAME_POINTER)?, frame_ptr);
+        assert_eq!(vm.get_register(R1)?, 60000);
+        assert!(vm.fault_frame.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn instruction_fault_fetch_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(256, 128)?;
+
+        let program = [
+            Opcode::DataSection as u8,
+            // -- data section --
+            1,
+            0,
+            // -- data section end --
+            0xff,
+            Opcode::TextSection as u8,
+            // -- program --
+
+            // setvec $int_fault, handler
+            Opcode::SetVector as u8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            1,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            24,
+            // jmp %r5
+            Opcode::JmpReg as u8,
+            R5 as u8,
+            // handler:
+            Opcode::InterruptReturn as u8,
+            // -- program end --
+        ];
+
+        vm.insert_program(&program)?;
+        vm.set_register(R5, 60000)?;
+
+        // handler gets the fault, but can't resume execution
+        let error = vm.run().unwrap_err();
+
+        assert!(matches!(error.inner(), MvmError::SegmentationFault(60000)));
+        assert_eq!(error.context().map(|context| context.address), Some(60000));
+        assert_eq!(vm.get_register(R0)?, FAULT_SEGMENTATION);
+
+        Ok(())
+    }
+
+    #[test]
+    fn instruction_fault_fret_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(1024, 512)?;
+
+        let program = [
+            Opcode::DataSection as u8,
+            // -- data section --
+            1,
+            0,
+            // -- data section end --
+            0xff,
+            Opcode::TextSection as u8,
+            // -- program --
+
+            // setvec $int_fault, handler
+            Opcode::SetVector as u8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            1,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            43,
+            // div %r3, $0
+            Opcode::Div8 as u8,
+            R3 as u8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            2,
+            // div %r3, $0
+            Opcode::Div8 as u8,
+            R3 as u8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            2,
+            Opcode::Halt as u8,
+            // handler:
+            // int $int_accinc
+            Opcode::Interrupt as u8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            2,
+            Opcode::FullReturn as u8,
+            // -- program end --
+        ];
+
+        vm.insert_program(&program)?;
+        vm.run()?;
+
+        // handler left with `fret`, so the second fault isn't a nested one
+        assert_eq!(vm.get_register(R_ACCUMULATOR)?, 2);
+        assert!(vm.fault_frame.is_none());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,383 @@
+//! **Mvm Filesystem** gives programs access to host files through system calls. Every path is
+//! resolved inside a sandbox root directory, so programs can't reach anything outside of it.
+//! Errors are not fatal for the machine: they're returned to the program as numeric codes.
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+
+// Open Flags
+// -----------------
+pub const OPEN_READ: u64 = 1 << 0;
+pub const OPEN_WRITE: u64 = 1 << 1;
+pub const OPEN_CREATE: u64 = 1 << 2;
+pub const OPEN_TRUNCATE: u64 = 1 << 3;
+pub const OPEN_APPEND: u64 = 1 << 4;
+// -----------------
+
+// first descriptors are reserved for standard streams
+const FIRST_DESCRIPTOR: u64 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u64)]
+pub enum FsError {
+    NoRoot = 1,
+    PathEscape = 2,
+    InvalidPath = 3,
+    NotFound = 4,
+    PermissionDenied = 5,
+    AlreadyExists = 6,
+    BadDescriptor = 7,
+    InvalidArgument = 8,
+    BufferTooSmall = 9,
+    Other = 10,
+}
+
+impl FsError {
+    /// Error code in the form it is returned to program: negative number in two's complement.
+    pub fn code(self) -> u64 {
+        (self as u64).wrapping_neg()
+    }
+}
+
+impl From<std::io::Error> for FsError {
+    fn from(value: std::io::Error) -> Self {
+        match value.kind() {
+            std::io::ErrorKind::NotFound => FsError::NotFound,
+            std::io::ErrorKind::PermissionDenied => FsError::PermissionDenied,
+            std::io::ErrorKind::AlreadyExists => FsError::AlreadyExists,
+            std::io::ErrorKind::InvalidInput => FsError::InvalidArgument,
+            _ => FsError::Other,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MvmFilesystem {
+    root: Option<PathBuf>,
+
+    files: HashMap<u64, File>,
+    next_descriptor: u64,
+}
+
+impl Default for MvmFilesystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MvmFilesystem {
+    pub fn new() -> Self {
+        Self {
+            root: None,
+            files: HashMap::new(),
+            next_descriptor: FIRST_DESCRIPTOR,
+        }
+    }
+
+    pub fn set_root(&mut self, root: impl AsRef<Path>) -> std::io::Result<()> {
+        let root = root.as_ref().canonicalize()?;
+
+        if !root.is_dir() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotADirectory,
+                format!("`{}` is not a directory", root.display()),
+            ));
+        }
+
+        self.root = Some(root);
+        Ok(())
+    }
+
+    pub fn open(&mut self, path: &[u8], flags: u64) -> Result<u64, FsError> {
+        let path = self.resolve(path, true)?;
+        let flags = if flags == 0 { OPEN_READ } else { flags };
+
+        let file = OpenOptions::new()
+            .read(flags & OPEN_READ != 0)
+            .write(flags & (OPEN_WRITE | OPEN_TRUNCATE) != 0)
+            .create(flags & OPEN_CREATE != 0)
+            .truncate(flags & OPEN_TRUNCATE != 0)
+            .append(flags & OPEN_APPEND != 0)
+            .open(path)?;
+
+        let descriptor = self.next_descriptor;
+
+        self.next_descriptor += 1;
+        self.files.insert(descriptor, file);
+
+        Ok(descriptor)
+    }
+
+    pub fn close(&mut self, descriptor: u64) -> Result<u64, FsError> {
+        self.files
+            .remove(&descriptor)
+            .map(|_| 0)
+            .ok_or(FsError::BadDescriptor)
+    }
+
+    pub fn read(&mut self, descriptor: u64, buffer: &mut [u8]) -> Result<u64, FsError> {
+        let file = self
+            .files
+            .get_mut(&descriptor)
+            .ok_or(FsError::BadDescriptor)?;
+
+        Ok(file.read(buffer)? as u64)
+    }
+
+    pub fn write(&mut self, descriptor: u64, buffer: &[u8]) -> Result<u64, FsError> {
+        let file = self
+            .files
+            .get_mut(&descriptor)
+            .ok_or(FsError::BadDescriptor)?;
+
+        Ok(file.write(buffer)? as u64)
+    }
+
+    pub fn seek(&mut self, descriptor: u64, offset: i64, whence: u64) -> Result<u64, FsError> {
+        let file = self
+            .files
+            .get_mut(&descriptor)
+            .ok_or(FsError::BadDescriptor)?;
+
+        let position = match whence {
+            0 => SeekFrom::Start(offset as u64),
+            1 => SeekFrom::Current(offset),
+            2 => SeekFrom::End(offset),
+            _ => return Err(FsError::InvalidArgument),
+        };
+
+        Ok(file.seek(position)?)
+    }
+
+    /// Returns `[size, kind, readonly]` of the entry, where kind is: 0 - file, 1 - directory.
+    pub fn stat(&self, path: &[u8]) -> Result<[u64; 3], FsError> {
+        let metadata = std::fs::metadata(self.resolve(path, true)?)?;
+
+        Ok([
+            metadata.len(),
+            metadata.is_dir() as u64,
+            metadata.permissions().readonly() as u64,
+        ])
+    }
+
+    pub fn unlink(&self, path: &[u8]) -> Result<u64, FsError> {
+        let path = self.resolve(path, false)?;
+
+        if Some(&path) == self.root.as_ref() {
+            return Err(FsError::PermissionDenied);
+        }
+
+        if path.symlink_metadata()?.is_dir() {
+            std::fs::remove_dir(path)?;
+        } else {
+            std::fs::remove_file(path)?;
+        }
+
+        Ok(0)
+    }
+
+    /// Writes sorted directory entries names separated with `\n` to buffer.
+    /// Returns count of written bytes.
+    pub fn readdir(&self, path: &[u8], buffer: &mut [u8]) -> Result<u64, FsError> {
+        let mut names = std::fs::read_dir(self.resolve(path, true)?)?
+            .map(|entry| entry.map(|entry| entry.file_name().to_string_lossy().into_owned()))
+            .collect::<Result<Vec<String>, _>>()?;
+
+        names.sort();
+
+        let listing = names.join("\n");
+
+        if listing.len() > buffer.len() {
+            return Err(FsError::BufferTooSmall);
+        }
+
+        buffer[..listing.len()].copy_from_slice(listing.as_bytes());
+        Ok(listing.len() as u64)
+    }
+}
+
+impl MvmFilesystem {
+    fn resolve(&self, path: &[u8], follow_symlinks: bool) -> Result<PathBuf, FsError> {
+        let root = self.root.as_ref().ok_or(FsError::NoRoot)?;
+        let path = std::str::from_utf8(path).map_err(|_| FsError::InvalidPath)?;
+
+        if path.is_empty() || path.contains('\0') {
+            return Err(FsError::InvalidPath);
+        }
+
+        // all paths are relative to root, `..` can't go above it
+
+        let mut resolved = root.clone();
+
+        for component in Path::new(path).components() {
+            match component {
+                Component::RootDir | Component::CurDir => {}
+                Component::ParentDir => {
+                    if &resolved == root {
+                        return Err(FsError::PathEscape);
+                    }
+
+                    resolved.pop();
+                }
+                Component::Normal(part) => resolved.push(part),
+                Component::Prefix(_) => return Err(FsError::InvalidPath),
+            }
+        }
+
+        if &resolved == root {
+            return Ok(resolved);
+        }
+
+        // symbolic links may point outside, so resolving the real location
+
+        let name = resolved.file_name().ok_or(FsError::InvalidPath)?;
+        let parent = resolved
+            .parent()
+            .ok_or(FsError::InvalidPath)?
+            .canonicalize()?;
+
+        let mut real = parent.join(name);
+
+        if follow_symlinks && real.symlink_metadata().is_ok() {
+            real = real.canonicalize()?;
+        }
+
+        if !real.starts_with(root) {
+            return Err(FsError::PathEscape);
+        }
+
+        Ok(real)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sandbox(name: &str) -> (MvmFilesystem, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("mvm-fs-{}-{}", name, std::process::id()));
+
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut filesystem = MvmFilesystem::new();
+        filesystem.set_root(&dir).unwrap();
+
+        (filesystem, dir)
+    }
+
+    #[test]
+    fn filesystem_no_root_test() {
+        let mut filesystem = MvmFilesystem::new();
+
+        assert_eq!(filesystem.open(b"file", OPEN_READ), Err(FsError::NoRoot));
+    }
+
+    #[test]
+    fn filesystem_default_descriptors_test() {
+        let dir = std::env::temp_dir().join(format!("mvm-fs-default-{}", std::process::id()));
+
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut filesystem = MvmFilesystem::default();
+        filesystem.set_root(&dir).unwrap();
+
+        let fd = filesystem
+            .open(b"file.txt", OPEN_WRITE | OPEN_CREATE)
+            .unwrap();
+
+        assert_eq!(fd, FIRST_DESCRIPTOR);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn filesystem_path_escape_test() {
+        let (mut filesystem, dir) = sandbox("escape");
+
+        assert_eq!(
+            filesystem.open(b"../file", OPEN_READ),
+            Err(FsError::PathEscape)
+        );
+        assert_eq!(
+            filesystem.open(b"a/../../file", OPEN_READ),
+            Err(FsError::PathEscape)
+        );
+        assert_eq!(filesystem.stat(b"/").unwrap()[1], 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn filesystem_symlink_escape_test() {
+        let (mut filesystem, dir) = sandbox("symlink");
+
+        std::os::unix::fs::symlink(std::env::temp_dir(), dir.join("link")).unwrap();
+
+        assert_eq!(
+            filesystem.open(b"link/file", OPEN_READ),
+            Err(FsError::PathEscape)
+        );
+        assert_eq!(filesystem.unlink(b"link"), Ok(0));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn filesystem_read_write_test() {
+        let (mut filesystem, dir) = sandbox("rw");
+
+        let fd = filesystem
+            .open(b"file.txt", OPEN_WRITE | OPEN_CREATE)
+            .unwrap();
+
+        assert_eq!(fd, FIRST_DESCRIPTOR);
+        assert_eq!(filesystem.write(fd, b"hello").unwrap(), 5);
+        assert_eq!(filesystem.close(fd), Ok(0));
+        assert_eq!(filesystem.close(fd), Err(FsError::BadDescriptor));
+
+        let fd = filesystem.open(b"/file.txt", OPEN_READ).unwrap();
+        let mut buffer = [0; 8];
+
+        assert_eq!(filesystem.seek(fd, 1, 0), Ok(1));
+        assert_eq!(filesystem.read(fd, &mut buffer), Ok(4));
+        assert_eq!(&buffer[..4], b"ello");
+
+        assert_eq!(filesystem.stat(b"file.txt"), Ok([5, 0, 0]));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn filesystem_readdir_unlink_test() {
+        let (mut filesystem, dir) = sandbox("readdir");
+
+        filesystem.open(b"b", OPEN_CREATE | OPEN_WRITE).unwrap();
+        filesystem.open(b"a", OPEN_CREATE | OPEN_WRITE).unwrap();
+
+        let mut buffer = [0; 8];
+
+        assert_eq!(filesystem.readdir(b".", &mut buffer), Ok(3));
+        assert_eq!(&buffer[..3], b"a\nb");
+        assert_eq!(
+            filesystem.readdir(b".", &mut buffer[..2]),
+            Err(FsError::BufferTooSmall)
+        );
+
+        assert_eq!(filesystem.unlink(b"a"), Ok(0));
+        assert_eq!(filesystem.unlink(b"a"), Err(FsError::NotFound));
+        assert_eq!(filesystem.unlink(b"."), Err(FsError::PermissionDenied));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn filesystem_error_code_test() {
+        assert_eq!(FsError::NoRoot.code() as i64, -1);
+        assert_eq!(FsError::Other.code() as i64, -10);
+    }
+}
@@ -0,0 +1,454 @@
+//! **Heap Checker** is a debugging layer over allocator (`mvm run --heap-check`).
+//! Every block is surrounded with guard bytes which are verified when block is freed and at exit.
+//! Freed blocks are filled with poison bytes and kept in quarantine (not reused), so writes after
+//! free and double frees are reported with addresses of `int` instructions which allocated and
+//! freed the block. Reads of freed memory are not trapped, they return poison bytes.
+
+use std::collections::{BTreeMap, VecDeque};
+use thiserror::Error;
+
+use super::allocator::{AllocatorError, AllocatorStats};
+use super::{MvmError, R_INSTRUCTION_POINTER, VM};
+
+pub const GUARD_SIZE: usize = 8;
+pub const GUARD_BYTE: u8 = 0xFD;
+pub const FREED_BYTE: u8 = 0xDD;
+
+/// Freed bytes kept in quarantine before they are returned to allocator
+const QUARANTINE_LIMIT: usize = 64 * 1024;
+
+/// Length of `int` instruction (opcode and constant address)
+const INT_INSTRUCTION_LENGTH: u64 = 1 + 8;
+
+#[derive(Debug, Error)]
+pub enum HeapError {
+    #[error("double free of block `{ptr}` (allocated at {alloc_site}, freed at {free_site})")]
+    DoubleFree {
+        ptr: usize,
+        alloc_site: u64,
+        free_site: u64,
+    },
+
+    #[error("guard {side} block `{ptr}` is corrupted (allocated at {alloc_site})")]
+    GuardCorrupted {
+        ptr: usize,
+        side: &'static str,
+        alloc_site: u64,
+    },
+
+    #[error(
+        "block `{ptr}` is written after free at address {address} (allocated at {alloc_site}, freed at {free_site})"
+    )]
+    UseAfterFree {
+        ptr: usize,
+        address: usize,
+        alloc_site: u64,
+        free_site: u64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeapBlock {
+    /// Address of allocator unit (before leading guard)
+    pub unit: usize,
+    /// Requested size
+    pub size: usize,
+    pub alloc_site: u64,
+    pub free_site: u64,
+}
+
+/// Block which wasn't freed until exit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeapLeak {
+    pub ptr: usize,
+    pub size: usize,
+    pub alloc_site: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct HeapChecker {
+    pub enabled: bool,
+
+    /// Live blocks by pointer returned to program
+    blocks: BTreeMap<usize, HeapBlock>,
+    /// Freed blocks in order of freeing
+    quarantine: VecDeque<(usize, HeapBlock)>,
+    quarantine_size: usize,
+
+    /// Requested bytes of live blocks (without guards and quarantine)
+    used_size: usize,
+    peak_used_size: usize,
+}
+
+impl HeapChecker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl VM {
+    pub(super) fn checked_allocate(&mut self, size: usize) -> Result<usize, MvmError> {
+        self.verify_quarantine()?;
+
+        let unit_size = size
+            .checked_add(2 * GUARD_SIZE)
+            .ok_or(AllocatorError::OutOfMemory)?;
+
+        let unit = match self.allocate_unit(unit_size) {
+            Err(MvmError::AllocatorError(AllocatorError::OutOfMemory)) => {
+                self.release_quarantine(0)?;
+                self.allocate_unit(unit_size)?
+            }
+
+            result => result?,
+        };
+
+        let ptr = unit + GUARD_SIZE;
+
+        self.memory
+            .fill(unit as u64, GUARD_BYTE, GUARD_SIZE as u64)?;
+        self.memory
+            .fill((ptr + size) as u64, GUARD_BYTE, GUARD_SIZE as u64)?;
+
+        self.heap_check.blocks.insert(
+            ptr,
+            HeapBlock {
+                unit,
+                size,
+                alloc_site: self.syscall_site(),
+                free_site: 0,
+            },
+        );
+
+        self.heap_check.used_size += size;
+        self.heap_check.peak_used_size = self
+            .heap_check
+            .peak_used_size
+            .max(self.heap_check.used_size);
+
+        Ok(ptr)
+    }
+
+    pub(super) fn checked_deallocate(&mut self, ptr: usize) -> Result<(), MvmError> {
+        self.verify_quarantine()?;
+
+        let Some(mut block) = self.heap_check.blocks.remove(&ptr) else {
+            if let Some((_, block)) = self.quarantined(ptr) {
+                return Err(HeapError::DoubleFree {
+                    ptr,
+                    alloc_site: block.alloc_site,
+                    free_site: block.free_site,
+                }
+                .into());
+            }
+
+            return Err(AllocatorError::InvalidFreePointer(ptr).into());
+        };
+
+        self.verify_guards(ptr, &block)?;
+
+        block.free_site = self.syscall_site();
+        self.memory
+            .fill(ptr as u64, FREED_BYTE, block.size as u64)?;
+
+        self.heap_check.quarantine.push_back((ptr, block));
+        self.heap_check.quarantine_size += block.size;
+        self.heap_check.used_size -= block.size;
+
+        self.release_quarantine(QUARANTINE_LIMIT)
+    }
+
+    /// Always moves block, so stale pointers to the old one are caught
+    pub(super) fn checked_reallocate(
+        &mut self,
+        ptr: usize,
+        size: usize,
+    ) -> Result<usize, MvmError> {
+        if ptr == 0 {
+            return self.checked_allocate(size);
+        }
+
+        if size == 0 {
+            self.checked_deallocate(ptr)?;
+            return Ok(0);
+        }
+
+        let Some(block) = self.heap_check.blocks.get(&ptr).copied() else {
+            return Err(AllocatorError::InvalidReallocPointer(ptr).into());
+        };
+
+        let new_ptr = self.checked_allocate(size)?;
+
+        self.memory
+            .copy(new_ptr as u64, ptr as u64, block.size.min(size) as u64)?;
+        self.checked_deallocate(ptr)?;
+
+        Ok(new_ptr)
+    }
+
+    /// Verifies all blocks and returns blocks which weren't freed.
+    /// Leaks are taken from allocator units, so blocks allocated without heap checker
+    /// are reported too (with zero allocation site).
+    pub fn heap_check_report(&mut self) -> Result<Vec<HeapLeak>, MvmError> {
+        self.verify_quarantine()?;
+
+        for (ptr, block) in &self.heap_check.blocks {
+            self.verify_guards(*ptr, block)?;
+        }
+
+        let leaks = self
+            .allocator
+            .units()
+            .filter(|(address, _)| {
+                !self
+                    .heap_check
+                    .quarantine
+                    .iter()
+                    .any(|(_, block)| block.unit == *address)
+            })
+            .map(|(address, size)| {
+                let ptr = address + GUARD_SIZE;
+
+                match self.heap_check.blocks.get(&ptr) {
+                    Some(block) if block.unit == address => HeapLeak {
+                        ptr,
+                        size: block.size,
+                        alloc_site: block.alloc_site,
+                    },
+                    _ => HeapLeak {
+                        ptr: address,
+                        size,
+                        alloc_site: 0,
+                    },
+                }
+            })
+            .collect();
+
+        Ok(leaks)
+    }
+
+    /// Allocator statistics where used bytes are taken by program. With heap checker
+    /// guards and quarantined blocks aren't counted as used (but they don't count as free too).
+    pub fn heap_stats(&self) -> AllocatorStats {
+        let stats = self.allocator.stats();
+
+        if !self.heap_check.enabled {
+            return stats;
+        }
+
+        AllocatorStats {
+            used: self.heap_check.used_size,
+            peak_used: self.heap_check.peak_used_size,
+            ..stats
+        }
+    }
+
+    /// Address of `int` instruction which made current system call
+    fn syscall_site(&self) -> u64 {
+        self.registers[R_INSTRUCTION_POINTER as usize].wrapping_sub(INT_INSTRUCTION_LENGTH)
+    }
+
+    fn quarantined(&self, ptr: usize) -> Option<&(usize, HeapBlock)> {
+        self.heap_check
+            .quarantine
+            .iter()
+            .find(|(block_ptr, _)| *block_ptr == ptr)
+    }
+
+    fn verify_guards(&self, ptr: usize, block: &HeapBlock) -> Result<(), MvmError> {
+        let guards = [("before", block.unit), ("after", ptr + block.size)];
+
+        for (side, address) in guards {
+            let guard = self.memory.get_slice(address as u64, GUARD_SIZE as u64)?;
+
+            if guard.iter().any(|byte| *byte != GUARD_BYTE) {
+                return Err(HeapError::GuardCorrupted {
+                    ptr,
+                    side,
+                    alloc_site: block.alloc_site,
+                }
+                .into());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn verify_quarantine(&self) -> Result<(), MvmError> {
+        for (ptr, block) in &self.heap_check.quarantine {
+            let data = self.memory.get_slice(*ptr as u64, block.size as u64)?;
+
+            if let Some(offset) = data.iter().position(|byte| *byte != FREED_BYTE) {
+                return Err(HeapError::UseAfterFree {
+                    ptr: *ptr,
+                    address: ptr + offset,
+                    alloc_site: block.alloc_site,
+                    free_site: block.free_site,
+                }
+                .into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns oldest quarantined blocks to allocator until quarantine fits to `limit`
+    fn release_quarantine(&mut self, limit: usize) -> Result<(), MvmError> {
+        while self.heap_check.quarantine_size > limit
+            && let Some((_, block)) = self.heap_check.quarantine.pop_front()
+        {
+            self.heap_check.quarantine_size -= block.size;
+            self.allocator.deallocate(block.unit)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Opcode;
+    use super::*;
+
+    fn heap_check_vm() -> Result<VM, MvmError> {
+        let mut vm = VM::new(512, 128)?;
+
+        vm.insert_program(&[Opcode::Halt as u8])?;
+        vm.heap_check.enabled = true;
+
+        Ok(vm)
+    }
+
+    #[test]
+    fn heap_check_guards_test() -> Result<(), MvmError> {
+        let mut vm = heap_check_vm()?;
+
+        let ptr = vm.allocate(10)?;
+
+        assert_eq!(vm.memory.get_u8(ptr as u64 - 1)?, GUARD_BYTE);
+        assert_eq!(vm.memory.get_u8(ptr as u64 + 10)?, GUARD_BYTE);
+
+        // writing one byte past the block
+        vm.memory.set_u8(ptr as u64 + 10, 0)?;
+
+        assert!(matches!(
+            vm.deallocate(ptr),
+            Err(MvmError::HeapError(HeapError::GuardCorrupted {
+                side: "after",
+                ..
+            }))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn heap_check_double_free_test() -> Result<(), MvmError> {
+        let mut vm = heap_check_vm()?;
+
+        vm.set_register(R_INSTRUCTION_POINTER, 100)?;
+        let ptr = vm.allocate(8)?;
+
+        vm.set_register(R_INSTRUCTION_POINTER, 200)?;
+        vm.deallocate(ptr)?;
+
+        assert_eq!(vm.memory.get_slice(ptr as u64, 8)?, &[FREED_BYTE; 8]);
+
+        assert!(matches!(
+            vm.deallocate(ptr),
+            Err(MvmError::HeapError(HeapError::DoubleFree {
+                alloc_site: 91,
+                free_site: 191,
+                ..
+            }))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn heap_check_use_after_free_test() -> Result<(), MvmError> {
+        let mut vm = heap_check_vm()?;
+
+        let ptr = vm.allocate(8)?;
+        vm.deallocate(ptr)?;
+
+        // freed block isn't reused
+        assert_ne!(vm.allocate(8)?, ptr);
+
+        vm.memory.set_u8(ptr as u64 + 4, 1)?;
+
+        assert!(matches!(
+            vm.allocate(8),
+            Err(MvmError::HeapError(HeapError::UseAfterFree { address, .. })) if address == ptr + 4
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn heap_check_realloc_test() -> Result<(), MvmError> {
+        let mut vm = heap_check_vm()?;
+
+        let ptr = vm.allocate(8)?;
+        vm.memory.set_u64(ptr as u64, 123)?;
+
+        let new_ptr = vm.reallocate(ptr, 16)?;
+
+        assert_ne!(new_ptr, ptr);
+        assert_eq!(vm.memory.get_u64(new_ptr as u64)?, 123);
+        assert_eq!(vm.memory.get_u8((new_ptr + 16) as u64)?, GUARD_BYTE);
+
+        Ok(())
+    }
+
+    #[test]
+    fn heap_check_leaks_test() -> Result<(), MvmError> {
+        let mut vm = heap_check_vm()?;
+
+        vm.set_register(R_INSTRUCTION_POINTER, 100)?;
+
+        let freed = vm.allocate(8)?;
+        let leaked = vm.allocate(12)?;
+
+        vm.deallocate(freed)?;
+
+        assert_eq!(
+            vm.heap_check_report()?,
+            vec![HeapLeak {
+                ptr: leaked,
+                size: 12,
+                alloc_site: 91,
+            }]
+        );
+
+        // quarantine is released when memory is needed
+        let big = vm.allocate(200)?;
+        vm.deallocate(big)?;
+        vm.allocate(200)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn heap_check_stats_test() -> Result<(), MvmError> {
+        let mut vm = heap_check_vm()?;
+
+        let first = vm.allocate(10)?;
+        let second = vm.allocate(20)?;
+        vm.deallocate(first)?;
+
+        let stats = vm.heap_stats();
+
+        assert_eq!(stats.used, 20);
+        assert_eq!(stats.peak_used, 30);
+
+        // quarantined block isn't in use
+        vm.deallocate(second)?;
+
+        assert_eq!(vm.heap_stats().used, 0);
+        assert!(vm.allocator.stats().used > 0);
+
+        Ok(())
+    }
+}
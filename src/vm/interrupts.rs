@@ -1,7 +1,37 @@
 use std::io::Read;
 
+use super::filesystem::FsError;
 use super::*;
 
+// Faults Trapping
+// -----------------
+// Program can install handler to this vector (`setvec $int_fault, handler`),
+// then faults will be delivered to it instead of stopping the machine:
+// * R0 - fault kind
+// * R1 - faulting instruction address
+// * R2 - fault details (invalid or protected address / opcode / register index)
+//
+// `iret` continues execution after the faulting instruction. Faults of instruction fetch
+// (jump outside of code, undecodable opcode) can't be resumed: `iret` stops the machine with them.
+// Handler is running until its saved state is popped (`iret`, `fret`) or dropped from the stack.
+pub const INT_FAULT: u8 = 1;
+
+pub const FAULT_DIVISION_BY_ZERO: u64 = 1;
+pub const FAULT_SEGMENTATION: u64 = 2;
+pub const FAULT_INVALID_OPCODE: u64 = 3;
+pub const FAULT_PROTECTION: u64 = 4;
+pub const FAULT_INVALID_REGISTER: u64 = 5;
+// -----------------
+
+/// Fault handler which is currently running
+#[derive(Debug)]
+pub struct FaultFrame {
+    /// Frame pointer of registers state saved before handler call
+    pub frame: u64,
+    /// Fault of instruction fetch, it is returned when handler tries to resume execution
+    pub fatal: Option<MvmError>,
+}
+
 impl VM {
     pub fn init_interrupts(&mut self) {
         // WARNING: Don't forget to add implemented interrupt here!
@@ -9,6 +39,62 @@ impl VM {
         self.interrupt_handlers[0] = Some(Self::handle_int0);
         self.interrupt_handlers[80] = Some(Self::handle_int80);
     }
+
+    /// Delivers fault to program handler, returns error back if it can't be handled.
+    /// Fault of instruction `fetch` has no next instruction, so handler can't resume it.
+    pub fn trap_fault(
+        &mut self,
+        error: MvmError,
+        address: u64,
+        fetch: bool,
+    ) -> Result<(), MvmError> {
+        let (kind, details) = match error {
+            MvmError::DivisionByZero => (FAULT_DIVISION_BY_ZERO, 0),
+            MvmError::SegmentationFault(fault_addr) => (FAULT_SEGMENTATION, fault_addr),
+            MvmError::InvalidOpcode(opcode) => (FAULT_INVALID_OPCODE, opcode as u64),
+            MvmError::ProtectionFault { address, .. } => (FAULT_PROTECTION, address),
+            MvmError::InvalidRegister(index) => (FAULT_INVALID_REGISTER, index),
+            _ => return Err(error),
+        };
+
+        let Some(handler_addr) = self.interrupt_vectors[INT_FAULT as usize] else {
+            return Err(error);
+        };
+
+        // fault inside of fault handler (its saved state is still on the stack) can't be recovered
+        let stack_ptr = self.get_register(R_STACK_POINTER)?;
+
+        if self
+            .fault_frame
+            .as_ref()
+            .is_some_and(|fault| fault.frame <= stack_ptr)
+        {
+            return Err(error);
+        }
+
+        // fetch fault is kept with faulting address in context (it's also saved as return address)
+        let error = if fetch {
+            self.with_context(error, address)
+        } else {
+            error
+        };
+
+        if self.push_state().is_err() {
+            return Err(error);
+        }
+
+        self.fault_frame = Some(FaultFrame {
+            frame: self.get_register(R_FRAME_POINTER)?,
+            fatal: fetch.then_some(error),
+        });
+
+        self.set_register(R0, kind)?;
+        self.set_register(R1, address)?;
+        self.set_register(R2, details)?;
+        self.set_register(R_INSTRUCTION_POINTER, handler_addr)?;
+
+        Ok(())
+    }
 }
 
 impl VM {
@@ -42,45 +128,42 @@ impl VM {
 
             // void read(void* buffer, u64 len)
             1 => {
-                let buf_addr = self.get_register(R0)? as usize;
-                let buf_len = self.get_register(R1)? as usize;
-                let buf = self.memory.get_mut_ptr(buf_addr);
+                let (buf_addr, buf_len) = (self.get_register(R0)?, self.get_register(R1)?);
 
-                if buf_len != 0 {
-                    let slice = unsafe { std::slice::from_raw_parts_mut(buf, buf_len) };
-                    let mut stdin = std::io::stdin().lock();
+                let buffer = self.memory.get_mut_slice(buf_addr, buf_len)?;
 
-                    let bytes_read = stdin.read(slice).map_err(MvmError::IOError)?;
+                if !buffer.is_empty() {
+                    let bytes_read = self.streams.stdin.read(buffer).map_err(MvmError::IOError)?;
 
-                    if bytes_read < buf_len {
-                        unsafe {
-                            std::ptr::write_volatile(slice.get_unchecked_mut(bytes_read), 0);
-                        }
-                    } else {
-                        unsafe {
-                            std::ptr::write_volatile(slice.get_unchecked_mut(buf_len - 1), 0);
-                        }
-                    }
+                    // null-terminating input (last byte is overwritten if buffer is full)
+                    buffer[bytes_read.min(buffer.len() - 1)] = 0;
                 }
             }
 
             // u64 write(i32 output, void* buffer, u64 len)
+            // returns `-1` for unknown output (only stdout and stderr are available)
             2 => {
-                let fd = self.get_register(R0)? as libc::c_int;
-                let len = self.get_register(R2)? as usize;
+                let fd = self.get_register(R0)?;
+                let (buf_addr, buf_len) = (self.get_register(R1)?, self.get_register(R2)?);
+
+                let buffer = self.memory.get_slice(buf_addr, buf_len)?;
 
-                let in_mem_buffer = self.get_register(R1)? as usize;
-                let buffer = self.memory.get_const_ptr(in_mem_buffer) as *const libc::c_void;
+                let written = match self.streams.output(fd) {
+                    Some(output) => output
+                        .write_all(buffer)
+                        .and_then(|_| output.flush())
+                        .map(|_| buf_len)
+                        .unwrap_or(u64::MAX),
+                    None => u64::MAX,
+                };
 
-                self.set_register(R_ACCUMULATOR, unsafe {
-                    libc::write(fd, buffer, len as libc::size_t)
-                } as u64)?;
+                self.set_register(R_ACCUMULATOR, written)?;
             }
 
             // void* alloc(u64 size)
             3 => {
                 let size = self.get_register(R0)? as usize;
-                let allocated = self.allocator.allocate(size)?;
+                let allocated = self.allocate(size)?;
 
                 self.set_register(R_ACCUMULATOR, allocated as u64)?;
             }
@@ -89,7 +172,166 @@ impl VM {
             4 => {
                 let ptr = self.get_register(R0)? as usize;
 
-                self.allocator.deallocate(ptr)?;
+                self.deallocate(ptr)?;
+            }
+
+            // Filesystem calls are working only inside sandbox root directory.
+            // On failure they return negative error code (see `FsError`).
+
+            // i64 open(char* path, u64 path_len, u64 flags)
+            5 => {
+                let path = self
+                    .memory
+                    .get_slice(self.get_register(R0)?, self.get_register(R1)?)?;
+                let result = self.filesystem.open(path, self.get_register(R2)?);
+
+                self.set_fs_result(result)?;
+            }
+
+            // i64 close(u64 fd)
+            6 => {
+                let result = self.filesystem.close(self.get_register(R0)?);
+
+                self.set_fs_result(result)?;
+            }
+
+            // i64 fread(u64 fd, void* buffer, u64 len)
+            7 => {
+                let fd = self.get_register(R0)?;
+                let (buf_addr, buf_len) = (self.get_register(R1)?, self.get_register(R2)?);
+
+                let buffer = self.memory.get_mut_slice(buf_addr, buf_len)?;
+                let result = self.filesystem.read(fd, buffer);
+
+                self.set_fs_result(result)?;
+            }
+
+            // i64 fwrite(u64 fd, void* buffer, u64 len)
+            8 => {
+                let fd = self.get_register(R0)?;
+                let (buf_addr, buf_len) = (self.get_register(R1)?, self.get_register(R2)?);
+
+                let buffer = self.memory.get_slice(buf_addr, buf_len)?;
+                let result = self.filesystem.write(fd, buffer);
+
+                self.set_fs_result(result)?;
+            }
+
+            // i64 seek(u64 fd, i64 offset, u64 whence)
+            9 => {
+                let result = self.filesystem.seek(
+                    self.get_register(R0)?,
+                    self.get_register(R1)? as i64,
+                    self.get_register(R2)?,
+                );
+
+                self.set_fs_result(result)?;
+            }
+
+            // i64 stat(char* path, u64 path_len, u64* statbuf)
+            // statbuf: [u64 size, u64 is_directory, u64 readonly]
+            10 => {
+                let path = self
+                    .memory
+                    .get_slice(self.get_register(R0)?, self.get_register(R1)?)?;
+                let statbuf = self.get_register(R2)?;
+
+                let result = match self.filesystem.stat(path) {
+                    Ok(stat) => {
+                        for (index, value) in stat.into_iter().enumerate() {
+                            self.memory.set_u64(statbuf + index as u64 * 8, value)?;
+                        }
+
+                        Ok(0)
+                    }
+                    Err(err) => Err(err),
+                };
+
+                self.set_fs_result(result)?;
+            }
+
+            // i64 unlink(char* path, u64 path_len)
+            11 => {
+                let path = self
+                    .memory
+                    .get_slice(self.get_register(R0)?, self.get_register(R1)?)?;
+                let result = self.filesystem.unlink(path);
+
+                self.set_fs_result(result)?;
+            }
+
+            // i64 readdir(char* path, u64 path_len, void* buffer, u64 len)
+            12 => {
+                let (path_addr, path_len) = (self.get_register(R0)?, self.get_register(R1)?);
+                let (buf_addr, buf_len) = (self.get_register(R2)?, self.get_register(R3)?);
+
+                // path is copied, because it may overlap with the buffer
+                let path = self.memory.get_slice(path_addr, path_len)?.to_vec();
+                let buffer = self.memory.get_mut_slice(buf_addr, buf_len)?;
+
+                let result = self.filesystem.readdir(&path, buffer);
+
+                self.set_fs_result(result)?;
+            }
+
+            // u64 argc()
+            13 => {
+                self.set_register(R_ACCUMULATOR, self.arguments.len() as u64)?;
+            }
+
+            // char** argv()
+            14 => {
+                self.set_register(R_ACCUMULATOR, self.argv_ptr)?;
+            }
+
+            // char** envp()
+            15 => {
+                self.set_register(R_ACCUMULATOR, self.envp_ptr)?;
+            }
+
+            // void* sbrk(u64 increment)
+            // Grows memory after the stack (only with memory limit set),
+            // returns previous memory end or -1 if memory can't grow.
+            16 => {
+                let increment = self.get_register(R0)?;
+                let previous_break = self.grow_memory(increment).unwrap_or(u64::MAX);
+
+                self.set_register(R_ACCUMULATOR, previous_break)?;
+            }
+
+            // void* realloc(void* ptr, u64 size)
+            17 => {
+                let ptr = self.get_register(R0)? as usize;
+                let size = self.get_register(R1)? as usize;
+                let reallocated = self.reallocate(ptr, size)?;
+
+                self.set_register(R_ACCUMULATOR, reallocated as u64)?;
+            }
+
+            // void* calloc(u64 count, u64 size)
+            18 => {
+                let size = self
+                    .get_register(R0)?
+                    .checked_mul(self.get_register(R1)?)
+                    .ok_or(AllocatorError::OutOfMemory)? as usize;
+                let allocated = self.allocate(size)?;
+
+                self.memory.fill(allocated as u64, 0, size as u64)?;
+                self.set_register(R_ACCUMULATOR, allocated as u64)?;
+            }
+
+            // void mem_stats(u64* stats)
+            // Writes heap usage, peak heap usage, free heap bytes and largest free block size.
+            19 => {
+                let address = self.get_register(R0)?;
+                let stats = self.heap_stats();
+
+                let values = [stats.used, stats.peak_used, stats.free, stats.largest_free];
+
+                for (index, value) in values.into_iter().enumerate() {
+                    self.memory
+                        .set_u64(address + index as u64 * 8, value as u64)?;
+                }
             }
 
             unknown => {
@@ -101,6 +343,10 @@ impl VM {
         self.pop_state()?;
         Ok(())
     }
+
+    fn set_fs_result(&mut self, result: Result<u64, FsError>) -> Result<(), MvmError> {
+        self.set_register(R_ACCUMULATOR, result.unwrap_or_else(FsError::code))
+    }
 }
 
 #[cfg(test)]
@@ -150,4 +396,286 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn syscall_filesystem_without_root_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(256, 128)?;
+
+        vm.memory.set_u8(0, b'a')?;
+
+        vm.set_register(R_SYSTEM_CALL, 5)?;
+        vm.set_register(R0, 0)?;
+        vm.set_register(R1, 1)?;
+        vm.set_register(R2, 0)?;
+
+        vm.push_state()?;
+        vm.handle_int80()?;
+
+        assert_eq!(vm.get_register(R_ACCUMULATOR)?, FsError::NoRoot.code());
+
+        Ok(())
+    }
+
+    #[test]
+    fn syscall_streams_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(256, 128)?;
+        let (streams, stdout, stderr) = Streams::captured("hello");
+
+        vm.streams = streams;
+        vm.insert_program(&[Opcode::Halt as u8])?;
+
+        // read(64, 16)
+        vm.set_register(R_SYSTEM_CALL, 1)?;
+        vm.set_register(R0, 64)?;
+        vm.set_register(R1, 16)?;
+
+        vm.push_state()?;
+        vm.handle_int80()?;
+
+        assert_eq!(vm.memory.get_slice(64, 6)?, b"hello\0");
+
+        // write(fd, 64, 5)
+        for fd in [1, 2, 7] {
+            vm.set_register(R_SYSTEM_CALL, 2)?;
+            vm.set_register(R0, fd)?;
+            vm.set_register(R1, 64)?;
+            vm.set_register(R2, 5)?;
+
+            vm.push_state()?;
+            vm.handle_int80()?;
+        }
+
+        // unknown output
+        assert_eq!(vm.get_register(R_ACCUMULATOR)?, u64::MAX);
+
+        assert_eq!(stdout.contents(), b"hello");
+        assert_eq!(stderr.contents(), b"hello");
+
+        // read(250, 16) - buffer is out of memory
+        vm.set_register(R_SYSTEM_CALL, 1)?;
+        vm.set_register(R0, 250)?;
+        vm.set_register(R1, 16)?;
+
+        vm.push_state()?;
+        assert!(matches!(
+            vm.handle_int80(),
+            Err(MvmError::SegmentationFault(250))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn syscall_sbrk_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(256, 128)?;
+
+        vm.insert_program(&[Opcode::Halt as u8])?;
+
+        // memory is fixed by default
+        vm.set_register(R_SYSTEM_CALL, 16)?;
+        vm.set_register(R0, 64)?;
+
+        vm.push_state()?;
+        vm.handle_int80()?;
+
+        assert_eq!(vm.get_register(R_ACCUMULATOR)?, u64::MAX);
+        assert_eq!(vm.memory.len(), 256);
+
+        vm.memory_limit = Some(512);
+
+        vm.push_state()?;
+        vm.handle_int80()?;
+
+        assert_eq!(vm.get_register(R_ACCUMULATOR)?, 256);
+        assert_eq!(vm.memory.len(), 256 + 64);
+
+        vm.memory.set_u64(256 + 56, 123)?;
+
+        // ceiling can't be exceeded
+        vm.set_register(R0, 512)?;
+
+        vm.push_state()?;
+        vm.handle_int80()?;
+
+        assert_eq!(vm.get_register(R_ACCUMULATOR)?, u64::MAX);
+        assert_eq!(vm.memory.len(), 256 + 64);
+
+        Ok(())
+    }
+
+    #[test]
+    fn syscall_alloc_growable_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(256, 128)?;
+
+        vm.insert_program(&[Opcode::Halt as u8])?;
+
+        vm.set_register(R_SYSTEM_CALL, 3)?;
+        vm.set_register(R0, 1024)?;
+
+        vm.push_state()?;
+        assert!(matches!(
+            vm.handle_int80(),
+            Err(MvmError::AllocatorError(AllocatorError::OutOfMemory))
+        ));
+        vm.pop_state()?;
+
+        vm.memory_limit = Some(256 + 4096);
+
+        vm.push_state()?;
+        vm.handle_int80()?;
+
+        // heap is placed after the stack, which keeps its bounds
+        let ptr = vm.get_register(R_ACCUMULATOR)?;
+
+        assert_eq!(ptr, 256);
+        assert_eq!(vm.memory.len(), 256 + 4096);
+        assert_eq!(vm.stack_end, 256);
+
+        vm.memory.set_u64(ptr + 1016, 123)?;
+
+        // next allocation exceeds the ceiling
+        vm.set_register(R0, 4096)?;
+
+        vm.push_state()?;
+        assert!(vm.handle_int80().is_err());
+        vm.pop_state()?;
+
+        // pages count for huge size overflows
+        vm.set_register(R0, u64::MAX - 99)?;
+
+        vm.push_state()?;
+        assert!(matches!(
+            vm.handle_int80(),
+            Err(MvmError::AllocatorError(AllocatorError::OutOfMemory))
+        ));
+        assert_eq!(vm.memory.len(), 256 + 4096);
+
+        Ok(())
+    }
+
+    #[test]
+    fn syscall_realloc_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(256, 128)?;
+
+        vm.insert_program(&[Opcode::Halt as u8])?;
+
+        vm.set_register(R_SYSTEM_CALL, 3)?;
+        vm.set_register(R0, 8)?;
+
+        vm.push_state()?;
+        vm.handle_int80()?;
+
+        let first = vm.get_register(R_ACCUMULATOR)?;
+
+        vm.push_state()?;
+        vm.handle_int80()?;
+
+        vm.memory.set_u64(first, 0xDEADBEEF)?;
+
+        // next unit is used, so data is moved
+        vm.set_register(R_SYSTEM_CALL, 17)?;
+        vm.set_register(R0, first)?;
+        vm.set_register(R1, 32)?;
+
+        vm.push_state()?;
+        vm.handle_int80()?;
+
+        let moved = vm.get_register(R_ACCUMULATOR)?;
+
+        assert_ne!(moved, first);
+        assert_eq!(vm.memory.get_u64(moved)?, 0xDEADBEEF);
+
+        // next unit is free, so it grows in place
+        vm.set_register(R0, moved)?;
+        vm.set_register(R1, 64)?;
+
+        vm.push_state()?;
+        vm.handle_int80()?;
+
+        assert_eq!(vm.get_register(R_ACCUMULATOR)?, moved);
+
+        // null pointer is allocated
+        vm.set_register(R0, 0)?;
+        vm.set_register(R1, 8)?;
+
+        vm.push_state()?;
+        vm.handle_int80()?;
+
+        assert_eq!(vm.get_register(R_ACCUMULATOR)?, first);
+
+        Ok(())
+    }
+
+    #[test]
+    fn syscall_calloc_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(256, 128)?;
+
+        vm.insert_program(&[Opcode::Halt as u8])?;
+
+        vm.set_register(R_SYSTEM_CALL, 3)?;
+        vm.set_register(R0, 16)?;
+
+        vm.push_state()?;
+        vm.handle_int80()?;
+
+        let ptr = vm.get_register(R_ACCUMULATOR)?;
+        vm.memory.fill(ptr, 0xAA, 16)?;
+
+        vm.set_register(R_SYSTEM_CALL, 4)?;
+        vm.set_register(R0, ptr)?;
+
+        vm.push_state()?;
+        vm.handle_int80()?;
+
+        // freed unit is reused and zeroed
+        vm.set_register(R_SYSTEM_CALL, 18)?;
+        vm.set_register(R0, 2)?;
+        vm.set_register(R1, 8)?;
+
+        vm.push_state()?;
+        vm.handle_int80()?;
+
+        assert_eq!(vm.get_register(R_ACCUMULATOR)?, ptr);
+        assert_eq!(vm.memory.get_slice(ptr, 16)?, &[0; 16]);
+
+        vm.set_register(R0, u64::MAX)?;
+
+        vm.push_state()?;
+        assert!(matches!(
+            vm.handle_int80(),
+            Err(MvmError::AllocatorError(AllocatorError::OutOfMemory))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn syscall_mem_stats_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(256, 128)?;
+
+        vm.insert_program(&[Opcode::Halt as u8])?;
+
+        vm.set_register(R_SYSTEM_CALL, 3)?;
+        vm.set_register(R0, 16)?;
+
+        vm.push_state()?;
+        vm.handle_int80()?;
+
+        let stats_ptr = vm.get_register(R_ACCUMULATOR)?;
+
+        vm.set_register(R_SYSTEM_CALL, 19)?;
+        vm.set_register(R0, stats_ptr)?;
+
+        vm.push_state()?;
+        vm.handle_int80()?;
+
+        let heap_size = vm.allocator.stats().used + vm.allocator.stats().free;
+
+        assert_eq!(vm.memory.get_u64(stats_ptr)?, 16);
+        assert_eq!(vm.memory.get_u64(stats_ptr + 8)?, 16);
+        assert_eq!(vm.memory.get_u64(stats_ptr + 16)?, heap_size as u64 - 16);
+        assert_eq!(vm.memory.get_u64(stats_ptr + 24)?, heap_size as u64 - 16);
+
+        Ok(())
+    }
 }
@@ -1,15 +1,38 @@
 use super::error::MvmError;
 
 #[repr(u8)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Opcode {
     // ---| System |---
     Halt = 0xf0,      // halt
-    Return = 0xf1,    // ret
-    Call = 0x27,      // call label
     Interrupt = 0xf2, // int $u8
     Debug = 0x41,     // dbg %reg
 
+    // machine is stopped with message from `ascii` string label (null-terminated)
+    Trap = 0x66,   // trap label
+    Assert = 0x67, // assert %reg, label (traps if register is zero)
+
+    // Calling convention:
+    // * arguments - r0 .. r5 (sequentially)
+    // * return value - accumulator (r0 .. r1 for additional values)
+    // * caller-saved - r0 .. r5, call, accumulator
+    // * callee-saved - r6 .. r8 (must be restored before `ret`)
+    //
+    // `call` pushes only return address and frame pointer, `ret` drops the frame and restores them.
+    // `fcall` saves all registers (except accumulator) which are restored by `fret`.
+    Call = 0x27,       // call label
+    Return = 0xf1,     // ret
+    FullCall = 0x43,   // fcall label
+    FullReturn = 0xf4, // fret
+
+    // indirect calls, address is taken from register
+    CallReg = 0x44,     // call %reg
+    FullCallReg = 0x45, // fcall %reg
+
+    // user-defined interrupt handlers, `iret` restores all registers including accumulator
+    SetVector = 0x42,       // setvec $u8, label
+    InterruptReturn = 0xf3, // iret
+
     // ---| Sections |---
     DataSection = 0x01, // section .data
     TextSection = 0x02, // section .text
@@ -20,6 +43,7 @@ pub enum Opcode {
     Mov32 = 0x05,  // mov %dest, $u32
     Mov64 = 0x06,  // mov %dest, $u64
     MovR2R = 0x07, // mov %dest, %src
+    Lea = 0x49,    // lea %dest, label (loads label address, not value)
 
     Load8 = 0x2a,  // load8 %dest, %addr_register
     Load16 = 0x2b, // load16 %dest, %addr_register
@@ -31,6 +55,33 @@ pub enum Opcode {
     Store32 = 0x29, // store32 %addr_register, %src
     Store64 = 0x40, // store64 %addr_register, %src
 
+    // base + displacement addressing, displacement is encoded inline as u64 (wrapping)
+    LoadOffset8 = 0x4a,  // load8 %dest, [%base + $disp]
+    LoadOffset16 = 0x4b, // load16 %dest, [%base + $disp]
+    LoadOffset32 = 0x4c, // load32 %dest, [%base + $disp]
+    LoadOffset64 = 0x4d, // load64 %dest, [%base + $disp]
+
+    StoreOffset8 = 0x4e,  // store8 [%base + $disp], %src
+    StoreOffset16 = 0x4f, // store16 [%base + $disp], %src
+    StoreOffset32 = 0x50, // store32 [%base + $disp], %src
+    StoreOffset64 = 0x51, // store64 [%base + $disp], %src
+
+    // base + index * scale addressing, scale is encoded inline as u8 (1, 2, 4 or 8)
+    LoadIndexed8 = 0x52,  // load8 %dest, [%base + %index * $scale]
+    LoadIndexed16 = 0x53, // load16 %dest, [%base + %index * $scale]
+    LoadIndexed32 = 0x54, // load32 %dest, [%base + %index * $scale]
+    LoadIndexed64 = 0x55, // load64 %dest, [%base + %index * $scale]
+
+    StoreIndexed8 = 0x56,  // store8 [%base + %index * $scale], %src
+    StoreIndexed16 = 0x57, // store16 [%base + %index * $scale], %src
+    StoreIndexed32 = 0x58, // store32 [%base + %index * $scale], %src
+    StoreIndexed64 = 0x59, // store64 [%base + %index * $scale], %src
+
+    // bulk memory operations, ranges are taken from registers
+    MemCopy = 0x63,    // memcpy %dest, %src, %length (ranges may overlap)
+    MemSet = 0x64,     // memset %dest, %value, %length (lowest byte of value)
+    MemCompare = 0x65, // memcmp %lhs, %rhs, %length (result goes to accumulator like `cmp`)
+
     // push instruction appends value from register to stack and places
     // offset (related to frame pointer) to %src.
     Push8 = 0x30,  // push8 %src
@@ -55,7 +106,21 @@ pub enum Opcode {
     Peek32 = 0x3e, // peek32 %dest, $u16
     Peek64 = 0x3f, // peek64 %dest, $u16
 
-    // TODO: Add stack & frame editing instructions
+    // setframe instruction copies value from source register to [stack frame + offset]
+    SetFrame8 = 0x5a,  // setframe8 $u16, %src
+    SetFrame16 = 0x5b, // setframe16 $u16, %src
+    SetFrame32 = 0x5c, // setframe32 $u16, %src
+    SetFrame64 = 0x5d, // setframe64 $u16, %src
+
+    // poke instruction copies value from source register to [current stack value - offset]
+    Poke8 = 0x5e,  // poke8 $u16, %src
+    Poke16 = 0x5f, // poke16 $u16, %src
+    Poke32 = 0x60, // poke32 $u16, %src
+    Poke64 = 0x61, // poke64 $u16, %src
+
+    // enter reserves space for locals in current frame, leave releases all of it
+    Enter = 0x62, // enter $u16
+    Leave = 0xf5, // leave
 
     // ---| Arithmetics |---
     Add8 = 0x08,   // add %dest, $u8
@@ -100,6 +165,13 @@ pub enum Opcode {
     Jnz = 0x1f, // jnz label
     Je = 0x21,  // je $u64 label
     Jne = 0x22, // jne $u64 label
+
+    // indirect jumps, address is taken from register
+    JmpReg = 0x2e, // jmp %reg
+    JzReg = 0x2f,  // jz %reg
+    JnzReg = 0x46, // jnz %reg
+    JeReg = 0x47,  // je $u64 %reg
+    JneReg = 0x48, // jne $u64 %reg
 }
 
 impl TryFrom<u8> for Opcode {
@@ -108,10 +180,18 @@ impl TryFrom<u8> for Opcode {
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         match value {
             0xf0 => Ok(Opcode::Halt),
-            0xf1 => Ok(Opcode::Return),
-            0x27 => Ok(Opcode::Call),
             0xf2 => Ok(Opcode::Interrupt),
             0x41 => Ok(Opcode::Debug),
+            0x66 => Ok(Opcode::Trap),
+            0x67 => Ok(Opcode::Assert),
+            0x27 => Ok(Opcode::Call),
+            0xf1 => Ok(Opcode::Return),
+            0x43 => Ok(Opcode::FullCall),
+            0xf4 => Ok(Opcode::FullReturn),
+            0x44 => Ok(Opcode::CallReg),
+            0x45 => Ok(Opcode::FullCallReg),
+            0x42 => Ok(Opcode::SetVector),
+            0xf3 => Ok(Opcode::InterruptReturn),
 
             0x01 => Ok(Opcode::DataSection),
             0x02 => Ok(Opcode::TextSection),
@@ -121,6 +201,7 @@ impl TryFrom<u8> for Opcode {
             0x05 => Ok(Opcode::Mov32),
             0x06 => Ok(Opcode::Mov64),
             0x07 => Ok(Opcode::MovR2R),
+            0x49 => Ok(Opcode::Lea),
 
             0x2a => Ok(Opcode::Load8),
             0x2b => Ok(Opcode::Load16),
@@ -132,6 +213,30 @@ impl TryFrom<u8> for Opcode {
             0x29 => Ok(Opcode::Store32),
             0x40 => Ok(Opcode::Store64),
 
+            0x4a => Ok(Opcode::LoadOffset8),
+            0x4b => Ok(Opcode::LoadOffset16),
+            0x4c => Ok(Opcode::LoadOffset32),
+            0x4d => Ok(Opcode::LoadOffset64),
+
+            0x4e => Ok(Opcode::StoreOffset8),
+            0x4f => Ok(Opcode::StoreOffset16),
+            0x50 => Ok(Opcode::StoreOffset32),
+            0x51 => Ok(Opcode::StoreOffset64),
+
+            0x52 => Ok(Opcode::LoadIndexed8),
+            0x53 => Ok(Opcode::LoadIndexed16),
+            0x54 => Ok(Opcode::LoadIndexed32),
+            0x55 => Ok(Opcode::LoadIndexed64),
+
+            0x56 => Ok(Opcode::StoreIndexed8),
+            0x57 => Ok(Opcode::StoreIndexed16),
+            0x58 => Ok(Opcode::StoreIndexed32),
+            0x59 => Ok(Opcode::StoreIndexed64),
+
+            0x63 => Ok(Opcode::MemCopy),
+            0x64 => Ok(Opcode::MemSet),
+            0x65 => Ok(Opcode::MemCompare),
+
             0x30 => Ok(Opcode::Push8),
             0x31 => Ok(Opcode::Push16),
             0x32 => Ok(Opcode::Push32),
@@ -152,6 +257,19 @@ impl TryFrom<u8> for Opcode {
             0x3e => Ok(Opcode::Peek32),
             0x3f => Ok(Opcode::Peek64),
 
+            0x5a => Ok(Opcode::SetFrame8),
+            0x5b => Ok(Opcode::SetFrame16),
+            0x5c => Ok(Opcode::SetFrame32),
+            0x5d => Ok(Opcode::SetFrame64),
+
+            0x5e => Ok(Opcode::Poke8),
+            0x5f => Ok(Opcode::Poke16),
+            0x60 => Ok(Opcode::Poke32),
+            0x61 => Ok(Opcode::Poke64),
+
+            0x62 => Ok(Opcode::Enter),
+            0xf5 => Ok(Opcode::Leave),
+
             0x08 => Ok(Opcode::Add8),
             0x09 => Ok(Opcode::Add16),
             0x0a => Ok(Opcode::Add32),
@@ -189,7 +307,138 @@ impl TryFrom<u8> for Opcode {
             0x21 => Ok(Opcode::Je),
             0x22 => Ok(Opcode::Jne),
 
+            0x2e => Ok(Opcode::JmpReg),
+            0x2f => Ok(Opcode::JzReg),
+            0x46 => Ok(Opcode::JnzReg),
+            0x47 => Ok(Opcode::JeReg),
+            0x48 => Ok(Opcode::JneReg),
+
             _ => Err(MvmError::InvalidOpcode(value)),
         }
     }
 }
+
+impl Opcode {
+    /// Widths of encoded operands in bytes: register or scale - 1, constant address, label or
+    /// displacement - 8.
+    pub fn operands(&self) -> &'static [u8] {
+        match self {
+            Opcode::Halt
+            | Opcode::Return
+            | Opcode::FullReturn
+            | Opcode::InterruptReturn
+            | Opcode::Leave
+            | Opcode::DataSection
+            | Opcode::TextSection => &[],
+
+            Opcode::Call
+            | Opcode::FullCall
+            | Opcode::Enter
+            | Opcode::Interrupt
+            | Opcode::Trap
+            | Opcode::Jmp
+            | Opcode::Jz
+            | Opcode::Jnz => &[8],
+
+            Opcode::SetVector | Opcode::Je | Opcode::Jne => &[8, 8],
+
+            Opcode::JeReg
+            | Opcode::JneReg
+            | Opcode::SetFrame8
+            | Opcode::SetFrame16
+            | Opcode::SetFrame32
+            | Opcode::SetFrame64
+            | Opcode::Poke8
+            | Opcode::Poke16
+            | Opcode::Poke32
+            | Opcode::Poke64 => &[8, 1],
+
+            Opcode::Debug
+            | Opcode::CallReg
+            | Opcode::FullCallReg
+            | Opcode::JmpReg
+            | Opcode::JzReg
+            | Opcode::JnzReg
+            | Opcode::Push8
+            | Opcode::Push16
+            | Opcode::Push32
+            | Opcode::Push64
+            | Opcode::Pop8
+            | Opcode::Pop16
+            | Opcode::Pop32
+            | Opcode::Pop64 => &[1],
+
+            Opcode::MovR2R
+            | Opcode::Load8
+            | Opcode::Load16
+            | Opcode::Load32
+            | Opcode::Load64
+            | Opcode::Store8
+            | Opcode::Store16
+            | Opcode::Store32
+            | Opcode::Store64
+            | Opcode::AddR2R
+            | Opcode::XAdd
+            | Opcode::SubR2R
+            | Opcode::MulR2R
+            | Opcode::DivR2R
+            | Opcode::CmpR2R => &[1, 1],
+
+            Opcode::Mov8
+            | Opcode::Mov16
+            | Opcode::Mov32
+            | Opcode::Mov64
+            | Opcode::Lea
+            | Opcode::Assert
+            | Opcode::Frame8
+            | Opcode::Frame16
+            | Opcode::Frame32
+            | Opcode::Frame64
+            | Opcode::Peek8
+            | Opcode::Peek16
+            | Opcode::Peek32
+            | Opcode::Peek64
+            | Opcode::Add8
+            | Opcode::Add16
+            | Opcode::Add32
+            | Opcode::Add64
+            | Opcode::Sub8
+            | Opcode::Sub16
+            | Opcode::Sub32
+            | Opcode::Sub64
+            | Opcode::Mul8
+            | Opcode::Mul16
+            | Opcode::Mul32
+            | Opcode::Mul64
+            | Opcode::Div8
+            | Opcode::Div16
+            | Opcode::Div32
+            | Opcode::Div64
+            | Opcode::Cmp8
+            | Opcode::Cmp16
+            | Opcode::Cmp32
+            | Opcode::Cmp64 => &[1, 8],
+
+            Opcode::MemCopy | Opcode::MemSet | Opcode::MemCompare => &[1, 1, 1],
+
+            Opcode::LoadOffset8
+            | Opcode::LoadOffset16
+            | Opcode::LoadOffset32
+            | Opcode::LoadOffset64 => &[1, 1, 8],
+
+            Opcode::StoreOffset8
+            | Opcode::StoreOffset16
+            | Opcode::StoreOffset32
+            | Opcode::StoreOffset64 => &[1, 8, 1],
+
+            Opcode::LoadIndexed8
+            | Opcode::LoadIndexed16
+            | Opcode::LoadIndexed32
+            | Opcode::LoadIndexed64
+            | Opcode::StoreIndexed8
+            | Opcode::StoreIndexed16
+            | Opcode::StoreIndexed32
+            | Opcode::StoreIndexed64 => &[1, 1, 1, 1],
+        }
+    }
+}
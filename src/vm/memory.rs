@@ -1,13 +1,94 @@
+use std::cmp::Ordering;
+
 use super::error::MvmError;
 
+/// Memory range with restricted access, `end` is exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryRegion {
+    pub name: &'static str,
+    pub start: u64,
+    pub end: u64,
+    pub writable: bool,
+    pub executable: bool,
+}
+
+impl MemoryRegion {
+    pub fn new(name: &'static str, start: u64, end: u64, writable: bool, executable: bool) -> Self {
+        Self {
+            name,
+            start,
+            end,
+            writable,
+            executable,
+        }
+    }
+
+    pub fn overlaps(&self, address: u64, length: u64) -> bool {
+        address < self.end && address.saturating_add(length) > self.start
+    }
+}
+
 pub struct MemoryBuffer {
     pub inner: Vec<u8>,
+
+    /// Protected regions, memory outside of them is writable and executable
+    pub regions: Vec<MemoryRegion>,
+
+    /// Range of decoded code (`end` is exclusive), writes to it set `code_modified` flag
+    pub code: (u64, u64),
+    pub code_modified: bool,
 }
 
 impl MemoryBuffer {
     pub fn new(size: usize) -> Self {
         Self {
             inner: vec![0; size],
+            regions: Vec::new(),
+            code: (0, 0),
+            code_modified: false,
+        }
+    }
+
+    /// Checks that `length` bytes starting from `address` are inside of memory
+    fn check_bounds(&self, address: u64, length: u64) -> Result<(), MvmError> {
+        match address.checked_add(length) {
+            Some(end) if end <= self.len() as u64 => Ok(()),
+            _ => Err(MvmError::SegmentationFault(address)),
+        }
+    }
+
+    pub fn check_write(&mut self, address: u64, length: u64) -> Result<(), MvmError> {
+        if let Some(region) = self
+            .regions
+            .iter()
+            .find(|region| !region.writable && region.overlaps(address, length))
+        {
+            return Err(MvmError::ProtectionFault {
+                address,
+                region: region.name,
+                access: "write",
+            });
+        }
+
+        if address < self.code.1 && address.saturating_add(length) > self.code.0 {
+            self.code_modified = true;
+        }
+
+        Ok(())
+    }
+
+    pub fn check_execute(&self, address: u64) -> Result<(), MvmError> {
+        match self
+            .regions
+            .iter()
+            .find(|region| !region.executable && region.overlaps(address, 1))
+        {
+            Some(region) => Err(MvmError::ProtectionFault {
+                address,
+                region: region.name,
+                access: "execute",
+            }),
+            None => Ok(()),
         }
     }
 
@@ -15,20 +96,55 @@ impl MemoryBuffer {
         self.inner.len()
     }
 
-    pub fn get_const_ptr(&self, address: usize) -> *const u8 {
-        if address >= self.len() {
-            return std::ptr::null();
-        };
+    /// Appends `length` zeroed bytes to the end of memory
+    pub fn grow(&mut self, length: usize) {
+        self.inner.resize(self.inner.len() + length, 0);
+    }
+
+    pub fn get_slice(&self, address: u64, length: u64) -> Result<&[u8], MvmError> {
+        let end = address
+            .checked_add(length)
+            .ok_or(MvmError::SegmentationFault(address))?;
 
-        unsafe { self.inner.as_ptr().add(address) }
+        self.inner
+            .get(address as usize..end as usize)
+            .ok_or(MvmError::SegmentationFault(address))
     }
 
-    pub fn get_mut_ptr(&mut self, address: usize) -> *mut u8 {
-        if address >= self.len() {
-            return std::ptr::null_mut();
-        };
+    pub fn get_mut_slice(&mut self, address: u64, length: u64) -> Result<&mut [u8], MvmError> {
+        self.check_write(address, length)?;
 
-        unsafe { self.inner.as_mut_ptr().add(address) }
+        let end = address
+            .checked_add(length)
+            .ok_or(MvmError::SegmentationFault(address))?;
+
+        self.inner
+            .get_mut(address as usize..end as usize)
+            .ok_or(MvmError::SegmentationFault(address))
+    }
+
+    /// Copies `length` bytes from `src` to `dest`, ranges may overlap (like `memmove`).
+    pub fn copy(&mut self, dest: u64, src: u64, length: u64) -> Result<(), MvmError> {
+        self.get_slice(src, length)?;
+        self.get_mut_slice(dest, length)?;
+
+        self.inner
+            .copy_within(src as usize..(src + length) as usize, dest as usize);
+
+        Ok(())
+    }
+
+    pub fn fill(&mut self, dest: u64, value: u8, length: u64) -> Result<(), MvmError> {
+        self.get_mut_slice(dest, length)?.fill(value);
+
+        Ok(())
+    }
+
+    /// Compares two ranges of `length` bytes lexicographically.
+    pub fn compare(&self, lhs: u64, rhs: u64, length: u64) -> Result<Ordering, MvmError> {
+        Ok(self
+            .get_slice(lhs, length)?
+            .cmp(self.get_slice(rhs, length)?))
     }
 }
 
@@ -38,9 +154,7 @@ impl MemoryBuffer {
     pub fn get_u8(&self, address: u64) -> Result<u8, MvmError> {
         const BYTES_LENGTH: u64 = 1;
 
-        if (address + BYTES_LENGTH - 1) as usize > self.len() - 1 {
-            return Err(MvmError::SegmentationFault(address));
-        }
+        self.check_bounds(address, BYTES_LENGTH)?;
 
         Ok(self.inner[address as usize])
     }
@@ -48,9 +162,9 @@ impl MemoryBuffer {
     pub fn set_u8(&mut self, address: u64, value: u8) -> Result<(), MvmError> {
         const BYTES_LENGTH: u64 = 1;
 
-        if (address + BYTES_LENGTH - 1) as usize > self.len() - 1 {
-            return Err(MvmError::SegmentationFault(address));
-        }
+        self.check_bounds(address, BYTES_LENGTH)?;
+
+        self.check_write(address, BYTES_LENGTH)?;
 
         self.inner[address as usize] = value;
 
@@ -62,9 +176,7 @@ impl MemoryBuffer {
     pub fn get_u16(&self, address: u64) -> Result<u16, MvmError> {
         const BYTES_LENGTH: u64 = 2;
 
-        if (address + BYTES_LENGTH - 1) as usize > self.len() - 1 {
-            return Err(MvmError::SegmentationFault(address));
-        }
+        self.check_bounds(address, BYTES_LENGTH)?;
 
         Ok(u16::from_be_bytes([
             self.inner[address as usize],
@@ -73,11 +185,11 @@ impl MemoryBuffer {
     }
 
     pub fn set_u16(&mut self, address: u64, value: u16) -> Result<(), MvmError> {
-        const BYTES_LENGTH: u64 = 1;
+        const BYTES_LENGTH: u64 = 2;
 
-        if (address + BYTES_LENGTH - 1) as usize > self.len() - 1 {
-            return Err(MvmError::SegmentationFault(address));
-        }
+        self.check_bounds(address, BYTES_LENGTH)?;
+
+        self.check_write(address, BYTES_LENGTH)?;
 
         let bytes = value.to_be_bytes();
 
@@ -94,9 +206,7 @@ impl MemoryBuffer {
     pub fn get_u32(&self, address: u64) -> Result<u32, MvmError> {
         const BYTES_LENGTH: u64 = 4;
 
-        if (address + BYTES_LENGTH - 1) as usize > self.len() - 1 {
-            return Err(MvmError::SegmentationFault(address));
-        }
+        self.check_bounds(address, BYTES_LENGTH)?;
 
         Ok(u32::from_be_bytes([
             self.inner[address as usize],
@@ -107,11 +217,11 @@ impl MemoryBuffer {
     }
 
     pub fn set_u32(&mut self, address: u64, value: u32) -> Result<(), MvmError> {
-        const BYTES_LENGTH: u64 = 1;
+        const BYTES_LENGTH: u64 = 4;
 
-        if (address + BYTES_LENGTH - 1) as usize > self.len() - 1 {
-            return Err(MvmError::SegmentationFault(address));
-        }
+        self.check_bounds(address, BYTES_LENGTH)?;
+
+        self.check_write(address, BYTES_LENGTH)?;
 
         let bytes = value.to_be_bytes();
 
@@ -130,9 +240,7 @@ impl MemoryBuffer {
     pub fn get_u64(&self, address: u64) -> Result<u64, MvmError> {
         const BYTES_LENGTH: u64 = 8;
 
-        if (address + BYTES_LENGTH - 1) as usize > self.len() - 1 {
-            return Err(MvmError::SegmentationFault(address));
-        }
+        self.check_bounds(address, BYTES_LENGTH)?;
 
         Ok(u64::from_be_bytes([
             self.inner[address as usize],
@@ -147,11 +255,11 @@ impl MemoryBuffer {
     }
 
     pub fn set_u64(&mut self, address: u64, value: u64) -> Result<(), MvmError> {
-        const BYTES_LENGTH: u64 = 1;
+        const BYTES_LENGTH: u64 = 8;
 
-        if (address + BYTES_LENGTH - 1) as usize > self.len() - 1 {
-            return Err(MvmError::SegmentationFault(address));
-        }
+        self.check_bounds(address, BYTES_LENGTH)?;
+
+        self.check_write(address, BYTES_LENGTH)?;
 
         let bytes = value.to_be_bytes();
 
@@ -195,6 +303,79 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn memory_bounds_test() {
+        let mut memory = MemoryBuffer::new(8);
+
+        assert!(memory.get_u8(8).is_err());
+        assert!(memory.get_u64(1).is_err());
+        assert!(memory.get_u64(u64::MAX).is_err());
+        assert!(memory.set_u16(u64::MAX, 1).is_err());
+
+        let mut empty = MemoryBuffer::new(0);
+
+        assert!(empty.get_u8(0).is_err());
+        assert!(empty.set_u32(0, 1).is_err());
+    }
+
+    #[test]
+    fn memory_slice_test() -> Result<(), MvmError> {
+        let mut memory = MemoryBuffer::new(8);
+
+        memory.get_mut_slice(2, 3)?.copy_from_slice(&[1, 2, 3]);
+        assert_eq!(memory.get_slice(1, 5)?, &[0, 1, 2, 3, 0]);
+
+        assert!(memory.get_slice(4, 5).is_err());
+        assert!(memory.get_mut_slice(u64::MAX, 2).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn memory_bulk_operations_test() -> Result<(), MvmError> {
+        let mut memory = MemoryBuffer::new(16);
+
+        memory.get_mut_slice(0, 4)?.copy_from_slice(&[1, 2, 3, 4]);
+
+        // overlapping forward and backward copies
+        memory.copy(2, 0, 4)?;
+        assert_eq!(memory.get_slice(0, 6)?, &[1, 2, 1, 2, 3, 4]);
+
+        memory.copy(0, 2, 4)?;
+        assert_eq!(memory.get_slice(0, 6)?, &[1, 2, 3, 4, 3, 4]);
+
+        memory.fill(8, 0xAA, 8)?;
+        assert_eq!(memory.get_slice(8, 8)?, &[0xAA; 8]);
+
+        assert_eq!(memory.compare(0, 2, 2)?, Ordering::Less);
+        assert_eq!(memory.compare(2, 4, 2)?, Ordering::Equal);
+        assert_eq!(memory.compare(8, 0, 4)?, Ordering::Greater);
+
+        // zero length operations are always valid inside memory
+        memory.copy(16, 0, 0)?;
+        assert_eq!(memory.compare(0, 8, 0)?, Ordering::Equal);
+
+        assert!(memory.copy(12, 0, 8).is_err());
+        assert!(memory.copy(0, 12, 8).is_err());
+        assert!(memory.fill(u64::MAX, 0, 2).is_err());
+        assert!(memory.compare(0, 10, 8).is_err());
+
+        memory
+            .regions
+            .push(MemoryRegion::new("constants", 12, 16, false, false));
+
+        assert!(matches!(
+            memory.copy(10, 0, 4),
+            Err(MvmError::ProtectionFault {
+                region: "constants",
+                ..
+            })
+        ));
+        assert!(memory.fill(12, 0, 1).is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn no_overwrite_memory_test() -> Result<(), MvmError> {
         let mut memory = MemoryBuffer::new(32);
@@ -213,4 +394,44 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn memory_protection_test() -> Result<(), MvmError> {
+        let mut memory = MemoryBuffer::new(32);
+
+        memory
+            .regions
+            .push(MemoryRegion::new("text", 8, 16, false, true));
+        memory
+            .regions
+            .push(MemoryRegion::new("data", 16, 24, true, false));
+
+        memory.set_u64(0, 123)?;
+        memory.set_u64(16, 123)?;
+
+        assert!(matches!(
+            memory.set_u8(8, 1),
+            Err(MvmError::ProtectionFault {
+                address: 8,
+                region: "text",
+                ..
+            })
+        ));
+        assert!(memory.set_u16(7, 1).is_err());
+        assert!(memory.get_mut_slice(4, 8).is_err());
+
+        assert!(memory.check_execute(15).is_ok());
+        assert!(memory.check_execute(16).is_err());
+
+        memory.code = (24, 32);
+        memory.set_u8(23, 1)?;
+
+        assert!(!memory.code_modified);
+
+        memory.set_u16(23, 1)?;
+
+        assert!(memory.code_modified);
+
+        Ok(())
+    }
 }
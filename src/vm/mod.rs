@@ -1,14 +1,32 @@
-use allocator::MvmAllocator;
-use error::MvmError;
+use allocator::{AllocatorError, MvmAllocator};
+use coverage::Coverage;
+pub use debuginfo::DebugInfo;
+use decoder::{DecodeCache, Instruction};
+pub use error::{ErrorContext, MvmError};
+use filesystem::MvmFilesystem;
+use heapcheck::HeapChecker;
+use interrupts::FaultFrame;
 pub use isa::Opcode;
-use memory::MemoryBuffer;
+use memory::{MemoryBuffer, MemoryRegion};
+use profiler::Profiler;
+pub use profiler::ROOT_FRAME;
+pub use streams::{SharedBuffer, Streams};
+pub use verifier::verify;
 
 mod allocator;
+mod coverage;
+mod debuginfo;
+mod decoder;
 mod error;
 mod execution;
+mod filesystem;
+mod heapcheck;
 mod interrupts;
 mod isa;
 mod memory;
+mod profiler;
+mod streams;
+mod verifier;
 
 // Registers Indexes
 // -----------------
@@ -27,6 +45,8 @@ pub const R_INSTRUCTION_POINTER: u64 = 11;
 pub const R_STACK_POINTER: u64 = 12;
 pub const R_FRAME_POINTER: u64 = 13;
 pub const R_MEMORY_POINTER: u64 = 14;
+
+pub const REGISTERS_COUNT: usize = 15;
 // -----------------
 pub fn register_to_str(index: u64) -> &'static str {
     match index {
@@ -64,16 +84,56 @@ pub struct VM {
     /// R12 - Stack Pointer
     /// R13 - Frame Pointer
     /// R14 - Memory Pointer (next address after program)
-    pub registers: MemoryBuffer,
+    pub registers: [u64; REGISTERS_COUNT],
 
     pub interrupt_handlers: [Option<InterruptHandler>; 256],
+    /// Handlers addresses installed by program (`setvec` instruction)
+    pub interrupt_vectors: [Option<u64>; 256],
+    /// Frame of the fault handler which is currently running
+    pub fault_frame: Option<FaultFrame>,
     pub allocator: MvmAllocator,
+    /// Guards, quarantine and leaks tracking for heap blocks (`--heap-check`)
+    pub heap_check: HeapChecker,
+    pub filesystem: MvmFilesystem,
+    /// Standard input and outputs of the program (process streams by default)
+    pub streams: Streams,
+    pub decode_cache: DecodeCache,
+    /// Executed instructions statistics (`--profile`)
+    pub profiler: Profiler,
+    /// Executed addresses and conditional jumps outcomes (`--coverage`)
+    pub coverage: Coverage,
+    /// Labels from assembler debug info file (used in reports)
+    pub debug_info: DebugInfo,
+
+    /// Program arguments and environment variables (`KEY=VALUE`).
+    /// They are placed to memory right after the program by `insert_program`.
+    pub arguments: Vec<String>,
+    pub environment: Vec<String>,
+    pub argv_ptr: u64,
+    pub envp_ptr: u64,
+
+    /// Size of constant pool which is placed right before text section (from binary metadata)
+    pub constants_size: u64,
+    /// Allows program to write to its own text section
+    pub self_modifying: bool,
+    /// Forbids execution of data section and constant pool
+    pub no_exec_data: bool,
 
     pub running: bool,
+    /// Set if last executed instruction has transferred control
+    /// (taken jump, call or interrupt with program handler)
+    pub jumped: bool,
     pub text_section: bool,
     pub exit_code: u8,
 
     pub stack_size: usize,
+    /// End of stack region (exclusive), heap grows after it in growable mode
+    pub stack_end: usize,
+    /// Memory size ceiling for growable heap (`sbrk` system call),
+    /// memory size is fixed if it's not set
+    pub memory_limit: Option<usize>,
+    /// Highest value of stack pointer (stack high-water mark)
+    pub stack_peak: u64,
 }
 
 impl VM {
@@ -83,13 +143,33 @@ impl VM {
 
         let mut vm = Self {
             memory,
-            registers: MemoryBuffer::new(15 * 8),
+            registers: [0; REGISTERS_COUNT],
             interrupt_handlers: [None; 256],
+            interrupt_vectors: [None; 256],
+            fault_frame: None,
             allocator: MvmAllocator::new(0, 0), // zero init, in future will be re-initialized
+            heap_check: HeapChecker::new(),
+            filesystem: MvmFilesystem::new(),
+            streams: Streams::new(),
+            decode_cache: DecodeCache::new(),
+            profiler: Profiler::new(),
+            coverage: Coverage::new(),
+            debug_info: DebugInfo::new(),
+            arguments: Vec::new(),
+            environment: Vec::new(),
+            argv_ptr: 0,
+            envp_ptr: 0,
+            constants_size: 0,
+            self_modifying: false,
+            no_exec_data: false,
             running: false,
+            jumped: false,
             text_section: false,
             exit_code: 1,
             stack_size,
+            stack_end: memsize,
+            memory_limit: None,
+            stack_peak: 0,
         };
 
         if stack_size >= memsize {
@@ -114,7 +194,7 @@ impl VM {
             return Err(MvmError::WriteEntryRejected);
         }
 
-        if program.len() >= (self.memory.len() - self.stack_size) {
+        if program.len() >= (self.stack_end - self.stack_size) {
             return Err(MvmError::OutOfBounds);
         }
 
@@ -134,6 +214,26 @@ impl VM {
                 .set_u8((program.len()) as u64, Opcode::Halt as u8)?;
         }
 
+        let text_start = if program[0] == Opcode::DataSection as u8 {
+            program
+                .windows(2)
+                .position(|w| w[0] == 0xff && w[1] == Opcode::TextSection as u8)
+                .map(|position| position as u64)
+                .unwrap_or(memptr as u64)
+        } else {
+            0
+        };
+
+        self.protect_program(text_start, memptr as u64);
+
+        self.decode_cache.reset(text_start, memptr as u64);
+        self.memory.code = (text_start, memptr as u64);
+        self.memory.code_modified = false;
+
+        if !self.arguments.is_empty() || !self.environment.is_empty() {
+            memptr = self.insert_arguments(memptr as u64)? as usize;
+        }
+
         self.set_register(R_MEMORY_POINTER, memptr as u64)?;
         self.set_register(R_INSTRUCTION_POINTER, 0)?;
 
@@ -144,12 +244,214 @@ impl VM {
         Ok(())
     }
 
+    /// Marks loaded program regions:
+    /// ```text
+    /// [DataSection] [data ...] [constants ...] [0xff TextSection code ...]
+    ///  |> text       |> data    |> constants    |> text
+    /// ```
+    /// Text and constants are read-only (text is writable in self-modifying mode),
+    /// data and constants are non-executable if `no_exec_data` is set.
+    /// Program without data section is considered as text.
+    fn protect_program(&mut self, text_start: u64, program_end: u64) {
+        let constants_start = text_start.saturating_sub(self.constants_size).max(1);
+        let writable_text = self.self_modifying;
+        let executable_data = !self.no_exec_data;
+
+        self.memory.regions.clear();
+
+        if text_start > 0 {
+            self.memory
+                .regions
+                .push(MemoryRegion::new("text", 0, 1, writable_text, true));
+            self.memory.regions.push(MemoryRegion::new(
+                "data",
+                1,
+                constants_start,
+                true,
+                executable_data,
+            ));
+            self.memory.regions.push(MemoryRegion::new(
+                "constants",
+                constants_start,
+                text_start,
+                false,
+                executable_data,
+            ));
+        }
+
+        self.memory.regions.push(MemoryRegion::new(
+            "text",
+            text_start,
+            program_end,
+            writable_text,
+            true,
+        ));
+    }
+
+    /// Arguments layout (starting from `address`):
+    /// ```text
+    /// [argv[0], ..., argv[argc - 1], 0] |> 64-bit pointers to arguments
+    /// [envp[0], ..., envp[envc - 1], 0] |> 64-bit pointers to environment variables
+    /// "arg0\0" ... "KEY=VALUE\0" ...    |> null-terminated strings
+    /// ```
+    /// Registers: R0 - argc, R1 - argv, R2 - envp.
+    /// Returns next free address after all strings.
+    fn insert_arguments(&mut self, address: u64) -> Result<u64, MvmError> {
+        let argc = self.arguments.len() as u64;
+        let envc = self.environment.len() as u64;
+
+        let argv_ptr = address;
+        let envp_ptr = argv_ptr + (argc + 1) * 8;
+        let mut string_ptr = envp_ptr + (envc + 1) * 8;
+
+        let strings_size: usize = self
+            .arguments
+            .iter()
+            .chain(self.environment.iter())
+            .map(|string| string.len() + 1)
+            .sum();
+
+        let frame_start = self.get_register(R_FRAME_POINTER)?;
+
+        if string_ptr + strings_size as u64 >= frame_start {
+            return Err(MvmError::OutOfBounds);
+        }
+
+        let strings = self
+            .arguments
+            .iter()
+            .map(|arg| (arg.clone(), argv_ptr))
+            .enumerate()
+            .chain(
+                self.environment
+                    .iter()
+                    .map(|var| (var.clone(), envp_ptr))
+                    .enumerate(),
+            )
+            .collect::<Vec<_>>();
+
+        for (index, (string, array_ptr)) in strings {
+            self.memory
+                .set_u64(array_ptr + index as u64 * 8, string_ptr)?;
+
+            for byte in string.bytes().chain([0]) {
+                self.memory.set_u8(string_ptr, byte)?;
+                string_ptr += 1;
+            }
+        }
+
+        self.memory.set_u64(argv_ptr + argc * 8, 0)?;
+        self.memory.set_u64(envp_ptr + envc * 8, 0)?;
+
+        self.argv_ptr = argv_ptr;
+        self.envp_ptr = envp_ptr;
+
+        self.set_register(R0, argc)?;
+        self.set_register(R1, argv_ptr)?;
+        self.set_register(R2, envp_ptr)?;
+
+        Ok(string_ptr)
+    }
+
     pub fn run(&mut self) -> Result<(), MvmError> {
         self.running = true;
 
         while self.running {
-            let instruction = self.fetch_u8()?;
-            self.execute_instruction(instruction)?;
+            self.step()?;
+        }
+
+        Ok(())
+    }
+
+    /// Executes single instruction, faults are passed to their handlers
+    pub fn step(&mut self) -> Result<(), MvmError> {
+        let instruction_ptr = self.get_register(R_INSTRUCTION_POINTER)?;
+
+        if self.memory.code_modified {
+            self.memory.code_modified = false;
+            self.decode_cache.invalidate();
+        }
+
+        let mut fetched = false;
+
+        if let Err(err) = self
+            .memory
+            .check_execute(instruction_ptr)
+            .and_then(|_| self.fetch_instruction())
+            .and_then(|instruction| {
+                fetched = true;
+
+                if self.profiler.enabled || self.coverage.enabled {
+                    return self.execute_traced(instruction_ptr, instruction);
+                }
+
+                self.execute_instruction(instruction)
+            })
+        {
+            self.trap_fault(err, instruction_ptr, !fetched)
+                .map_err(|err| self.with_context(err, instruction_ptr))?;
+
+            if self.profiler.enabled {
+                self.profiler
+                    .enter(self.get_register(R_INSTRUCTION_POINTER)?);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Wraps instruction error with machine state,
+    /// instruction pointer is moved back to faulting instruction.
+    fn with_context(&mut self, error: MvmError, address: u64) -> MvmError {
+        if matches!(error, MvmError::Runtime { .. }) {
+            return error;
+        }
+
+        self.registers[R_INSTRUCTION_POINTER as usize] = address;
+
+        let (opcode, operands) = match self.decode(address) {
+            Ok(instruction) => (
+                Some(instruction.opcode),
+                self.memory
+                    .get_slice(address.wrapping_add(1), instruction.length - 1)
+                    .map(<[u8]>::to_vec)
+                    .unwrap_or_default(),
+            ),
+            Err(_) => (None, Vec::new()),
+        };
+
+        MvmError::Runtime {
+            error: Box::new(error),
+            context: Box::new(ErrorContext {
+                address,
+                opcode,
+                operands,
+                registers: self.registers,
+            }),
+        }
+    }
+
+    /// Executes instruction, recording it for profiler and coverage
+    fn execute_traced(&mut self, address: u64, instruction: Instruction) -> Result<(), MvmError> {
+        if self.profiler.enabled {
+            self.profiler.record(address, instruction.opcode);
+        }
+
+        if self.coverage.enabled {
+            self.coverage.record(address);
+        }
+
+        self.execute_instruction(instruction)?;
+
+        if self.profiler.enabled {
+            let instruction_ptr = self.get_register(R_INSTRUCTION_POINTER)?;
+
+            self.profiler
+                .follow(instruction.opcode, self.jumped, instruction_ptr);
+        }
+
+        if self.coverage.enabled && Coverage::is_branch(instruction.opcode) {
+            self.coverage.record_branch(address, self.jumped);
         }
 
         Ok(())
@@ -159,11 +461,25 @@ impl VM {
 #[allow(unused)]
 impl VM {
     pub fn get_register(&self, index: u64) -> Result<u64, MvmError> {
-        self.registers.get_u64(index * 8)
+        self.registers
+            .get(index as usize)
+            .copied()
+            .ok_or(MvmError::InvalidRegister(index))
     }
 
     pub fn set_register(&mut self, index: u64, value: u64) -> Result<(), MvmError> {
-        self.registers.set_u64(index * 8, value)
+        let register = self
+            .registers
+            .get_mut(index as usize)
+            .ok_or(MvmError::InvalidRegister(index))?;
+
+        *register = value;
+
+        if index == R_STACK_POINTER {
+            self.stack_peak = self.stack_peak.max(value);
+        }
+
+        Ok(())
     }
 
     fn peek_byte(&self) -> Result<u8, MvmError> {
@@ -215,7 +531,7 @@ impl VM {
         const BYTES_LENGTH: u64 = 1;
 
         let stack_ptr = self.get_register(R_STACK_POINTER)?;
-        let address = stack_ptr - offset as u64 - BYTES_LENGTH;
+        let address = stack_ptr.wrapping_sub(offset as u64 + BYTES_LENGTH);
 
         self.memory.get_u8(address)
     }
@@ -224,7 +540,7 @@ impl VM {
         const BYTES_LENGTH: u64 = 2;
 
         let stack_ptr = self.get_register(R_STACK_POINTER)?;
-        let address = stack_ptr - offset as u64 - BYTES_LENGTH;
+        let address = stack_ptr.wrapping_sub(offset as u64 + BYTES_LENGTH);
 
         self.memory.get_u16(address)
     }
@@ -233,7 +549,7 @@ impl VM {
         const BYTES_LENGTH: u64 = 4;
 
         let stack_ptr = self.get_register(R_STACK_POINTER)?;
-        let address = stack_ptr - offset as u64 - BYTES_LENGTH;
+        let address = stack_ptr.wrapping_sub(offset as u64 + BYTES_LENGTH);
 
         self.memory.get_u32(address)
     }
@@ -242,7 +558,7 @@ impl VM {
         const BYTES_LENGTH: u64 = 8;
 
         let stack_ptr = self.get_register(R_STACK_POINTER)?;
-        let address = stack_ptr - offset as u64 - BYTES_LENGTH;
+        let address = stack_ptr.wrapping_sub(offset as u64 + BYTES_LENGTH);
 
         self.memory.get_u64(address)
     }
@@ -251,7 +567,7 @@ impl VM {
         const BYTES_LENGTH: u64 = 1;
 
         let stack_ptr = self.get_register(R_STACK_POINTER)?;
-        let address = stack_ptr - offset as u64 - BYTES_LENGTH;
+        let address = stack_ptr.wrapping_sub(offset as u64 + BYTES_LENGTH);
 
         self.memory.set_u8(address, value)
     }
@@ -260,7 +576,7 @@ impl VM {
         const BYTES_LENGTH: u64 = 2;
 
         let stack_ptr = self.get_register(R_STACK_POINTER)?;
-        let address = stack_ptr - offset as u64 - BYTES_LENGTH;
+        let address = stack_ptr.wrapping_sub(offset as u64 + BYTES_LENGTH);
 
         self.memory.set_u16(address, value)
     }
@@ -269,7 +585,7 @@ impl VM {
         const BYTES_LENGTH: u64 = 4;
 
         let stack_ptr = self.get_register(R_STACK_POINTER)?;
-        let address = stack_ptr - offset as u64 - BYTES_LENGTH;
+        let address = stack_ptr.wrapping_sub(offset as u64 + BYTES_LENGTH);
 
         self.memory.set_u32(address, value)
     }
@@ -278,7 +594,7 @@ impl VM {
         const BYTES_LENGTH: u64 = 8;
 
         let stack_ptr = self.get_register(R_STACK_POINTER)?;
-        let address = stack_ptr - offset as u64 - BYTES_LENGTH;
+        let address = stack_ptr.wrapping_sub(offset as u64 + BYTES_LENGTH);
 
         self.memory.set_u64(address, value)
     }
@@ -288,7 +604,7 @@ impl VM {
 
         let stack_ptr = self.get_register(R_STACK_POINTER)?;
 
-        if (stack_ptr + BYTES_LENGTH - 1) as usize >= self.memory.len() {
+        if stack_ptr.saturating_add(BYTES_LENGTH) > self.stack_end as u64 {
             return Err(MvmError::StackOverflow);
         }
 
@@ -301,7 +617,7 @@ impl VM {
 
         let stack_ptr = self.get_register(R_STACK_POINTER)?;
 
-        if (stack_ptr + BYTES_LENGTH - 1) as usize >= self.memory.len() {
+        if stack_ptr.saturating_add(BYTES_LENGTH) > self.stack_end as u64 {
             return Err(MvmError::StackOverflow);
         }
 
@@ -314,7 +630,7 @@ impl VM {
 
         let stack_ptr = self.get_register(R_STACK_POINTER)?;
 
-        if (stack_ptr + BYTES_LENGTH - 1) as usize >= self.memory.len() {
+        if stack_ptr.saturating_add(BYTES_LENGTH) > self.stack_end as u64 {
             return Err(MvmError::StackOverflow);
         }
 
@@ -327,7 +643,7 @@ impl VM {
 
         let stack_ptr = self.get_register(R_STACK_POINTER)?;
 
-        if (stack_ptr + BYTES_LENGTH - 1) as usize >= self.memory.len() {
+        if stack_ptr.saturating_add(BYTES_LENGTH) > self.stack_end as u64 {
             return Err(MvmError::StackOverflow);
         }
 
@@ -341,16 +657,20 @@ impl VM {
         let stack_ptr = self.get_register(R_STACK_POINTER)?;
         let frame_ptr = self.get_register(R_FRAME_POINTER)?;
 
-        if (stack_ptr - BYTES_LENGTH) < (self.memory.len() - self.stack_size) as u64 {
+        let address = stack_ptr
+            .checked_sub(BYTES_LENGTH)
+            .ok_or(MvmError::EmptyStackPop)?;
+
+        if address < (self.stack_end - self.stack_size) as u64 {
             return Err(MvmError::EmptyStackPop);
         }
 
-        if (stack_ptr - BYTES_LENGTH) < (frame_ptr) {
+        if address < frame_ptr {
             return Err(MvmError::StackOutOfFrame);
         }
 
-        let value = self.memory.get_u8(stack_ptr - BYTES_LENGTH)?;
-        self.set_register(R_STACK_POINTER, stack_ptr - BYTES_LENGTH)?;
+        let value = self.memory.get_u8(address)?;
+        self.set_register(R_STACK_POINTER, address)?;
 
         Ok(value)
     }
@@ -361,16 +681,20 @@ impl VM {
         let stack_ptr = self.get_register(R_STACK_POINTER)?;
         let frame_ptr = self.get_register(R_FRAME_POINTER)?;
 
-        if (stack_ptr - BYTES_LENGTH) < (self.memory.len() - self.stack_size) as u64 {
+        let address = stack_ptr
+            .checked_sub(BYTES_LENGTH)
+            .ok_or(MvmError::EmptyStackPop)?;
+
+        if address < (self.stack_end - self.stack_size) as u64 {
             return Err(MvmError::EmptyStackPop);
         }
 
-        if (stack_ptr - BYTES_LENGTH) < (frame_ptr) {
+        if address < frame_ptr {
             return Err(MvmError::StackOutOfFrame);
         }
 
-        let value = self.memory.get_u16(stack_ptr - BYTES_LENGTH)?;
-        self.set_register(R_STACK_POINTER, stack_ptr - BYTES_LENGTH)?;
+        let value = self.memory.get_u16(address)?;
+        self.set_register(R_STACK_POINTER, address)?;
 
         Ok(value)
     }
@@ -381,16 +705,20 @@ impl VM {
         let stack_ptr = self.get_register(R_STACK_POINTER)?;
         let frame_ptr = self.get_register(R_FRAME_POINTER)?;
 
-        if (stack_ptr - BYTES_LENGTH) < (self.memory.len() - self.stack_size) as u64 {
+        let address = stack_ptr
+            .checked_sub(BYTES_LENGTH)
+            .ok_or(MvmError::EmptyStackPop)?;
+
+        if address < (self.stack_end - self.stack_size) as u64 {
             return Err(MvmError::EmptyStackPop);
         }
 
-        if (stack_ptr - BYTES_LENGTH) < (frame_ptr) {
+        if address < frame_ptr {
             return Err(MvmError::StackOutOfFrame);
         }
 
-        let value = self.memory.get_u32(stack_ptr - BYTES_LENGTH)?;
-        self.set_register(R_STACK_POINTER, stack_ptr - BYTES_LENGTH)?;
+        let value = self.memory.get_u32(address)?;
+        self.set_register(R_STACK_POINTER, address)?;
 
         Ok(value)
     }
@@ -401,16 +729,20 @@ impl VM {
         let stack_ptr = self.get_register(R_STACK_POINTER)?;
         let frame_ptr = self.get_register(R_FRAME_POINTER)?;
 
-        if (stack_ptr - BYTES_LENGTH) < (self.memory.len() - self.stack_size) as u64 {
+        let address = stack_ptr
+            .checked_sub(BYTES_LENGTH)
+            .ok_or(MvmError::EmptyStackPop)?;
+
+        if address < (self.stack_end - self.stack_size) as u64 {
             return Err(MvmError::EmptyStackPop);
         }
 
-        if (stack_ptr - BYTES_LENGTH) < (frame_ptr) {
+        if address < frame_ptr {
             return Err(MvmError::StackOutOfFrame);
         }
 
-        let value = self.memory.get_u64(stack_ptr - BYTES_LENGTH)?;
-        self.set_register(R_STACK_POINTER, stack_ptr - BYTES_LENGTH)?;
+        let value = self.memory.get_u64(address)?;
+        self.set_register(R_STACK_POINTER, address)?;
 
         Ok(value)
     }
@@ -419,66 +751,256 @@ impl VM {
 
     fn frame_get_u8(&self, offset: u16) -> Result<u8, MvmError> {
         let frame_ptr = self.get_register(R_FRAME_POINTER)?;
-        let address = frame_ptr + offset as u64;
+        let address = frame_ptr
+            .checked_add(offset as u64)
+            .ok_or(MvmError::SegmentationFault(frame_ptr))?;
 
         self.memory.get_u8(address)
     }
 
     fn frame_get_u16(&self, offset: u16) -> Result<u16, MvmError> {
         let frame_ptr = self.get_register(R_FRAME_POINTER)?;
-        let address = frame_ptr + offset as u64;
+        let address = frame_ptr
+            .checked_add(offset as u64)
+            .ok_or(MvmError::SegmentationFault(frame_ptr))?;
 
         self.memory.get_u16(address)
     }
 
     fn frame_get_u32(&self, offset: u16) -> Result<u32, MvmError> {
         let frame_ptr = self.get_register(R_FRAME_POINTER)?;
-        let address = frame_ptr + offset as u64;
+        let address = frame_ptr
+            .checked_add(offset as u64)
+            .ok_or(MvmError::SegmentationFault(frame_ptr))?;
 
         self.memory.get_u32(address)
     }
 
     fn frame_get_u64(&self, offset: u16) -> Result<u64, MvmError> {
         let frame_ptr = self.get_register(R_FRAME_POINTER)?;
-        let address = frame_ptr + offset as u64;
+        let address = frame_ptr
+            .checked_add(offset as u64)
+            .ok_or(MvmError::SegmentationFault(frame_ptr))?;
 
         self.memory.get_u64(address)
     }
 
     fn frame_set_u8(&mut self, offset: u16, value: u8) -> Result<(), MvmError> {
         let frame_ptr = self.get_register(R_FRAME_POINTER)?;
-        let address = frame_ptr + offset as u64;
+        let address = frame_ptr
+            .checked_add(offset as u64)
+            .ok_or(MvmError::SegmentationFault(frame_ptr))?;
 
         self.memory.set_u8(address, value)
     }
 
     fn frame_set_u16(&mut self, offset: u16, value: u16) -> Result<(), MvmError> {
         let frame_ptr = self.get_register(R_FRAME_POINTER)?;
-        let address = frame_ptr + offset as u64;
+        let address = frame_ptr
+            .checked_add(offset as u64)
+            .ok_or(MvmError::SegmentationFault(frame_ptr))?;
 
         self.memory.set_u16(address, value)
     }
 
     fn frame_set_u32(&mut self, offset: u16, value: u32) -> Result<(), MvmError> {
         let frame_ptr = self.get_register(R_FRAME_POINTER)?;
-        let address = frame_ptr + offset as u64;
+        let address = frame_ptr
+            .checked_add(offset as u64)
+            .ok_or(MvmError::SegmentationFault(frame_ptr))?;
 
         self.memory.set_u32(address, value)
     }
 
     fn frame_set_u64(&mut self, offset: u16, value: u64) -> Result<(), MvmError> {
         let frame_ptr = self.get_register(R_FRAME_POINTER)?;
-        let address = frame_ptr + offset as u64;
+        let address = frame_ptr
+            .checked_add(offset as u64)
+            .ok_or(MvmError::SegmentationFault(frame_ptr))?;
 
         self.memory.set_u64(address, value)
     }
 }
 
 impl VM {
+    /// Maximal stack usage in bytes
+    pub fn stack_peak_depth(&self) -> u64 {
+        self.stack_peak
+            .saturating_sub((self.stack_end - self.stack_size) as u64)
+    }
+
+    /// Moves end of memory (program break) by `increment` bytes in growable mode.
+    /// Returns previous break or `None` if memory limit is exceeded.
+    fn grow_memory(&mut self, increment: u64) -> Option<u64> {
+        let limit = self.memory_limit?;
+        let previous_break = self.memory.len();
+
+        let new_break = previous_break.checked_add(usize::try_from(increment).ok()?)?;
+
+        if new_break > limit {
+            return None;
+        }
+
+        self.memory.grow(new_break - previous_break);
+
+        Some(previous_break as u64)
+    }
+
+    /// Allocates heap block (surrounded with guards if heap checker is enabled)
+    fn allocate(&mut self, size: usize) -> Result<usize, MvmError> {
+        if self.heap_check.enabled {
+            return self.checked_allocate(size);
+        }
+
+        self.allocate_unit(size)
+    }
+
+    fn deallocate(&mut self, ptr: usize) -> Result<(), MvmError> {
+        if self.heap_check.enabled {
+            return self.checked_deallocate(ptr);
+        }
+
+        Ok(self.allocator.deallocate(ptr)?)
+    }
+
+    /// Allocates memory, requesting more pages if allocator runs out of memory in growable mode.
+    fn allocate_unit(&mut self, size: usize) -> Result<usize, MvmError> {
+        const PAGE_SIZE: usize = 4096;
+
+        match self.allocator.allocate(size) {
+            Err(AllocatorError::OutOfMemory) if self.memory_limit.is_some() => {
+                let pages_size = size
+                    .max(1)
+                    .checked_next_multiple_of(PAGE_SIZE)
+                    .ok_or(AllocatorError::OutOfMemory)?;
+
+                let Some(start) = self.grow_memory(pages_size as u64) else {
+                    return Err(AllocatorError::OutOfMemory.into());
+                };
+
+                let start = start as usize;
+                self.allocator.extend(start, start + pages_size);
+
+                Ok(self.allocator.allocate(size)?)
+            }
+
+            result => Ok(result?),
+        }
+    }
+
+    /// Resizes allocated unit, copying its data if it was moved.
+    /// Null pointer is allocated, zero size frees the unit.
+    fn reallocate(&mut self, ptr: usize, size: usize) -> Result<usize, MvmError> {
+        if self.heap_check.enabled {
+            return self.checked_reallocate(ptr, size);
+        }
+
+        if ptr == 0 {
+            return self.allocate(size);
+        }
+
+        if size == 0 {
+            self.allocator.deallocate(ptr)?;
+            return Ok(0);
+        }
+
+        let old_size = self
+            .allocator
+            .size_of(ptr)
+            .ok_or(AllocatorError::InvalidReallocPointer(ptr))?;
+
+        let new_ptr = match self.allocator.reallocate(ptr, size) {
+            Err(AllocatorError::OutOfMemory) if self.memory_limit.is_some() => {
+                let new_ptr = self.allocate_unit(size)?;
+                self.allocator.deallocate(ptr)?;
+
+                new_ptr
+            }
+
+            result => result?,
+        };
+
+        if new_ptr != ptr {
+            self.memory
+                .copy(new_ptr as u64, ptr as u64, old_size.min(size) as u64)?;
+        }
+
+        Ok(new_ptr)
+    }
+
+    /// Verifies that `[stack pointer - offset - length]` write stays inside current frame
+    fn check_stack_write(&self, offset: u16, length: u64) -> Result<(), MvmError> {
+        let stack_ptr = self.get_register(R_STACK_POINTER)?;
+        let frame_ptr = self.get_register(R_FRAME_POINTER)?;
+
+        match stack_ptr.checked_sub(offset as u64 + length) {
+            Some(address) if address >= frame_ptr => Ok(()),
+            _ => Err(MvmError::StackOutOfFrame),
+        }
+    }
+
+    /// Verifies that `[frame pointer + offset]` write stays below stack pointer
+    fn check_frame_write(&self, offset: u16, length: u64) -> Result<(), MvmError> {
+        let stack_ptr = self.get_register(R_STACK_POINTER)?;
+        let frame_ptr = self.get_register(R_FRAME_POINTER)?;
+
+        if frame_ptr.saturating_add(offset as u64 + length) > stack_ptr {
+            return Err(MvmError::StackOutOfFrame);
+        }
+
+        Ok(())
+    }
+
+    /// Pushes return address and frame pointer, new frame starts after them:
+    /// ```text
+    /// [return address][frame pointer] |> frame pointer
+    /// ```
+    fn push_frame(&mut self) -> Result<(), MvmError> {
+        const REQUIRED_SPACE: u64 = 2 * 8;
+
+        if self
+            .get_register(R_STACK_POINTER)?
+            .saturating_add(REQUIRED_SPACE)
+            > self.stack_end as u64
+        {
+            return Err(MvmError::CallStackOverflow);
+        }
+
+        self.stack_push_u64(self.get_register(R_INSTRUCTION_POINTER)?)?;
+        self.stack_push_u64(self.get_register(R_FRAME_POINTER)?)?;
+
+        let stack_ptr = self.get_register(R_STACK_POINTER)?;
+        self.set_register(R_FRAME_POINTER, stack_ptr)?;
+
+        Ok(())
+    }
+
+    /// Drops current frame and restores registers saved by `push_frame`.
+    fn pop_frame(&mut self) -> Result<(), MvmError> {
+        let frame_ptr = self.get_register(R_FRAME_POINTER)?;
+
+        if frame_ptr < (self.stack_end - self.stack_size) as u64 + 2 * 8 {
+            return Err(MvmError::EmptyCallStackPop);
+        }
+
+        let saved_frame_ptr = self.memory.get_u64(frame_ptr - 8)?;
+        let return_addr = self.memory.get_u64(frame_ptr - 2 * 8)?;
+
+        self.set_register(R_STACK_POINTER, frame_ptr - 2 * 8)?;
+        self.set_register(R_FRAME_POINTER, saved_frame_ptr)?;
+        self.set_register(R_INSTRUCTION_POINTER, return_addr)?;
+
+        Ok(())
+    }
+
     fn push_state(&mut self) -> Result<(), MvmError> {
         const REQUIRED_SPACE: u64 = 14 * 8; // 14 is count of registers below
 
-        if self.get_register(R_STACK_POINTER)? + REQUIRED_SPACE > self.memory.len() as u64 {
+        if self
+            .get_register(R_STACK_POINTER)?
+            .saturating_add(REQUIRED_SPACE)
+            > self.stack_end as u64
+        {
             return Err(MvmError::CallStackOverflow);
         }
 
@@ -503,26 +1025,38 @@ impl VM {
         Ok(())
     }
 
-    fn pop_state(&mut self) -> Result<(), MvmError> {
-        if self.get_register(R_FRAME_POINTER)? as usize <= self.memory.len() - self.stack_size {
+    /// Restores registers saved by `push_state` (except accumulator).
+    /// Returns saved accumulator value, fails with fetch fault if its handler tries to return.
+    fn pop_state(&mut self) -> Result<u64, MvmError> {
+        if self.get_register(R_FRAME_POINTER)? as usize <= self.stack_end - self.stack_size {
             return Err(MvmError::EmptyCallStackPop);
         }
 
-        // erasing stack frame data
+        let frame_ptr = self.get_register(R_FRAME_POINTER)?;
 
-        while self.get_register(R_STACK_POINTER)? > self.get_register(R_FRAME_POINTER)? {
-            let _ = self.stack_pop_u8()?;
+        // leaving fault handler (frames above it are dropped, so it isn't running too)
+        if let Some(fault) = self
+            .fault_frame
+            .take_if(|fault| fault.frame >= frame_ptr)
+            .filter(|fault| fault.frame == frame_ptr)
+            && let Some(error) = fault.fatal
+        {
+            return Err(error);
         }
 
+        // dropping stack frame data
+
+        self.set_register(R_STACK_POINTER, frame_ptr)?;
+
         // returning registers values
 
-        self.set_register(R_FRAME_POINTER, self.get_register(R_FRAME_POINTER)? - 8)?;
+        self.set_register(R_FRAME_POINTER, frame_ptr - 8)?;
 
         let frame_ptr = self.stack_pop_u64()?;
         self.set_register(R_FRAME_POINTER, frame_ptr)?;
 
         let instruction_ptr = self.stack_pop_u64()?;
-        let _accumulator = self.stack_pop_u64()?;
+        let accumulator = self.stack_pop_u64()?;
         let system_call = self.stack_pop_u64()?;
         let r8 = self.stack_pop_u64()?;
         let r7 = self.stack_pop_u64()?;
@@ -549,11 +1083,12 @@ impl VM {
         self.set_register(R1, r1)?;
         self.set_register(R0, r0)?;
 
-        Ok(())
+        Ok(accumulator)
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::erasing_op, clippy::identity_op)]
 mod tests {
     use super::*;
 
@@ -589,6 +1124,91 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn vm_memory_protection_test() -> Result<(), MvmError> {
+        let program = [
+            Opcode::DataSection as u8,
+            // -- data section --
+            5,
+            7,
+            // -- data section end --
+            0xff,
+            Opcode::TextSection as u8,
+            // store8 %r2, %r1
+            Opcode::Store8 as u8,
+            R2 as u8,
+            R1 as u8,
+            Opcode::Halt as u8,
+        ];
+
+        let run_with = |address: u64, self_modifying: bool| -> Result<VM, MvmError> {
+            let mut vm = VM::new(128, 16)?;
+
+            vm.constants_size = 1;
+            vm.self_modifying = self_modifying;
+
+            vm.insert_program(&program)?;
+            vm.set_register(R1, 123)?;
+            vm.set_register(R2, address)?;
+            vm.run()?;
+
+            Ok(vm)
+        };
+
+        assert_eq!(run_with(1, false)?.memory.get_u8(1)?, 123);
+        assert_eq!(run_with(5, true)?.memory.get_u8(5)?, 123);
+
+        assert!(matches!(
+            run_with(2, false).as_ref().map_err(MvmError::inner),
+            Err(MvmError::ProtectionFault {
+                address: 2,
+                region: "constants",
+                access: "write"
+            })
+        ));
+        assert!(matches!(
+            run_with(5, false).as_ref().map_err(MvmError::inner),
+            Err(MvmError::ProtectionFault { region: "text", .. })
+        ));
+        assert!(run_with(0, false).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn vm_no_exec_data_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(128, 16)?;
+
+        vm.no_exec_data = true;
+
+        let program = [
+            Opcode::DataSection as u8,
+            Opcode::Halt as u8,
+            0xff,
+            Opcode::TextSection as u8,
+            Opcode::Halt as u8,
+        ];
+
+        vm.insert_program(&program)?;
+
+        assert!(vm.memory.check_execute(0).is_ok());
+        assert!(vm.memory.check_execute(1).is_err());
+        assert!(vm.memory.check_execute(4).is_ok());
+
+        vm.set_register(R_INSTRUCTION_POINTER, 1)?;
+
+        assert!(matches!(
+            vm.run().as_ref().map_err(MvmError::inner),
+            Err(MvmError::ProtectionFault {
+                address: 1,
+                access: "execute",
+                ..
+            })
+        ));
+
+        Ok(())
+    }
+
     #[test]
     fn vm_insert_program_with_halt_test() -> Result<(), MvmError> {
         let mut vm = VM::new(128, 16)?;
@@ -611,6 +1231,52 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn vm_insert_program_arguments_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(128, 16)?;
+
+        vm.arguments = vec![String::from("prog"), String::from("-v")];
+        vm.environment = vec![String::from("A=1")];
+
+        let program = [Opcode::Halt as u8];
+
+        vm.insert_program(&program)?;
+
+        let argv = vm.get_register(R1)?;
+        let envp = vm.get_register(R2)?;
+
+        assert_eq!(vm.get_register(R0)?, 2);
+        assert_eq!(argv, 1);
+        assert_eq!(envp, argv + 3 * 8);
+
+        let strings = envp + 2 * 8;
+
+        assert_eq!(vm.memory.get_u64(argv)?, strings);
+        assert_eq!(vm.memory.get_u64(argv + 8)?, strings + 5);
+        assert_eq!(vm.memory.get_u64(argv + 16)?, 0);
+        assert_eq!(vm.memory.get_u64(envp)?, strings + 8);
+        assert_eq!(vm.memory.get_u64(envp + 8)?, 0);
+
+        assert_eq!(vm.memory.get_slice(strings, 12)?, b"prog\0-v\0A=1\0");
+        assert_eq!(vm.get_register(R_MEMORY_POINTER)?, strings + 12);
+
+        Ok(())
+    }
+
+    #[test]
+    fn vm_insert_program_arguments_overflow_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(64, 16)?;
+
+        vm.arguments = vec!["a".repeat(64)];
+
+        assert!(matches!(
+            vm.insert_program(&[Opcode::Halt as u8]),
+            Err(MvmError::OutOfBounds)
+        ));
+
+        Ok(())
+    }
+
     #[test]
     fn vm_fetch_u8_test() -> Result<(), MvmError> {
         let mut vm = VM::new(128, 16)?;
@@ -699,6 +1365,72 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn vm_invalid_register_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(128, 16)?;
+
+        assert!(matches!(
+            vm.get_register(REGISTERS_COUNT as u64),
+            Err(MvmError::InvalidRegister(15))
+        ));
+        assert!(matches!(
+            vm.set_register(u64::MAX, 1),
+            Err(MvmError::InvalidRegister(u64::MAX))
+        ));
+
+        let program = [Opcode::MovR2R as u8, R0 as u8, 200, Opcode::Halt as u8];
+
+        vm.insert_program(&program)?;
+
+        assert!(matches!(
+            vm.run().as_ref().map_err(MvmError::inner),
+            Err(MvmError::InvalidRegister(200))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn vm_runtime_error_context_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(128, 16)?;
+
+        let program = [
+            Opcode::MovR2R as u8,
+            R2 as u8,
+            R1 as u8,
+            Opcode::MovR2R as u8,
+            R0 as u8,
+            200,
+            Opcode::Halt as u8,
+        ];
+
+        vm.insert_program(&program)?;
+        vm.set_register(R1, 7)?;
+
+        let err = vm.run().unwrap_err();
+        let context = err.context().expect("runtime context");
+
+        assert!(matches!(err.inner(), MvmError::InvalidRegister(200)));
+        assert_eq!(context.address, 3);
+        assert_eq!(context.opcode, Some(Opcode::MovR2R));
+        assert_eq!(context.operands, [R0 as u8, 200]);
+        assert_eq!(context.registers[R2 as usize], 7);
+        assert_eq!(context.registers[R_INSTRUCTION_POINTER as usize], 3);
+        assert_eq!(vm.get_register(R_INSTRUCTION_POINTER)?, 3);
+
+        // undecodable instruction
+        vm.memory.inner[3] = 0xfe;
+        vm.decode_cache.invalidate();
+        vm.set_register(R_INSTRUCTION_POINTER, 3)?;
+
+        let err = vm.run().unwrap_err();
+
+        assert!(matches!(err.inner(), MvmError::InvalidOpcode(0xfe)));
+        assert_eq!(err.context().and_then(|context| context.opcode), None);
+
+        Ok(())
+    }
+
     #[test]
     fn vm_set_register_test() -> Result<(), MvmError> {
         let mut vm = VM::new(128, 16)?;
@@ -922,6 +1654,99 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn vm_grow_memory_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(64, 16)?;
+
+        assert_eq!(vm.grow_memory(8), None);
+
+        vm.memory_limit = Some(80);
+
+        assert_eq!(vm.grow_memory(8), Some(64));
+        assert_eq!(vm.grow_memory(0), Some(72));
+        assert_eq!(vm.grow_memory(9), None);
+        assert_eq!(vm.grow_memory(u64::MAX), None);
+        assert_eq!(vm.memory.len(), 72);
+
+        // stack doesn't grow into the heap
+        vm.set_register(R_STACK_POINTER, 60)?;
+
+        assert!(matches!(vm.stack_push_u64(1), Err(MvmError::StackOverflow)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn vm_stack_peak_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(64, 32)?;
+
+        let start_depth = vm.stack_peak_depth();
+
+        vm.stack_push_u64(1)?;
+        vm.stack_push_u32(2)?;
+        vm.stack_pop_u32()?;
+        vm.stack_pop_u64()?;
+
+        assert_eq!(vm.stack_peak_depth(), start_depth + 12);
+
+        Ok(())
+    }
+
+    #[test]
+    fn vm_corrupted_stack_pointer_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(128, 32)?;
+
+        vm.set_register(R_STACK_POINTER, 0)?;
+
+        assert!(matches!(vm.stack_pop_u64(), Err(MvmError::EmptyStackPop)));
+        assert!(matches!(
+            vm.stack_get_u16(4),
+            Err(MvmError::SegmentationFault(_))
+        ));
+
+        vm.set_register(R_STACK_POINTER, u64::MAX)?;
+
+        assert!(matches!(vm.stack_push_u64(1), Err(MvmError::StackOverflow)));
+        assert!(matches!(vm.push_frame(), Err(MvmError::CallStackOverflow)));
+
+        vm.set_register(R_FRAME_POINTER, u64::MAX)?;
+
+        assert!(matches!(
+            vm.frame_get_u32(8),
+            Err(MvmError::SegmentationFault(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn vm_push_pop_frame_test() -> Result<(), MvmError> {
+        const MEMSIZE: u64 = 256;
+        const STACKSIZE: u64 = 128;
+
+        let mut vm = VM::new(256, 128)?;
+
+        assert!(matches!(vm.pop_frame(), Err(MvmError::EmptyCallStackPop)));
+
+        vm.set_register(R_INSTRUCTION_POINTER, 42)?;
+        vm.push_frame()?;
+
+        assert_eq!(vm.get_register(R_FRAME_POINTER)?, MEMSIZE - STACKSIZE + 16);
+
+        vm.stack_push_u64(123)?;
+        vm.set_register(R0, 123)?;
+        vm.set_register(R_INSTRUCTION_POINTER, 0)?;
+
+        vm.pop_frame()?;
+
+        assert_eq!(vm.get_register(R0)?, 123);
+        assert_eq!(vm.get_register(R_INSTRUCTION_POINTER)?, 42);
+        assert_eq!(vm.get_register(R_FRAME_POINTER)?, MEMSIZE - STACKSIZE);
+        assert_eq!(vm.get_register(R_STACK_POINTER)?, MEMSIZE - STACKSIZE);
+
+        Ok(())
+    }
+
     #[test]
     fn vm_push_state_test() -> Result<(), MvmError> {
         const PUSHED_REGSITERS: u64 = 14;
@@ -0,0 +1,330 @@
+//! **Mvm Profiler** counts executed instructions per opcode, per address and per call stack
+//! (`mvm run --profile`). Call stack is tracked by `call`/`ret` boundaries (interrupt and fault
+//! handlers included), functions are named by debug info labels when they're available.
+
+use std::collections::HashMap;
+
+use super::Opcode;
+use super::debuginfo::DebugInfo;
+
+/// Name of the bottom frame (code executed outside of any call)
+pub const ROOT_FRAME: &str = "[program]";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionProfile {
+    /// Function entry address, `None` for the root frame
+    pub address: Option<u64>,
+    /// Instructions executed by function itself
+    pub self_count: u64,
+    /// Instructions executed by function and its callees
+    pub total_count: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct Profiler {
+    pub enabled: bool,
+    pub total: u64,
+
+    opcodes: HashMap<u8, u64>,
+    addresses: HashMap<u64, u64>,
+
+    /// Entry addresses of called functions (without the root frame)
+    call_stack: Vec<u64>,
+    /// Interned call stacks with count of instructions executed on top of them
+    stacks: Vec<(Vec<u64>, u64)>,
+    stacks_ids: HashMap<Vec<u64>, usize>,
+    current_stack: usize,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        let mut profiler = Self::default();
+
+        profiler.switch_stack();
+        profiler
+    }
+
+    pub fn record(&mut self, address: u64, opcode: Opcode) {
+        self.total += 1;
+
+        *self.opcodes.entry(opcode as u8).or_default() += 1;
+        *self.addresses.entry(address).or_default() += 1;
+        self.stacks[self.current_stack].1 += 1;
+    }
+
+    pub fn enter(&mut self, address: u64) {
+        self.call_stack.push(address);
+        self.switch_stack();
+    }
+
+    pub fn leave(&mut self) {
+        if self.call_stack.pop().is_some() {
+            self.switch_stack();
+        }
+    }
+
+    /// Follows call stack changes made by executed instruction
+    pub fn follow(&mut self, opcode: Opcode, jumped: bool, target: u64) {
+        match opcode {
+            // interrupts with machine handlers are returned immediately
+            Opcode::Call
+            | Opcode::CallReg
+            | Opcode::FullCall
+            | Opcode::FullCallReg
+            | Opcode::Interrupt
+                if jumped =>
+            {
+                self.enter(target)
+            }
+
+            Opcode::Return | Opcode::FullReturn | Opcode::InterruptReturn => self.leave(),
+
+            _ => {}
+        }
+    }
+
+    /// Most executed opcodes (descending)
+    pub fn hot_opcodes(&self, limit: usize) -> Vec<(Opcode, u64)> {
+        let mut opcodes = self
+            .opcodes
+            .iter()
+            .filter_map(|(opcode, count)| Some((Opcode::try_from(*opcode).ok()?, *count)))
+            .collect::<Vec<_>>();
+
+        opcodes.sort_by(|a, b| b.1.cmp(&a.1).then((a.0 as u8).cmp(&(b.0 as u8))));
+        opcodes.truncate(limit);
+        opcodes
+    }
+
+    /// Most executed instruction addresses (descending)
+    pub fn hot_addresses(&self, limit: usize) -> Vec<(u64, u64)> {
+        let mut addresses = self
+            .addresses
+            .iter()
+            .map(|(address, count)| (*address, *count))
+            .collect::<Vec<_>>();
+
+        addresses.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        addresses.truncate(limit);
+        addresses
+    }
+
+    /// Functions sorted by instructions executed by themselves (descending)
+    pub fn functions(&self) -> Vec<FunctionProfile> {
+        let mut functions: HashMap<Option<u64>, FunctionProfile> = HashMap::new();
+
+        for (stack, count) in &self.stacks {
+            let frames = std::iter::once(None).chain(stack.iter().copied().map(Some));
+            let mut counted = Vec::new();
+
+            for frame in frames {
+                // recursive calls are counted once in total
+                if counted.contains(&frame) {
+                    continue;
+                }
+
+                counted.push(frame);
+
+                functions
+                    .entry(frame)
+                    .or_insert(FunctionProfile {
+                        address: frame,
+                        self_count: 0,
+                        total_count: 0,
+                    })
+                    .total_count += count;
+            }
+
+            let leaf = stack.last().copied();
+
+            if let Some(function) = functions.get_mut(&leaf) {
+                function.self_count += count;
+            }
+        }
+
+        let mut functions = functions.into_values().collect::<Vec<_>>();
+
+        functions.sort_by(|a, b| {
+            b.self_count
+                .cmp(&a.self_count)
+                .then(b.total_count.cmp(&a.total_count))
+                .then(a.address.cmp(&b.address))
+        });
+        functions
+    }
+
+    /// Call stacks in folded format (`root;caller;callee count` lines) for flamegraph tools
+    pub fn folded_stacks(&self, debug_info: &DebugInfo) -> String {
+        let mut lines = self
+            .stacks
+            .iter()
+            .filter(|(_, count)| *count > 0)
+            .map(|(stack, count)| {
+                let frames = std::iter::once(ROOT_FRAME.to_owned())
+                    .chain(stack.iter().map(|address| debug_info.locate(*address)))
+                    .collect::<Vec<_>>();
+
+                format!("{} {count}", frames.join(";"))
+            })
+            .collect::<Vec<_>>();
+
+        lines.sort();
+        lines.into_iter().map(|line| line + "\n").collect()
+    }
+
+    fn switch_stack(&mut self) {
+        self.current_stack = match self.stacks_ids.get(&self.call_stack) {
+            Some(id) => *id,
+            None => {
+                let id = self.stacks.len();
+
+                self.stacks.push((self.call_stack.clone(), 0));
+                self.stacks_ids.insert(self.call_stack.clone(), id);
+
+                id
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{MvmError, R0, R1, VM};
+    use super::*;
+
+    #[test]
+    fn profiler_stacks_test() {
+        let mut profiler = Profiler::new();
+
+        profiler.record(0, Opcode::Call);
+        profiler.enter(10);
+        profiler.record(10, Opcode::Call);
+        profiler.enter(20);
+        profiler.record(20, Opcode::AddR2R);
+        profiler.record(21, Opcode::Return);
+        profiler.leave();
+        profiler.record(11, Opcode::Return);
+        profiler.leave();
+        profiler.record(1, Opcode::Halt);
+
+        // unbalanced return keeps the root frame
+        profiler.leave();
+
+        assert_eq!(profiler.total, 6);
+        assert_eq!(
+            profiler.hot_opcodes(2),
+            vec![(Opcode::Call, 2), (Opcode::Return, 2)]
+        );
+        assert_eq!(profiler.hot_addresses(1), vec![(0, 1)]);
+
+        let debug_info = DebugInfo::parse("label 10 outer\n");
+
+        assert_eq!(
+            profiler.folded_stacks(&debug_info),
+            "[program] 2\n[program];outer 2\n[program];outer;outer+10 2\n"
+        );
+
+        assert_eq!(
+            profiler.functions(),
+            vec![
+                FunctionProfile {
+                    address: None,
+                    self_count: 2,
+                    total_count: 6,
+                },
+                FunctionProfile {
+                    address: Some(10),
+                    self_count: 2,
+                    total_count: 4,
+                },
+                FunctionProfile {
+                    address: Some(20),
+                    self_count: 2,
+                    total_count: 2,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn profiler_recursion_test() {
+        let mut profiler = Profiler::new();
+
+        profiler.enter(10);
+        profiler.record(10, Opcode::Call);
+        profiler.enter(10);
+        profiler.record(10, Opcode::Return);
+
+        let function = profiler
+            .functions()
+            .into_iter()
+            .find(|function| function.address == Some(10))
+            .unwrap();
+
+        assert_eq!(function.self_count, 2);
+        assert_eq!(function.total_count, 2);
+    }
+
+    #[test]
+    fn vm_profiled_run_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(256, 64)?;
+
+        #[rustfmt::skip]
+        let program = [
+            // call func (address 11)
+            Opcode::Call as u8, 0, 0, 0, 0, 0, 0, 0, 11,
+            // program end
+            Opcode::Halt as u8,
+            Opcode::Halt as u8,
+            // func: add %r0, %r1; ret
+            Opcode::AddR2R as u8, R0 as u8, R1 as u8,
+            Opcode::Return as u8,
+        ];
+
+        vm.insert_program(&program)?;
+        vm.profiler.enabled = true;
+        vm.run()?;
+
+        assert_eq!(vm.profiler.total, 4);
+        assert_eq!(
+            vm.profiler
+                .folded_stacks(&DebugInfo::parse("label 11 func")),
+            "[program] 2\n[program];func 2\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn vm_profiled_call_to_next_instruction_test() -> Result<(), MvmError> {
+        let mut vm = VM::new(512, 256)?;
+
+        #[rustfmt::skip]
+        let program = [
+            // 0: call func (address 9, right after the call)
+            Opcode::Call as u8, 0, 0, 0, 0, 0, 0, 0, 9,
+            // 9: func: jnz 28 (taken after return)
+            Opcode::Jnz as u8, 0, 0, 0, 0, 0, 0, 0, 28,
+            // 18: int $0 (increments accumulator, vector at 29)
+            Opcode::Interrupt as u8, 0, 0, 0, 0, 0, 0, 0, 29,
+            // 27: ret
+            Opcode::Return as u8,
+            // 28: halt
+            Opcode::Halt as u8,
+            // 29: interrupt vector
+            0,
+        ];
+
+        vm.insert_program(&program)?;
+        vm.profiler.enabled = true;
+        vm.run()?;
+
+        assert_eq!(vm.profiler.total, 6);
+        assert_eq!(
+            vm.profiler.folded_stacks(&DebugInfo::parse("label 9 func")),
+            "[program] 3\n[program];func 3\n"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,88 @@
+//! **Mvm Streams** are standard input and outputs of the program (`read` and `write` system calls,
+//! `dbg` instruction). They're process streams by default, embedders can replace them with
+//! in-memory buffers, pipes or their own sinks.
+
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+
+// Standard Descriptors
+// -----------------
+pub const STDOUT: u64 = 1;
+pub const STDERR: u64 = 2;
+// -----------------
+
+pub struct Streams {
+    pub stdin: Box<dyn Read + Send>,
+    pub stdout: Box<dyn Write + Send>,
+    pub stderr: Box<dyn Write + Send>,
+}
+
+impl Default for Streams {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Streams {
+    pub fn new() -> Self {
+        Self {
+            stdin: Box::new(std::io::stdin()),
+            stdout: Box::new(std::io::stdout()),
+            stderr: Box::new(std::io::stderr()),
+        }
+    }
+
+    /// Streams reading given input, returns buffers which capture stdout and stderr
+    pub fn captured(input: impl Into<Vec<u8>>) -> (Self, SharedBuffer, SharedBuffer) {
+        let (stdout, stderr) = (SharedBuffer::new(), SharedBuffer::new());
+
+        let streams = Self {
+            stdin: Box::new(std::io::Cursor::new(input.into())),
+            stdout: Box::new(stdout.clone()),
+            stderr: Box::new(stderr.clone()),
+        };
+
+        (streams, stdout, stderr)
+    }
+
+    /// Output stream by descriptor (`STDOUT` or `STDERR`)
+    pub fn output(&mut self, descriptor: u64) -> Option<&mut (dyn Write + Send)> {
+        match descriptor {
+            STDOUT => Some(self.stdout.as_mut()),
+            STDERR => Some(self.stderr.as_mut()),
+            _ => None,
+        }
+    }
+}
+
+/// Output sink which can be read while the machine owns its clone
+#[derive(Debug, Default, Clone)]
+pub struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn contents(&self) -> Vec<u8> {
+        self.0
+            .lock()
+            .map(|buffer| buffer.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0
+            .lock()
+            .map_err(|_| std::io::Error::other("shared buffer is poisoned"))?
+            .extend_from_slice(buf);
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
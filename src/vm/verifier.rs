@@ -0,0 +1,491 @@
+//! **Mvm Verifier** statically checks program before execution (`mvm verify`, and `mvm run`
+//! unless `--no-verify` is set). Text section is walked instruction by instruction using opcodes
+//! operand layouts, so invalid opcodes, truncated instructions, wrong register indexes, jumps
+//! outside of code and references outside of data section and constant pool are found before
+//! they are hit. Indirect jumps (by register) can't be checked statically.
+//!
+//! Program layout is the same as in `VM::insert_program`: program without data section is
+//! considered as text which may contain its constants.
+
+use std::collections::BTreeSet;
+use thiserror::Error;
+
+use super::{Opcode, REGISTERS_COUNT};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum VerifyError {
+    #[error("no program `text` section found")]
+    NoTextSection,
+
+    #[error("invalid opcode {opcode:#04x} at {address}")]
+    InvalidOpcode { address: u64, opcode: u8 },
+
+    #[error("truncated `{opcode:?}` instruction at {address} ({length} bytes expected)")]
+    TruncatedInstruction {
+        address: u64,
+        opcode: Opcode,
+        length: u64,
+    },
+
+    #[error("invalid register index {index} in `{opcode:?}` instruction at {address}")]
+    InvalidRegister {
+        address: u64,
+        opcode: Opcode,
+        index: u64,
+    },
+
+    #[error("invalid scale {scale} in `{opcode:?}` instruction at {address}")]
+    InvalidScale {
+        address: u64,
+        opcode: Opcode,
+        scale: u64,
+    },
+
+    #[error("`{opcode:?}` instruction at {address} targets {target} which is {reason}")]
+    InvalidTarget {
+        address: u64,
+        opcode: Opcode,
+        target: u64,
+        reason: &'static str,
+    },
+
+    #[error(
+        "`{opcode:?}` instruction at {address} references {width} bytes at {reference} outside of {region}"
+    )]
+    InvalidReference {
+        address: u64,
+        opcode: Opcode,
+        reference: u64,
+        width: u64,
+        region: &'static str,
+    },
+}
+
+impl VerifyError {
+    /// Address of instruction which caused the error
+    pub fn address(&self) -> Option<u64> {
+        match self {
+            VerifyError::NoTextSection => None,
+            VerifyError::InvalidOpcode { address, .. }
+            | VerifyError::TruncatedInstruction { address, .. }
+            | VerifyError::InvalidRegister { address, .. }
+            | VerifyError::InvalidScale { address, .. }
+            | VerifyError::InvalidTarget { address, .. }
+            | VerifyError::InvalidReference { address, .. } => Some(*address),
+        }
+    }
+}
+
+/// Meaning of encoded operand
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operand {
+    Register,
+    Scale,
+    /// Address of value with given width in data section or constant pool
+    Constant(u64),
+    /// Code address of jump, call or interrupt handler
+    Target,
+    /// Any address inside of program (`lea`)
+    Address,
+    /// Inline value
+    Displacement,
+}
+
+/// Verifies loaded program (without metadata), returns all found errors
+pub fn verify(program: &[u8]) -> Result<(), Vec<VerifyError>> {
+    let (text_start, data) = if program.first() == Some(&(Opcode::DataSection as u8)) {
+        let text_start = program
+            .windows(2)
+            .position(|w| w[0] == 0xff && w[1] == Opcode::TextSection as u8)
+            .ok_or(vec![VerifyError::NoTextSection])? as u64;
+
+        // walking starts from `TextSection` opcode (after `0xff`)
+        (text_start + 1, 1..text_start)
+    } else {
+        (0, 0..program.len() as u64)
+    };
+
+    let end = program.len() as u64;
+
+    let mut errors = Vec::new();
+    let mut boundaries = BTreeSet::new();
+    let mut targets = Vec::new();
+
+    let mut text_section = false;
+    let mut address = text_start;
+
+    while address < end {
+        boundaries.insert(address);
+
+        let byte = program[address as usize];
+
+        // entry point jump is executed as regular `jmp`
+        let (opcode, layout) = if text_section && byte == 0xff {
+            (Opcode::Jmp, &[Operand::Target][..])
+        } else {
+            match Opcode::try_from(byte) {
+                Ok(opcode) => (opcode, operands(opcode)),
+                Err(_) => {
+                    errors.push(VerifyError::InvalidOpcode {
+                        address,
+                        opcode: byte,
+                    });
+
+                    address += 1;
+                    continue;
+                }
+            }
+        };
+
+        text_section |= opcode == Opcode::TextSection;
+
+        let widths = layout.iter().map(|operand| match operand {
+            Operand::Register | Operand::Scale => 1,
+            _ => 8,
+        });
+
+        let length = 1 + widths.clone().sum::<u64>();
+
+        if address + length > end {
+            errors.push(VerifyError::TruncatedInstruction {
+                address,
+                opcode,
+                length,
+            });
+            break;
+        }
+
+        let mut offset = address + 1;
+
+        for (operand, width) in layout.iter().zip(widths) {
+            let bytes = &program[offset as usize..(offset + width) as usize];
+            let value = bytes
+                .iter()
+                .fold(0u64, |value, byte| (value << 8) | *byte as u64);
+
+            offset += width;
+
+            match operand {
+                Operand::Register if value >= REGISTERS_COUNT as u64 => {
+                    errors.push(VerifyError::InvalidRegister {
+                        address,
+                        opcode,
+                        index: value,
+                    });
+                }
+
+                Operand::Scale if !matches!(value, 1 | 2 | 4 | 8) => {
+                    errors.push(VerifyError::InvalidScale {
+                        address,
+                        opcode,
+                        scale: value,
+                    });
+                }
+
+                Operand::Constant(width)
+                    if value < data.start
+                        || value.checked_add(*width).is_none_or(|end| end > data.end) =>
+                {
+                    errors.push(VerifyError::InvalidReference {
+                        address,
+                        opcode,
+                        reference: value,
+                        width: *width,
+                        region: if text_start > 0 {
+                            "data section and constant pool"
+                        } else {
+                            "program"
+                        },
+                    });
+                }
+
+                Operand::Address if value > end => {
+                    errors.push(VerifyError::InvalidReference {
+                        address,
+                        opcode,
+                        reference: value,
+                        width: 0,
+                        region: "program",
+                    });
+                }
+
+                Operand::Target => targets.push((address, opcode, value)),
+
+                _ => {}
+            }
+        }
+
+        address += length;
+    }
+
+    // machine appends `halt` if program doesn't end with it
+    if program.last() != Some(&(Opcode::Halt as u8)) {
+        boundaries.insert(end);
+    }
+
+    for (address, opcode, target) in targets {
+        let reason = if target < text_start || target > end {
+            "outside of text section"
+        } else if !boundaries.contains(&target) {
+            "not an instruction boundary"
+        } else {
+            continue;
+        };
+
+        errors.push(VerifyError::InvalidTarget {
+            address,
+            opcode,
+            target,
+            reason,
+        });
+    }
+
+    if errors.is_empty() {
+        return Ok(());
+    }
+
+    errors.sort_by_key(VerifyError::address);
+    Err(errors)
+}
+
+fn operands(opcode: Opcode) -> &'static [Operand] {
+    use Operand::*;
+
+    match opcode {
+        Opcode::Halt
+        | Opcode::Return
+        | Opcode::FullReturn
+        | Opcode::InterruptReturn
+        | Opcode::Leave
+        | Opcode::DataSection
+        | Opcode::TextSection => &[],
+
+        Opcode::Call | Opcode::FullCall | Opcode::Jmp | Opcode::Jz | Opcode::Jnz => &[Target],
+
+        Opcode::Interrupt => &[Constant(1)],
+        Opcode::Enter => &[Constant(2)],
+        Opcode::Trap => &[Constant(8)],
+
+        Opcode::SetVector => &[Constant(1), Target],
+        Opcode::Je | Opcode::Jne => &[Constant(8), Target],
+        Opcode::JeReg | Opcode::JneReg => &[Constant(8), Register],
+
+        Opcode::SetFrame8
+        | Opcode::SetFrame16
+        | Opcode::SetFrame32
+        | Opcode::SetFrame64
+        | Opcode::Poke8
+        | Opcode::Poke16
+        | Opcode::Poke32
+        | Opcode::Poke64 => &[Constant(2), Register],
+
+        Opcode::Debug
+        | Opcode::CallReg
+        | Opcode::FullCallReg
+        | Opcode::JmpReg
+        | Opcode::JzReg
+        | Opcode::JnzReg
+        | Opcode::Push8
+        | Opcode::Push16
+        | Opcode::Push32
+        | Opcode::Push64
+        | Opcode::Pop8
+        | Opcode::Pop16
+        | Opcode::Pop32
+        | Opcode::Pop64 => &[Register],
+
+        Opcode::MovR2R
+        | Opcode::Load8
+        | Opcode::Load16
+        | Opcode::Load32
+        | Opcode::Load64
+        | Opcode::Store8
+        | Opcode::Store16
+        | Opcode::Store32
+        | Opcode::Store64
+        | Opcode::AddR2R
+        | Opcode::XAdd
+        | Opcode::SubR2R
+        | Opcode::MulR2R
+        | Opcode::DivR2R
+        | Opcode::CmpR2R => &[Register, Register],
+
+        Opcode::Lea => &[Register, Address],
+        Opcode::Assert => &[Register, Constant(8)],
+
+        Opcode::Mov8 | Opcode::Add8 | Opcode::Sub8 | Opcode::Mul8 | Opcode::Div8 | Opcode::Cmp8 => {
+            &[Register, Constant(1)]
+        }
+
+        Opcode::Mov16
+        | Opcode::Add16
+        | Opcode::Sub16
+        | Opcode::Mul16
+        | Opcode::Div16
+        | Opcode::Cmp16
+        | Opcode::Frame8
+        | Opcode::Frame16
+        | Opcode::Frame32
+        | Opcode::Frame64
+        | Opcode::Peek8
+        | Opcode::Peek16
+        | Opcode::Peek32
+        | Opcode::Peek64 => &[Register, Constant(2)],
+
+        Opcode::Mov32
+        | Opcode::Add32
+        | Opcode::Sub32
+        | Opcode::Mul32
+        | Opcode::Div32
+        | Opcode::Cmp32 => &[Register, Constant(4)],
+
+        Opcode::Mov64
+        | Opcode::Add64
+        | Opcode::Sub64
+        | Opcode::Mul64
+        | Opcode::Div64
+        | Opcode::Cmp64 => &[Register, Constant(8)],
+
+        Opcode::MemCopy | Opcode::MemSet | Opcode::MemCompare => &[Register, Register, Register],
+
+        Opcode::LoadOffset8
+        | Opcode::LoadOffset16
+        | Opcode::LoadOffset32
+        | Opcode::LoadOffset64 => &[Register, Register, Displacement],
+
+        Opcode::StoreOffset8
+        | Opcode::StoreOffset16
+        | Opcode::StoreOffset32
+        | Opcode::StoreOffset64 => &[Register, Displacement, Register],
+
+        Opcode::LoadIndexed8
+        | Opcode::LoadIndexed16
+        | Opcode::LoadIndexed32
+        | Opcode::LoadIndexed64 => &[Register, Register, Register, Scale],
+
+        Opcode::StoreIndexed8
+        | Opcode::StoreIndexed16
+        | Opcode::StoreIndexed32
+        | Opcode::StoreIndexed64 => &[Register, Register, Scale, Register],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::{R0, R1};
+
+    #[test]
+    fn verifier_layouts_test() {
+        // operand meanings must match encoded widths of all opcodes
+        for byte in 0..=u8::MAX {
+            let Ok(opcode) = Opcode::try_from(byte) else {
+                continue;
+            };
+
+            let widths = operands(opcode)
+                .iter()
+                .map(|operand| match operand {
+                    Operand::Register | Operand::Scale => 1,
+                    _ => 8,
+                })
+                .collect::<Vec<u8>>();
+
+            assert_eq!(widths, opcode.operands(), "{opcode:?}");
+        }
+    }
+
+    #[test]
+    fn verifier_valid_program_test() {
+        #[rustfmt::skip]
+        let program = [
+            Opcode::DataSection as u8,
+            // 1: constant
+            7,
+            0xff,
+            Opcode::TextSection as u8,
+            // 4: entry jump to 13
+            0xff, 0, 0, 0, 0, 0, 0, 0, 13,
+            // 13: mov %r0, $7 (constant at 1)
+            Opcode::Mov8 as u8, R0 as u8, 0, 0, 0, 0, 0, 0, 0, 1,
+            // 23: jz 13
+            Opcode::Jz as u8, 0, 0, 0, 0, 0, 0, 0, 13,
+            // 32: jmp to appended `halt`
+            Opcode::Jmp as u8, 0, 0, 0, 0, 0, 0, 0, 41,
+        ];
+
+        assert_eq!(verify(&program), Ok(()));
+    }
+
+    #[test]
+    fn verifier_errors_test() {
+        #[rustfmt::skip]
+        let program = [
+            Opcode::DataSection as u8,
+            7,
+            0xff,
+            Opcode::TextSection as u8,
+            // 4: mov %r15, $7 (constant in text section)
+            Opcode::Mov8 as u8, 15, 0, 0, 0, 0, 0, 0, 0, 20,
+            // 14: jmp into data section
+            Opcode::Jmp as u8, 0, 0, 0, 0, 0, 0, 0, 1,
+            // 23: call into middle of `mov`
+            Opcode::Call as u8, 0, 0, 0, 0, 0, 0, 0, 6,
+            // 32: invalid opcode
+            0xee,
+            // 33: load8 %r0, [%r1 + %r0 * $3]
+            Opcode::LoadIndexed8 as u8, R0 as u8, R1 as u8, R0 as u8, 3,
+            // 38: truncated `jmp`
+            Opcode::Jmp as u8, 0, 0,
+        ];
+
+        assert_eq!(
+            verify(&program),
+            Err(vec![
+                VerifyError::InvalidRegister {
+                    address: 4,
+                    opcode: Opcode::Mov8,
+                    index: 15,
+                },
+                VerifyError::InvalidReference {
+                    address: 4,
+                    opcode: Opcode::Mov8,
+                    reference: 20,
+                    width: 1,
+                    region: "data section and constant pool",
+                },
+                VerifyError::InvalidTarget {
+                    address: 14,
+                    opcode: Opcode::Jmp,
+                    target: 1,
+                    reason: "outside of text section",
+                },
+                VerifyError::InvalidTarget {
+                    address: 23,
+                    opcode: Opcode::Call,
+                    target: 6,
+                    reason: "not an instruction boundary",
+                },
+                VerifyError::InvalidOpcode {
+                    address: 32,
+                    opcode: 0xee,
+                },
+                VerifyError::InvalidScale {
+                    address: 33,
+                    opcode: Opcode::LoadIndexed8,
+                    scale: 3,
+                },
+                VerifyError::TruncatedInstruction {
+                    address: 38,
+                    opcode: Opcode::Jmp,
+                    length: 9,
+                },
+            ])
+        );
+
+        assert_eq!(
+            verify(&[Opcode::DataSection as u8, 0]),
+            Err(vec![VerifyError::NoTextSection])
+        );
+    }
+}